@@ -14,15 +14,22 @@
 // You should have received a copy of the GNU General Public License
 // along with this program.  If not, see <https://www.gnu.org/licenses/>.
 
+use crate::bool_matrix::BoolMatrix;
+pub use crate::bool_matrix::WORD_BITS;
+use crate::channel_reduction::ChannelReduction;
+use crate::labeling::{label_components, BoundingBox, Component};
+use crate::skeletonizers::AdjacencyMode;
 use image::GenericImageView;
 use image::Pixel;
 use image::RgbImage;
 use num_traits::Bounded;
 use std::convert::TryInto;
 
+// Pixels are stored one bit per pixel, a set bit meaning foreground. Every row
+// starts at a word boundary, bit `i` of word `k` holding the pixel `64 * k + i`.
 #[derive(Debug, Clone)]
 pub struct BinaryImage {
-    image: Vec<Vec<PixelColor>>,
+    pixels: BoolMatrix,
     bg_color: PixelColor,
     fg_color: PixelColor,
 }
@@ -33,55 +40,75 @@ pub enum PixelColor {
     White,
 }
 
+impl PixelColor {
+    pub fn opposite(self) -> PixelColor {
+        match self {
+            PixelColor::Black => PixelColor::White,
+            PixelColor::White => PixelColor::Black,
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Polarity {
+    BlackForeground,
+    WhiteForeground,
+    // The color with fewer pixels is the foreground, black on a tie.
+    Auto,
+}
+
 // todo: add unchecked
 impl BinaryImage {
     pub fn new(width: usize, height: usize, bg: PixelColor) -> Self {
         BinaryImage {
-            image: vec![vec![bg; width]; height],
+            pixels: BoolMatrix::new(width, height, false),
             bg_color: bg,
-            fg_color: if bg == PixelColor::Black {
-                PixelColor::White
-            } else {
-                PixelColor::Black
-            },
+            fg_color: bg.opposite(),
         }
     }
 
     pub fn from_image<ImgView>(image_view: &ImgView, bg_color: PixelColor) -> Self
+    where
+        ImgView: GenericImageView,
+    {
+        Self::from_image_with_reduction(image_view, bg_color, ChannelReduction::default())
+    }
+
+    // A pixel is white if its reduced value is non-zero.
+    pub fn from_image_with_reduction<ImgView>(
+        image_view: &ImgView,
+        bg_color: PixelColor,
+        reduction: ChannelReduction,
+    ) -> Self
     where
         ImgView: GenericImageView,
     {
         let height = image_view.height().try_into().unwrap();
         let width = image_view.width().try_into().unwrap();
 
-        let mut image = vec![vec![PixelColor::Black; width]; height];
+        let fg_color = bg_color.opposite();
+
+        let mut pixels = BoolMatrix::new(width, height, false);
 
         for y in 0..image_view.height() {
             for x in 0..image_view.width() {
                 let pixel = image_view.get_pixel(x, y);
-                let mut is_zero = true;
-                for channel in pixel.channels() {
-                    let channel_value: u32 = num_traits::cast(*channel).unwrap();
-                    if channel_value != 0 {
-                        is_zero = false;
-                        break;
-                    }
-                }
+                let is_zero = reduction.reduce(&pixel) == 0.0;
+
+                let color = if is_zero {
+                    PixelColor::Black
+                } else {
+                    PixelColor::White
+                };
 
-                if !is_zero {
-                    image[y as usize][x as usize] = PixelColor::White;
+                if color == fg_color {
+                    pixels.set(x as usize, y as usize);
                 }
             }
         }
 
-        let fg_color = if bg_color == PixelColor::Black {
-            PixelColor::White
-        } else {
-            PixelColor::Black
-        };
-
         BinaryImage {
-            image,
+            pixels,
             bg_color,
             fg_color,
         }
@@ -107,11 +134,31 @@ impl BinaryImage {
     }
 
     pub fn width(&self) -> usize {
-        self.image[0].len()
+        self.pixels.width()
     }
 
     pub fn height(&self) -> usize {
-        self.image.len()
+        self.pixels.height()
+    }
+
+    pub fn words_per_row(&self) -> usize {
+        self.pixels.words_per_row()
+    }
+
+    pub fn row_words(&self, y: usize) -> &[u64] {
+        self.pixels.row(y)
+    }
+
+    pub fn get_word(&self, word_x: usize, y: usize) -> u64 {
+        self.pixels.word(word_x, y)
+    }
+
+    pub fn set_word(&mut self, word_x: usize, y: usize, fg_bits: u64) {
+        self.pixels.set_word(word_x, y, fg_bits);
+    }
+
+    pub fn count_fg(&self) -> usize {
+        self.pixels.count_ones()
     }
 
     pub fn get_bg_color(&self) -> PixelColor {
@@ -122,12 +169,49 @@ impl BinaryImage {
         self.fg_color
     }
 
+    // Swaps the foreground and the background if needed, the color of every
+    // pixel stays the same.
+    pub fn set_bg_color(&mut self, bg_color: PixelColor) {
+        if bg_color != self.bg_color {
+            self.pixels.invert();
+            std::mem::swap(&mut self.bg_color, &mut self.fg_color);
+        }
+    }
+
+    pub fn set_polarity(&mut self, polarity: Polarity) {
+        let fg_color = match polarity {
+            Polarity::BlackForeground => PixelColor::Black,
+            Polarity::WhiteForeground => PixelColor::White,
+            Polarity::Auto => {
+                let pixels_count = self.width() * self.height();
+                let white_count = if self.fg_color == PixelColor::White {
+                    self.count_fg()
+                } else {
+                    pixels_count - self.count_fg()
+                };
+
+                if white_count * 2 < pixels_count {
+                    PixelColor::White
+                } else {
+                    PixelColor::Black
+                }
+            }
+        };
+
+        self.set_bg_color(fg_color.opposite());
+    }
+
+    // Turns every white pixel black and every black pixel white.
+    pub fn invert(&mut self) {
+        self.pixels.invert();
+    }
+
     pub fn is_fg(&self, x: usize, y: usize) -> bool {
-        self.get_color(x, y) == self.fg_color
+        self.pixels.check(x, y)
     }
 
     pub fn is_bg(&self, x: usize, y: usize) -> bool {
-        self.get_color(x, y) == self.bg_color
+        !self.pixels.check(x, y)
     }
 
     pub fn is_fg_or_default(&self, x: i32, y: i32) -> bool {
@@ -139,38 +223,108 @@ impl BinaryImage {
     }
 
     pub fn set_fg(&mut self, x: usize, y: usize) {
-        self.set_color(x, y, self.fg_color);
+        self.pixels.set(x, y);
     }
 
     pub fn set_bg(&mut self, x: usize, y: usize) {
-        self.set_color(x, y, self.bg_color);
+        self.pixels.unset(x, y);
     }
 
     pub fn set_color(&mut self, x: usize, y: usize, color: PixelColor) {
-        self.image[y][x] = color;
+        self.pixels.set_value(x, y, color == self.fg_color);
     }
 
     pub fn get_color(&self, x: usize, y: usize) -> PixelColor {
-        self.image[y][x]
+        if self.pixels.check(x, y) {
+            self.fg_color
+        } else {
+            self.bg_color
+        }
     }
 
     pub fn get_color_or_default(&self, x: i32, y: i32) -> PixelColor {
-        if x < 0 || y < 0 {
+        if x < 0 || y < 0 || x as usize >= self.width() || y as usize >= self.height() {
             return self.bg_color;
         }
 
-        self.image
-            .get(y as usize)
-            .and_then(|row| row.get(x as usize))
-            .copied()
-            .unwrap_or(self.bg_color)
+        self.get_color(x as usize, y as usize)
     }
 
     pub fn fill(&mut self, color: PixelColor) {
+        self.pixels.fill(color == self.fg_color);
+    }
+
+    // Fills the background regions that do not touch the image border, only
+    // those of at most `max_area` pixels if it is given. The adjacency is the
+    // one of the foreground, the background uses the opposite one.
+    pub fn fill_holes(&mut self, max_area: Option<usize>, mode: AdjacencyMode) {
+        let mut background = self.clone();
+        background.invert();
+
+        let labeling = label_components(&background, mode.opposite());
+        let max_area = max_area.unwrap_or(usize::MAX);
+        let is_hole: Vec<bool> = labeling
+            .components()
+            .map(|component| {
+                !touches_border(component.bounding_box(), self.width(), self.height())
+                    && component.area() <= max_area
+            })
+            .collect();
+
         for (x, y) in self.pixels_iter() {
-            self.set_color(x, y, color);
+            let label = labeling.label(x, y);
+            if label != 0 && is_hole[label as usize - 1] {
+                self.set_fg(x, y);
+            }
         }
     }
+
+    // Removes the foreground components that touch the image border.
+    pub fn clear_border(&mut self, mode: AdjacencyMode) {
+        let (width, height) = (self.width(), self.height());
+        self.retain_components(mode, |component| {
+            !touches_border(component.bounding_box(), width, height)
+        });
+    }
+
+    // Removes the foreground components with fewer than `min_area` pixels,
+    // returns the number of removed components.
+    pub fn area_open(&mut self, min_area: usize, mode: AdjacencyMode) -> usize {
+        self.retain_components(mode, |component| component.area() >= min_area)
+    }
+
+    // Keeps only the foreground components for which `predicate` holds,
+    // returns the number of removed components.
+    pub fn retain_components<F>(&mut self, mode: AdjacencyMode, predicate: F) -> usize
+    where
+        F: Fn(&Component) -> bool,
+    {
+        let labeling = label_components(self, mode);
+        let is_removed: Vec<bool> = labeling
+            .components()
+            .map(|component| !predicate(component))
+            .collect();
+
+        if !is_removed.contains(&true) {
+            return 0;
+        }
+
+        for (x, y) in self.pixels_iter() {
+            let label = labeling.label(x, y);
+            if label != 0 && is_removed[label as usize - 1] {
+                self.set_bg(x, y);
+            }
+        }
+
+        is_removed.iter().filter(|&&removed| removed).count()
+    }
+}
+
+fn touches_border(bounding_box: BoundingBox, width: usize, height: usize) -> bool {
+    bounding_box.x() == 0
+        || bounding_box.y() == 0
+        || bounding_box.x() + bounding_box.width() == width
+        || bounding_box.y() + bounding_box.height() == height
 }
 
 pub struct PixelIterator {
@@ -182,11 +336,15 @@ pub struct PixelIterator {
 
 impl PixelIterator {
     pub fn new(image: &BinaryImage) -> PixelIterator {
+        PixelIterator::with_size(image.width(), image.height())
+    }
+
+    pub fn with_size(width: usize, height: usize) -> PixelIterator {
         PixelIterator {
             current_x: 0,
             current_y: 0,
-            width: image.width(),
-            height: image.height(),
+            width,
+            height,
         }
     }
 }
@@ -196,12 +354,12 @@ impl Iterator for PixelIterator {
 
     fn next(&mut self) -> Option<Self::Item> {
         if self.current_x == self.width {
-            if self.current_y >= self.height - 1 {
-                return Option::None;
-            } else {
-                self.current_y += 1;
-                self.current_x = 0;
-            }
+            self.current_y += 1;
+            self.current_x = 0;
+        }
+
+        if self.width == 0 || self.current_y >= self.height {
+            return Option::None;
         }
 
         let ret = (self.current_x, self.current_y);
@@ -210,3 +368,299 @@ impl Iterator for PixelIterator {
         Option::Some(ret)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::skeletonizers::test_shapes::{from_ascii, to_ascii};
+    use image::Rgb;
+
+    #[test]
+    fn from_image_test() {
+        // Arrange
+        let mut rgb_image = RgbImage::new(2, 1);
+        rgb_image.put_pixel(1, 0, Rgb([0, 0, 1]));
+
+        // Act
+        let image = BinaryImage::from_image(&rgb_image, PixelColor::White);
+
+        // Assert
+        assert_eq!(PixelColor::Black, image.get_color(0, 0));
+        assert_eq!(PixelColor::White, image.get_color(1, 0));
+        assert!(image.is_fg(0, 0));
+        assert!(image.is_bg(1, 0));
+    }
+
+    #[test]
+    fn from_image_with_reduction_test() {
+        // Arrange
+        let mut rgb_image = RgbImage::new(2, 1);
+        rgb_image.put_pixel(0, 0, Rgb([0, 0, 1]));
+        rgb_image.put_pixel(1, 0, Rgb([1, 0, 0]));
+        let reduction = ChannelReduction::Channel(crate::ColorChannel::Red);
+
+        // Act
+        let image =
+            BinaryImage::from_image_with_reduction(&rgb_image, PixelColor::White, reduction);
+
+        // Assert
+        assert_eq!(PixelColor::Black, image.get_color(0, 0));
+        assert_eq!(PixelColor::White, image.get_color(1, 0));
+    }
+
+    #[test]
+    fn to_rgb_image_test() {
+        // Arrange
+        let mut image = BinaryImage::new(2, 1, PixelColor::Black);
+        image.set_fg(0, 0);
+
+        // Act
+        let rgb_image = image.to_rgb_image();
+
+        // Assert
+        assert_eq!(Rgb([0, 0, 0]), *rgb_image.get_pixel(0, 0));
+        assert_eq!(Rgb([255, 255, 255]), *rgb_image.get_pixel(1, 0));
+    }
+
+    #[test]
+    fn invert_test() {
+        // Arrange
+        let mut image = BinaryImage::new(2, 1, PixelColor::White);
+        image.set_fg(0, 0);
+
+        // Act
+        image.invert();
+
+        // Assert
+        assert_eq!(PixelColor::White, image.get_color(0, 0));
+        assert_eq!(PixelColor::Black, image.get_color(1, 0));
+        assert_eq!(PixelColor::White, image.get_bg_color());
+    }
+
+    #[test]
+    fn set_bg_color_test() {
+        // Arrange
+        let mut image = BinaryImage::new(2, 1, PixelColor::White);
+        image.set_fg(0, 0);
+
+        // Act
+        image.set_bg_color(PixelColor::Black);
+
+        // Assert
+        assert_eq!(PixelColor::Black, image.get_color(0, 0));
+        assert_eq!(PixelColor::White, image.get_color(1, 0));
+        assert!(image.is_bg(0, 0));
+        assert!(image.is_fg(1, 0));
+    }
+
+    #[test]
+    fn set_polarity_auto_test() {
+        // Arrange
+        let mut image = BinaryImage::new(3, 1, PixelColor::Black);
+        image.set_color(0, 0, PixelColor::Black);
+        image.set_color(1, 0, PixelColor::Black);
+        image.set_color(2, 0, PixelColor::White);
+
+        // Act
+        image.set_polarity(Polarity::Auto);
+
+        // Assert
+        assert_eq!(PixelColor::White, image.get_fg_color());
+        assert!(image.is_fg(2, 0));
+        assert_eq!(1, image.count_fg());
+    }
+
+    #[test]
+    fn get_color_or_default_outside_test() {
+        // Arrange
+        let mut image = BinaryImage::new(2, 2, PixelColor::White);
+        image.fill(PixelColor::Black);
+
+        // Act & Assert
+        assert_eq!(PixelColor::Black, image.get_color_or_default(1, 1));
+        assert_eq!(PixelColor::White, image.get_color_or_default(-1, 0));
+        assert_eq!(PixelColor::White, image.get_color_or_default(2, 0));
+        assert_eq!(PixelColor::White, image.get_color_or_default(0, 2));
+    }
+
+    #[test]
+    fn words_test() {
+        // Arrange
+        let mut image = BinaryImage::new(130, 2, PixelColor::White);
+
+        // Act
+        image.set_fg(0, 1);
+        image.set_fg(129, 1);
+
+        // Assert
+        assert_eq!(3, image.words_per_row());
+        assert_eq!(&[0, 0, 0], image.row_words(0));
+        assert_eq!(&[1, 0, 0b10], image.row_words(1));
+        assert_eq!(2, image.count_fg());
+    }
+
+    #[test]
+    fn pixels_iter_empty_test() {
+        // Arrange
+        let image = BinaryImage::new(0, 0, PixelColor::White);
+
+        // Act & Assert
+        assert_eq!(0, image.width());
+        assert_eq!(0, image.pixels_iter().count());
+    }
+
+    #[test]
+    fn pixels_iter_test() {
+        // Arrange
+        let image = BinaryImage::new(3, 2, PixelColor::White);
+
+        // Act
+        let pixels: Vec<_> = image.pixels_iter().collect();
+
+        // Assert
+        assert_eq!(vec![(0, 0), (1, 0), (2, 0), (0, 1), (1, 1), (2, 1)], pixels);
+    }
+
+    #[test]
+    fn fill_holes_test() {
+        // Arrange
+        let rows = [
+            "........", //
+            ".######.", //
+            ".#.##..#", //
+            ".#.##..#", //
+            ".######.", //
+            "........", //
+        ];
+        let mut all_holes = from_ascii(&rows);
+        let mut small_holes = from_ascii(&rows);
+
+        // Act
+        all_holes.fill_holes(None, AdjacencyMode::Eight);
+        small_holes.fill_holes(Some(2), AdjacencyMode::Eight);
+
+        // Assert
+        let expected_all = [
+            "........", //
+            ".######.", //
+            ".#######", //
+            ".#######", //
+            ".######.", //
+            "........", //
+        ];
+        let expected_small = [
+            "........", //
+            ".######.", //
+            ".####..#", //
+            ".####..#", //
+            ".######.", //
+            "........", //
+        ];
+        assert_eq!(expected_all.to_vec(), to_ascii(&all_holes));
+        assert_eq!(expected_small.to_vec(), to_ascii(&small_holes));
+    }
+
+    #[test]
+    fn fill_holes_adjacency_test() {
+        // Arrange
+        let rows = [
+            "....", //
+            ".##.", //
+            "#..#", //
+            ".##.", //
+        ];
+        let mut four = from_ascii(&rows);
+        let mut eight = from_ascii(&rows);
+
+        // Act
+        four.fill_holes(None, AdjacencyMode::Four);
+        eight.fill_holes(None, AdjacencyMode::Eight);
+
+        // Assert
+        assert_eq!(to_ascii(&from_ascii(&rows)), to_ascii(&four));
+        assert_eq!(vec!["....", ".##.", "####", ".##."], to_ascii(&eight));
+    }
+
+    #[test]
+    fn clear_border_test() {
+        // Arrange
+        let rows = [
+            "#.....", //
+            ".#..#.", //
+            "....#.", //
+            "......", //
+            "##..#.", //
+        ];
+        let mut four = from_ascii(&rows);
+        let mut eight = from_ascii(&rows);
+
+        // Act
+        four.clear_border(AdjacencyMode::Four);
+        eight.clear_border(AdjacencyMode::Eight);
+
+        // Assert
+        assert_eq!(
+            vec!["......", ".#..#.", "....#.", "......", "......"],
+            to_ascii(&four)
+        );
+        assert_eq!(
+            vec!["......", "....#.", "....#.", "......", "......"],
+            to_ascii(&eight)
+        );
+    }
+
+    #[test]
+    fn area_open_test() {
+        // Arrange
+        let mut image = from_ascii(&[
+            "#.....##", //
+            "......##", //
+            "..#.....", //
+            "...#.###", //
+            ".....###", //
+        ]);
+
+        // Act
+        let removed = image.area_open(3, AdjacencyMode::Four);
+
+        // Assert
+        let expected = [
+            "......##", //
+            "......##", //
+            "........", //
+            ".....###", //
+            ".....###", //
+        ];
+        assert_eq!(3, removed);
+        assert_eq!(expected.to_vec(), to_ascii(&image));
+    }
+
+    #[test]
+    fn retain_components_test() {
+        // Arrange
+        let mut image = from_ascii(&[
+            "####....#", //
+            ".......#.", //
+            "..##..#..", //
+            "..##.....", //
+            ".........", //
+        ]);
+
+        // Act
+        let removed = image.retain_components(AdjacencyMode::Eight, |component| {
+            let ratio = component.bounding_box().aspect_ratio();
+            (0.5..=2.0).contains(&ratio)
+        });
+
+        // Assert
+        let expected = [
+            "........#", //
+            ".......#.", //
+            "..##..#..", //
+            "..##.....", //
+            ".........", //
+        ];
+        assert_eq!(1, removed);
+        assert_eq!(expected.to_vec(), to_ascii(&image));
+    }
+}
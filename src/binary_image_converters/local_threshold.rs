@@ -0,0 +1,255 @@
+// local_threshold.rs - Integral images for local thresholding converters
+// Copyright (C) 2019 Denis Karpovskiy
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use crate::binary_image::{BinaryImage, PixelColor};
+use crate::channel_reduction::ChannelReduction;
+use crate::progress::{report, Cancelled, Progress};
+use image::GenericImageView;
+
+// The square window the local statistics are taken over and the reduction
+// turning its pixels into the luminance they are computed from.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LocalWindow {
+    size: u32,
+    reduction: ChannelReduction,
+}
+
+impl LocalWindow {
+    pub fn new(size: u32, reduction: ChannelReduction) -> Self {
+        LocalWindow { size, reduction }
+    }
+}
+
+// Calls `visit_row` for every row, top to bottom, with the luminance of its
+// pixels and the mean and the standard deviation of the window centred on
+// each of them. The window is clipped by the image borders. The sums of the
+// luminance and its square are kept per column over the rows inside of the
+// window and slid down one row at a time, so only those rows are in memory.
+pub fn for_each_row<Img, V>(
+    image: &Img,
+    window: LocalWindow,
+    mut visit_row: V,
+) -> Result<(), Cancelled>
+where
+    Img: GenericImageView,
+    V: FnMut(u32, &[f64], &[(f64, f64)]) -> Result<(), Cancelled>,
+{
+    let width = image.width() as usize;
+    let height = image.height() as usize;
+    let half = window.size.max(1) as usize / 2;
+
+    // row `y` is kept at `y % rows.len()` until it leaves the window
+    let mut rows = vec![vec![0.0; width]; (2 * half + 1).min(height.max(1))];
+    let mut column_sums = vec![0.0; width];
+    let mut column_squares = vec![0.0; width];
+    let mut row_sums = vec![0.0; width + 1];
+    let mut row_squares = vec![0.0; width + 1];
+    let mut statistics = vec![(0.0, 0.0); width];
+
+    let mut first_summed_row = 0;
+    let mut next_row = 0;
+    for y in 0..height {
+        let top = y.saturating_sub(half);
+        let bottom = (y + half + 1).min(height);
+
+        while first_summed_row < top {
+            let row = &rows[first_summed_row % rows.len()];
+            for x in 0..width {
+                column_sums[x] -= row[x];
+                column_squares[x] -= row[x] * row[x];
+            }
+            first_summed_row += 1;
+        }
+
+        while next_row < bottom {
+            let index = next_row % rows.len();
+            for x in 0..width {
+                let value = window
+                    .reduction
+                    .reduce(&image.get_pixel(x as u32, next_row as u32));
+                rows[index][x] = value;
+                column_sums[x] += value;
+                column_squares[x] += value * value;
+            }
+            next_row += 1;
+        }
+
+        for x in 0..width {
+            row_sums[x + 1] = row_sums[x] + column_sums[x];
+            row_squares[x + 1] = row_squares[x] + column_squares[x];
+        }
+
+        for (x, pixel_statistics) in statistics.iter_mut().enumerate() {
+            let left = x.saturating_sub(half);
+            let right = (x + half + 1).min(width);
+
+            let area = ((right - left) * (bottom - top)) as f64;
+            let mean = (row_sums[right] - row_sums[left]) / area;
+            let variance = ((row_squares[right] - row_squares[left]) / area - mean * mean).max(0.0);
+
+            *pixel_statistics = (mean, variance.sqrt());
+        }
+
+        visit_row(y as u32, &rows[y % rows.len()], &statistics)?;
+    }
+
+    Ok(())
+}
+
+pub fn compute_max_progress(_width: u32, height: u32, passes: u32) -> u32 {
+    height * passes
+}
+
+// Makes every pixel whose luminance is greater than its local threshold white
+// and every other pixel black. The progress goes on from `current_progress`,
+// one step per row.
+pub fn apply_local_threshold<Img, F, T>(
+    image: &Img,
+    bg_color: PixelColor,
+    window: LocalWindow,
+    report_progress: &F,
+    mut current_progress: i32,
+    max_progress: i32,
+    compute_threshold: T,
+) -> Result<BinaryImage, Cancelled>
+where
+    Img: GenericImageView,
+    F: Fn(i32, i32) -> Progress,
+    T: Fn(f64, f64) -> f64,
+{
+    let mut binary_image =
+        BinaryImage::new(image.width() as usize, image.height() as usize, bg_color);
+
+    for_each_row(image, window, |y, luminances, statistics| {
+        for (x, (&luminance, &(mean, deviation))) in
+            luminances.iter().zip(statistics.iter()).enumerate()
+        {
+            let color = if luminance > compute_threshold(mean, deviation) {
+                PixelColor::White
+            } else {
+                PixelColor::Black
+            };
+            binary_image.set_color(x, y as usize, color);
+        }
+
+        current_progress += 1;
+        report(report_progress, current_progress, max_progress)
+    })?;
+
+    report_progress(max_progress, max_progress);
+
+    Ok(binary_image)
+}
+
+// Dark vertical strokes on a background getting brighter from left to right.
+#[cfg(test)]
+pub mod test_images {
+    use image::{GrayImage, Luma};
+
+    pub fn is_stroke(x: u32) -> bool {
+        x % 10 == 5
+    }
+
+    pub fn uneven_lighting_image() -> GrayImage {
+        GrayImage::from_fn(40, 40, |x, _| {
+            let background = 60 + 2 * x as u8;
+            if is_stroke(x) {
+                Luma([background / 2])
+            } else {
+                Luma([background])
+            }
+        })
+    }
+
+    pub fn assert_strokes_found(image: &GrayImage) {
+        for (x, y, pixel) in image.enumerate_pixels() {
+            let expected = if is_stroke(x) { 0 } else { 255 };
+            assert_eq!(expected, pixel[0], "at ({}, {})", x, y);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::{GrayImage, Luma};
+
+    fn collect_statistics(image: &GrayImage, window_size: u32) -> Vec<Vec<(f64, f64)>> {
+        let mut rows = Vec::new();
+        let window = LocalWindow::new(window_size, ChannelReduction::Rec709Luma);
+        for_each_row(image, window, |_, _, statistics| {
+            rows.push(statistics.to_vec());
+            Ok(())
+        })
+        .unwrap();
+
+        rows
+    }
+
+    #[test]
+    fn mean_and_deviation_test() {
+        // Arrange
+        let image = GrayImage::from_fn(3, 3, |x, _| Luma([x as u8 * 10]));
+
+        // Act
+        let (mean, deviation) = collect_statistics(&image, 3)[1][1];
+
+        // Assert
+        assert!((mean - 10.0).abs() < 1e-9);
+        assert!((deviation - (200.0f64 / 3.0).sqrt()).abs() < 1e-9);
+    }
+
+    #[test]
+    fn mean_and_deviation_borders_test() {
+        // Arrange
+        let image = GrayImage::from_fn(3, 3, |x, _| Luma([x as u8 * 10]));
+
+        // Act
+        let (mean, _) = collect_statistics(&image, 3)[0][0];
+
+        // Assert
+        assert!((mean - 5.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn sliding_window_test() {
+        // Arrange
+        let image = GrayImage::from_fn(7, 9, |x, y| Luma([((x * 37 + y * 91) % 256) as u8]));
+        let window_size = 5;
+
+        // Act
+        let rows = collect_statistics(&image, window_size);
+
+        // Assert
+        for (y, row) in rows.iter().enumerate() {
+            for (x, &(mean, deviation)) in row.iter().enumerate() {
+                let values: Vec<f64> = (y.saturating_sub(2)..(y + 3).min(9))
+                    .flat_map(|wy| (x.saturating_sub(2)..(x + 3).min(7)).map(move |wx| (wx, wy)))
+                    .map(|(wx, wy)| image.get_pixel(wx as u32, wy as u32)[0] as f64)
+                    .collect();
+                let expected_mean = values.iter().sum::<f64>() / values.len() as f64;
+                let expected_variance = values
+                    .iter()
+                    .map(|value| (value - expected_mean).powi(2))
+                    .sum::<f64>()
+                    / values.len() as f64;
+
+                assert!((mean - expected_mean).abs() < 1e-6);
+                assert!((deviation - expected_variance.sqrt()).abs() < 1e-6);
+            }
+        }
+    }
+}
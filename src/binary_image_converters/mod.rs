@@ -14,17 +14,94 @@
 // You should have received a copy of the GNU General Public License
 // along with this program.  If not, see <https://www.gnu.org/licenses/>.
 
+mod local_threshold;
+pub mod mean_c_binary_image_converter;
+pub mod niblack_binary_image_converter;
+pub mod otsu_binary_image_converter;
+pub mod sauvola_binary_image_converter;
 pub mod threshold_binary_image_converter;
-use image::{GenericImage, Pixel};
+pub mod wolf_binary_image_converter;
+use crate::binary_image::{BinaryImage, PixelColor};
+use crate::progress::{Cancelled, Progress};
+use crate::roi::Roi;
+use image::{GenericImage, GenericImageView, Pixel};
+use num_traits::{Bounded, Zero};
 
 pub trait BinaryImageConverter {
     fn convert_to_binary<Img, Pix>(&self, image: &mut Img)
     where
         Pix: Pixel,
-        Img: GenericImage<Pixel = Pix>;
-    fn convert_to_binary_with_progress<Img, Pix, F>(&self, image: &mut Img, report_progress: F)
+        Img: GenericImage<Pixel = Pix>,
+    {
+        let _ = self.convert_to_binary_with_progress(image, None, |_, _| Progress::Continue);
+    }
+
+    fn convert_to_binary_in_roi<Img, Pix>(&self, image: &mut Img, roi: &Roi)
+    where
+        Pix: Pixel,
+        Img: GenericImage<Pixel = Pix>,
+    {
+        let _ = self.convert_to_binary_with_progress(image, Some(roi), |_, _| Progress::Continue);
+    }
+
+    // Writes the result back into the image: white pixels get the maximum
+    // value in every channel and black pixels get zero. Only the pixels
+    // inside of `roi` are written, the local thresholds near its edge still
+    // see the pixels around it. A cancelled conversion leaves the image as
+    // it was.
+    fn convert_to_binary_with_progress<Img, Pix, F>(
+        &self,
+        image: &mut Img,
+        roi: Option<&Roi>,
+        report_progress: F,
+    ) -> Result<(), Cancelled>
     where
         Img: GenericImage<Pixel = Pix>,
         Pix: Pixel,
-        F: Fn(i32, i32);
+        F: Fn(i32, i32) -> Progress,
+    {
+        let binary_image =
+            self.convert_to_binary_image_with_progress(image, PixelColor::Black, report_progress)?;
+
+        let max = <Pix::Subpixel as Bounded>::max_value();
+        let zero = <Pix::Subpixel as Zero>::zero();
+        for (x, y) in binary_image.pixels_iter() {
+            if roi.is_some_and(|roi| !roi.contains(x, y)) {
+                continue;
+            }
+
+            let pixel = image.get_pixel_mut(x as u32, y as u32);
+            if binary_image.get_color(x, y) == PixelColor::White {
+                pixel.apply(|_| max);
+            } else {
+                pixel.apply(|_| zero);
+            }
+        }
+
+        Ok(())
+    }
+
+    fn convert_to_binary_image<Img>(&self, image: &Img, bg_color: PixelColor) -> BinaryImage
+    where
+        Img: GenericImageView,
+    {
+        match self.convert_to_binary_image_with_progress(image, bg_color, |_, _| Progress::Continue)
+        {
+            Ok(binary_image) => binary_image,
+            // without a callback that asks for it the conversion is never cancelled
+            Err(Cancelled) => {
+                BinaryImage::new(image.width() as usize, image.height() as usize, bg_color)
+            }
+        }
+    }
+
+    fn convert_to_binary_image_with_progress<Img, F>(
+        &self,
+        image: &Img,
+        bg_color: PixelColor,
+        report_progress: F,
+    ) -> Result<BinaryImage, Cancelled>
+    where
+        Img: GenericImageView,
+        F: Fn(i32, i32) -> Progress;
 }
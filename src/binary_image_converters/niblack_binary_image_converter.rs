@@ -0,0 +1,95 @@
+// niblack_binary_image_converter.rs - Converts to binary using the Niblack method
+// Copyright (C) 2019 Denis Karpovskiy
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use super::local_threshold::{apply_local_threshold, compute_max_progress, LocalWindow};
+use super::BinaryImageConverter;
+use crate::binary_image::{BinaryImage, PixelColor};
+use crate::channel_reduction::ChannelReduction;
+use crate::progress::{Cancelled, Progress};
+use image::GenericImageView;
+use std::convert::TryInto;
+
+pub struct NiblackBinaryImageConverter {
+    window_size: u32,
+    k: f64,
+    reduction: ChannelReduction,
+}
+
+impl NiblackBinaryImageConverter {
+    pub fn new(window_size: u32, k: f64) -> Self {
+        Self::with_reduction(window_size, k, ChannelReduction::Rec709Luma)
+    }
+
+    pub fn with_reduction(window_size: u32, k: f64, reduction: ChannelReduction) -> Self {
+        NiblackBinaryImageConverter {
+            window_size,
+            k,
+            reduction,
+        }
+    }
+}
+
+impl BinaryImageConverter for NiblackBinaryImageConverter {
+    fn convert_to_binary_image_with_progress<Img, F>(
+        &self,
+        image: &Img,
+        bg_color: PixelColor,
+        report_progress: F,
+    ) -> Result<BinaryImage, Cancelled>
+    where
+        Img: GenericImageView,
+        F: Fn(i32, i32) -> Progress,
+    {
+        let max_progress: i32 = compute_max_progress(image.width(), image.height(), 1)
+            .try_into()
+            .unwrap();
+
+        apply_local_threshold(
+            image,
+            bg_color,
+            LocalWindow::new(self.window_size, self.reduction),
+            &report_progress,
+            0,
+            max_progress,
+            |mean, deviation| mean + self.k * deviation,
+        )
+    }
+}
+
+impl Default for NiblackBinaryImageConverter {
+    fn default() -> Self {
+        NiblackBinaryImageConverter::new(15, -0.2)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::binary_image_converters::local_threshold::test_images::*;
+
+    #[test]
+    fn niblack_uneven_lighting_test() {
+        // Arrange
+        let mut image = uneven_lighting_image();
+        let converter = NiblackBinaryImageConverter::new(15, -0.5);
+
+        // Act
+        converter.convert_to_binary(&mut image);
+
+        // Assert
+        assert_strokes_found(&image);
+    }
+}
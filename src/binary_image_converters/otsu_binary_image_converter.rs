@@ -0,0 +1,362 @@
+// otsu_binary_image_converter.rs - Converts to binary by the Otsu threshold
+// Copyright (C) 2019 Denis Karpovskiy
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use super::BinaryImageConverter;
+use crate::binary_image::{BinaryImage, PixelColor};
+use crate::channel_reduction::ChannelReduction;
+use crate::progress::{report, Cancelled, Progress};
+use image::{GenericImageView, Pixel};
+use num_traits::Bounded;
+use std::cell::Cell;
+use std::convert::TryInto;
+
+const HISTOGRAM_SIZE: usize = 256;
+
+pub struct OtsuBinaryImageConverter {
+    last_threshold: Cell<Option<u32>>,
+    reduction: ChannelReduction,
+}
+
+impl OtsuBinaryImageConverter {
+    pub fn new() -> Self {
+        Self::with_reduction(ChannelReduction::Rec709Luma)
+    }
+
+    pub fn with_reduction(reduction: ChannelReduction) -> Self {
+        OtsuBinaryImageConverter {
+            last_threshold: Cell::new(None),
+            reduction,
+        }
+    }
+
+    // The threshold chosen by the latest conversion, in the subpixel units of
+    // that image. A pixel is set to the maximum value if its luminance is
+    // greater than the threshold, the pixels themselves are compared by their
+    // histogram bins so the ones sharing the threshold bin all stay black.
+    pub fn last_threshold(&self) -> Option<u32> {
+        self.last_threshold.get()
+    }
+
+    pub fn compute_threshold<Img>(&self, image: &Img) -> u32
+    where
+        Img: GenericImageView,
+    {
+        let max_value = Self::max_subpixel_value::<Img::Pixel>();
+        let mut histogram = [0u64; HISTOGRAM_SIZE];
+        for y in 0..image.height() {
+            self.add_row_to_histogram(image, y, max_value, &mut histogram);
+        }
+
+        Self::bin_to_threshold(Self::find_best_bin(&histogram), max_value)
+    }
+
+    fn compute_max_progress(_width: u32, height: u32) -> u32 {
+        // one pass to build the histogram and one to apply the threshold
+        height * 2
+    }
+
+    // Returns the last histogram bin of the background.
+    fn compute_bin_with_progress<Img, F>(
+        &self,
+        image: &Img,
+        report_progress: &F,
+        max_progress: i32,
+    ) -> Result<usize, Cancelled>
+    where
+        Img: GenericImageView,
+        F: Fn(i32, i32) -> Progress,
+    {
+        let max_value = Self::max_subpixel_value::<Img::Pixel>();
+        let mut histogram = [0u64; HISTOGRAM_SIZE];
+
+        for y in 0..image.height() {
+            self.add_row_to_histogram(image, y, max_value, &mut histogram);
+
+            report(report_progress, y as i32 + 1, max_progress)?;
+        }
+
+        Ok(Self::find_best_bin(&histogram))
+    }
+
+    fn add_row_to_histogram<Img>(
+        &self,
+        image: &Img,
+        y: u32,
+        max_value: f64,
+        histogram: &mut [u64; HISTOGRAM_SIZE],
+    ) where
+        Img: GenericImageView,
+    {
+        for x in 0..image.width() {
+            let luminance = self.reduction.reduce(&image.get_pixel(x, y));
+            histogram[Self::bin(luminance, max_value)] += 1;
+        }
+    }
+
+    // The greatest whole value still falling into the bin. A luminance lands
+    // past the bin once it reaches the upper edge, half a bin above its centre.
+    fn bin_to_threshold(bin: usize, max_value: f64) -> u32 {
+        let upper_edge = (bin as f64 + 0.5) * max_value / (HISTOGRAM_SIZE - 1) as f64;
+        (upper_edge.ceil() as u32).saturating_sub(1)
+    }
+
+    // Returns the last bin of the background class which maximises the
+    // between-class variance.
+    fn find_best_bin(histogram: &[u64; HISTOGRAM_SIZE]) -> usize {
+        let total: u64 = histogram.iter().sum();
+        let total_sum: f64 = histogram
+            .iter()
+            .enumerate()
+            .map(|(i, &count)| i as f64 * count as f64)
+            .sum();
+
+        let mut best_bin = 0;
+        let mut best_variance = -1.0;
+        let mut background_count = 0u64;
+        let mut background_sum = 0.0;
+
+        for (i, &count) in histogram.iter().enumerate() {
+            background_count += count;
+            background_sum += i as f64 * count as f64;
+
+            let foreground_count = total - background_count;
+            if background_count == 0 || foreground_count == 0 {
+                continue;
+            }
+
+            let background_mean = background_sum / background_count as f64;
+            let foreground_mean = (total_sum - background_sum) / foreground_count as f64;
+            let variance = background_count as f64
+                * foreground_count as f64
+                * (background_mean - foreground_mean).powi(2);
+
+            if variance > best_variance {
+                best_variance = variance;
+                best_bin = i;
+            }
+        }
+
+        best_bin
+    }
+
+    fn max_subpixel_value<Pix: Pixel>() -> f64 {
+        num_traits::cast(<Pix::Subpixel as Bounded>::max_value()).unwrap()
+    }
+
+    fn bin(luminance: f64, max_value: f64) -> usize {
+        let bin = (luminance / max_value * (HISTOGRAM_SIZE - 1) as f64).round() as usize;
+        bin.min(HISTOGRAM_SIZE - 1)
+    }
+}
+
+impl BinaryImageConverter for OtsuBinaryImageConverter {
+    fn convert_to_binary_image_with_progress<Img, F>(
+        &self,
+        image: &Img,
+        bg_color: PixelColor,
+        report_progress: F,
+    ) -> Result<BinaryImage, Cancelled>
+    where
+        Img: GenericImageView,
+        F: Fn(i32, i32) -> Progress,
+    {
+        let max_progress: i32 = Self::compute_max_progress(image.width(), image.height())
+            .try_into()
+            .unwrap();
+        let max_value = Self::max_subpixel_value::<Img::Pixel>();
+        let threshold_bin =
+            self.compute_bin_with_progress(image, &report_progress, max_progress)?;
+        self.last_threshold
+            .set(Some(Self::bin_to_threshold(threshold_bin, max_value)));
+
+        let mut binary_image =
+            BinaryImage::new(image.width() as usize, image.height() as usize, bg_color);
+
+        let mut current_progress = image.height() as i32;
+        for y in 0..image.height() {
+            for x in 0..image.width() {
+                let luminance = self.reduction.reduce(&image.get_pixel(x, y));
+                let color = if Self::bin(luminance, max_value) > threshold_bin {
+                    PixelColor::White
+                } else {
+                    PixelColor::Black
+                };
+                binary_image.set_color(x as usize, y as usize, color);
+            }
+
+            current_progress += 1;
+            report(&report_progress, current_progress, max_progress)?;
+        }
+
+        report_progress(max_progress, max_progress);
+
+        Ok(binary_image)
+    }
+}
+
+impl Default for OtsuBinaryImageConverter {
+    fn default() -> Self {
+        OtsuBinaryImageConverter::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::channel_reduction::ColorChannel;
+    use image::{GrayImage, ImageBuffer, Luma, Rgb, RgbImage};
+
+    fn two_level_image() -> GrayImage {
+        GrayImage::from_fn(4, 4, |x, _| if x < 2 { Luma([40]) } else { Luma([200]) })
+    }
+
+    #[test]
+    fn compute_threshold_test() {
+        // Arrange
+        let image = two_level_image();
+        let converter = OtsuBinaryImageConverter::new();
+
+        // Act
+        let threshold = converter.compute_threshold(&image);
+
+        // Assert
+        assert!((40..200).contains(&threshold));
+    }
+
+    #[test]
+    fn otsu_convert_test() {
+        // Arrange
+        let mut image = two_level_image();
+        let converter = OtsuBinaryImageConverter::new();
+
+        // Act
+        converter.convert_to_binary(&mut image);
+
+        // Assert
+        assert!(converter.last_threshold().is_some());
+        for (x, _, pixel) in image.enumerate_pixels() {
+            if x < 2 {
+                assert_eq!(0, pixel[0]);
+            } else {
+                assert_eq!(255, pixel[0]);
+            }
+        }
+    }
+
+    #[test]
+    fn otsu_sixteen_bit_test() {
+        // Arrange
+        let image = ImageBuffer::<Luma<u16>, Vec<u16>>::from_fn(4, 1, |x, _| {
+            Luma([[10_000, 10_100, 50_000, 50_100][x as usize]])
+        });
+        let converter = OtsuBinaryImageConverter::new();
+
+        // Act
+        let binary_image = converter.convert_to_binary_image(&image, PixelColor::White);
+
+        // Assert
+        let threshold = converter.last_threshold().unwrap();
+        for x in 0..4 {
+            let luminance = image.get_pixel(x, 0)[0] as u32;
+            let is_white = binary_image.get_color(x as usize, 0) == PixelColor::White;
+            assert_eq!(luminance > threshold, is_white);
+        }
+        assert!(binary_image.get_color(2, 0) == PixelColor::White);
+    }
+
+    #[test]
+    fn bin_to_threshold_test() {
+        // Act
+        let eight_bit = OtsuBinaryImageConverter::bin_to_threshold(100, 255.0);
+        let sixteen_bit = OtsuBinaryImageConverter::bin_to_threshold(100, 65535.0);
+
+        // Assert
+        assert_eq!(100, eight_bit);
+        assert_eq!(100, OtsuBinaryImageConverter::bin(eight_bit as f64, 255.0));
+        assert_eq!(
+            101,
+            OtsuBinaryImageConverter::bin(eight_bit as f64 + 1.0, 255.0)
+        );
+        assert_eq!(
+            100,
+            OtsuBinaryImageConverter::bin(sixteen_bit as f64, 65535.0)
+        );
+        assert_eq!(
+            101,
+            OtsuBinaryImageConverter::bin(sixteen_bit as f64 + 1.0, 65535.0)
+        );
+    }
+
+    #[test]
+    fn find_best_bin_test() {
+        // Arrange
+        let mut histogram = [0; HISTOGRAM_SIZE];
+        histogram[10] = 5;
+        histogram[11] = 5;
+        histogram[100] = 5;
+        histogram[101] = 5;
+
+        // Act
+        let bin = OtsuBinaryImageConverter::find_best_bin(&histogram);
+
+        // Assert
+        assert!((11..100).contains(&bin));
+    }
+
+    #[test]
+    fn otsu_progress_test() {
+        // Arrange
+        let mut image = two_level_image();
+        let converter = OtsuBinaryImageConverter::new();
+        let last_progress = Cell::new((0, 0));
+
+        // Act
+        converter
+            .convert_to_binary_with_progress(&mut image, None, |current, max| {
+                last_progress.set((current, max));
+                Progress::Continue
+            })
+            .unwrap();
+
+        // Assert
+        assert_eq!((8, 8), last_progress.get());
+    }
+
+    #[test]
+    fn otsu_single_channel_test() {
+        // Arrange
+        let image = RgbImage::from_fn(4, 1, |x, _| {
+            if x < 2 {
+                Rgb([200, 40, 40])
+            } else {
+                Rgb([40, 200, 40])
+            }
+        });
+        let luma_converter = OtsuBinaryImageConverter::new();
+        let red_converter =
+            OtsuBinaryImageConverter::with_reduction(ChannelReduction::Channel(ColorChannel::Red));
+
+        // Act
+        let luma_image = luma_converter.convert_to_binary_image(&image, PixelColor::White);
+        let red_image = red_converter.convert_to_binary_image(&image, PixelColor::White);
+
+        // Assert
+        for x in 0..4 {
+            assert_eq!(x < 2, luma_image.is_fg(x, 0));
+            assert_eq!(x >= 2, red_image.is_fg(x, 0));
+        }
+    }
+}
@@ -0,0 +1,137 @@
+// sauvola_binary_image_converter.rs - Converts to binary using the Sauvola method
+// Copyright (C) 2019 Denis Karpovskiy
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use super::local_threshold::{apply_local_threshold, compute_max_progress, LocalWindow};
+use super::BinaryImageConverter;
+use crate::binary_image::{BinaryImage, PixelColor};
+use crate::channel_reduction::ChannelReduction;
+use crate::progress::{Cancelled, Progress};
+use image::{GenericImageView, Pixel};
+use num_traits::Bounded;
+use std::convert::TryInto;
+
+pub struct SauvolaBinaryImageConverter {
+    window_size: u32,
+    k: f64,
+    r: Option<f64>,
+    reduction: ChannelReduction,
+}
+
+impl SauvolaBinaryImageConverter {
+    pub fn new(window_size: u32, k: f64, r: f64) -> Self {
+        Self::with_reduction(window_size, k, Some(r), ChannelReduction::Rec709Luma)
+    }
+
+    // The dynamic range of the standard deviation `r` is taken as half of the
+    // subpixel range of the converted image, 128 for 8-bit images as in the
+    // paper.
+    pub fn with_default_range(window_size: u32, k: f64) -> Self {
+        Self::with_reduction(window_size, k, None, ChannelReduction::Rec709Luma)
+    }
+
+    // With `r` set to `None` the range is chosen as in `with_default_range`.
+    pub fn with_reduction(
+        window_size: u32,
+        k: f64,
+        r: Option<f64>,
+        reduction: ChannelReduction,
+    ) -> Self {
+        SauvolaBinaryImageConverter {
+            window_size,
+            k,
+            r,
+            reduction,
+        }
+    }
+}
+
+impl BinaryImageConverter for SauvolaBinaryImageConverter {
+    fn convert_to_binary_image_with_progress<Img, F>(
+        &self,
+        image: &Img,
+        bg_color: PixelColor,
+        report_progress: F,
+    ) -> Result<BinaryImage, Cancelled>
+    where
+        Img: GenericImageView,
+        F: Fn(i32, i32) -> Progress,
+    {
+        let max_progress: i32 = compute_max_progress(image.width(), image.height(), 1)
+            .try_into()
+            .unwrap();
+
+        let r = self.r.unwrap_or_else(|| {
+            let max_value: f64 =
+                num_traits::cast(<<Img::Pixel as Pixel>::Subpixel as Bounded>::max_value())
+                    .unwrap();
+            (max_value + 1.0) / 2.0
+        });
+
+        apply_local_threshold(
+            image,
+            bg_color,
+            LocalWindow::new(self.window_size, self.reduction),
+            &report_progress,
+            0,
+            max_progress,
+            |mean, deviation| mean * (1.0 + self.k * (deviation / r - 1.0)),
+        )
+    }
+}
+
+impl Default for SauvolaBinaryImageConverter {
+    fn default() -> Self {
+        SauvolaBinaryImageConverter::with_default_range(15, 0.34)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::binary_image_converters::local_threshold::test_images::*;
+    use image::{ImageBuffer, Luma};
+
+    #[test]
+    fn sauvola_uneven_lighting_test() {
+        // Arrange
+        let mut image = uneven_lighting_image();
+        let converter = SauvolaBinaryImageConverter::new(15, 0.34, 128.0);
+
+        // Act
+        converter.convert_to_binary(&mut image);
+
+        // Assert
+        assert_strokes_found(&image);
+    }
+
+    #[test]
+    fn sauvola_sixteen_bit_test() {
+        // Arrange
+        let image = uneven_lighting_image();
+        let wide_image = ImageBuffer::<Luma<u16>, Vec<u16>>::from_fn(40, 40, |x, y| {
+            Luma([image.get_pixel(x, y)[0] as u16 * 257])
+        });
+        let converter = SauvolaBinaryImageConverter::default();
+
+        // Act
+        let binary_image = converter.convert_to_binary_image(&wide_image, PixelColor::White);
+
+        // Assert
+        for (x, y) in binary_image.pixels_iter() {
+            assert_eq!(is_stroke(x as u32), binary_image.is_fg(x, y));
+        }
+    }
+}
@@ -15,17 +15,27 @@
 // along with this program.  If not, see <https://www.gnu.org/licenses/>.
 
 use super::BinaryImageConverter;
-use image::{GenericImage, Pixel};
-use num_traits::{Bounded, Zero};
+use crate::binary_image::{BinaryImage, PixelColor};
+use crate::channel_reduction::ChannelReduction;
+use crate::progress::{report, Cancelled, Progress};
+use image::GenericImageView;
 use std::convert::TryInto;
 
 pub struct ThresholdBinaryImageConverter {
     threshold: u32,
+    reduction: ChannelReduction,
 }
 
 impl ThresholdBinaryImageConverter {
     pub fn new(threshold: u32) -> Self {
-        ThresholdBinaryImageConverter { threshold }
+        Self::with_reduction(threshold, ChannelReduction::default())
+    }
+
+    pub fn with_reduction(threshold: u32, reduction: ChannelReduction) -> Self {
+        ThresholdBinaryImageConverter {
+            threshold,
+            reduction,
+        }
     }
 
     fn compute_max_progress(_width: u32, height: u32) -> u32 {
@@ -34,48 +44,166 @@ impl ThresholdBinaryImageConverter {
 }
 
 impl BinaryImageConverter for ThresholdBinaryImageConverter {
-    fn convert_to_binary<Img, Pix>(&self, image: &mut Img)
+    fn convert_to_binary_image_with_progress<Img, F>(
+        &self,
+        image: &Img,
+        bg_color: PixelColor,
+        report_progress: F,
+    ) -> Result<BinaryImage, Cancelled>
     where
-        Pix: Pixel,
-        Img: GenericImage<Pixel = Pix>,
+        Img: GenericImageView,
+        F: Fn(i32, i32) -> Progress,
     {
-        self.convert_to_binary_with_progress(image, |_, _| {});
-    }
+        let mut binary_image =
+            BinaryImage::new(image.width() as usize, image.height() as usize, bg_color);
 
-    fn convert_to_binary_with_progress<Img, Pix, F>(&self, image: &mut Img, report_progress: F)
-    where
-        Img: GenericImage<Pixel = Pix>,
-        Pix: Pixel,
-        F: Fn(i32, i32),
-    {
         let max_progress = Self::compute_max_progress(image.width(), image.height());
         let mut current_progress = 0;
         for y in 0..image.height() {
             for x in 0..image.width() {
-                let pixel = image.get_pixel_mut(x, y);
-
-                let mut is_zero = true;
-                for c in pixel.channels() {
-                    let pixel_value: u32 = num_traits::cast(*c).unwrap();
-                    if pixel_value > self.threshold {
-                        is_zero = false;
-                        break;
-                    }
-                }
-
-                let max = <Pix::Subpixel as Bounded>::max_value();
-                let zero = <Pix::Subpixel as Zero>::zero();
-                if is_zero {
-                    pixel.apply(|_| zero);
+                let pixel = image.get_pixel(x, y);
+                let color = if self.reduction.reduce(&pixel) > self.threshold as f64 {
+                    PixelColor::White
                 } else {
-                    pixel.apply(|_| max);
-                }
+                    PixelColor::Black
+                };
+                binary_image.set_color(x as usize, y as usize, color);
             }
 
             current_progress += 1;
-            report_progress(current_progress, max_progress.try_into().unwrap());
+            report(
+                &report_progress,
+                current_progress,
+                max_progress.try_into().unwrap(),
+            )?;
         }
 
         report_progress(max_progress as i32, max_progress.try_into().unwrap());
+
+        Ok(binary_image)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{BoundingBox, ColorChannel, Roi};
+    use image::{Rgb, RgbImage};
+
+    fn strokes_image() -> RgbImage {
+        let mut image = RgbImage::new(3, 1);
+        image.put_pixel(0, 0, Rgb([0, 0, 200]));
+        image.put_pixel(1, 0, Rgb([200, 0, 0]));
+        image.put_pixel(2, 0, Rgb([200, 200, 200]));
+        image
+    }
+
+    #[test]
+    fn threshold_any_channel_test() {
+        // Arrange
+        let mut image = strokes_image();
+        let converter = ThresholdBinaryImageConverter::new(100);
+
+        // Act
+        converter.convert_to_binary(&mut image);
+
+        // Assert
+        assert_eq!(Rgb([255, 255, 255]), *image.get_pixel(0, 0));
+        assert_eq!(Rgb([255, 255, 255]), *image.get_pixel(1, 0));
+        assert_eq!(Rgb([255, 255, 255]), *image.get_pixel(2, 0));
+    }
+
+    #[test]
+    fn threshold_in_roi_test() {
+        // Arrange
+        let mut image = strokes_image();
+        let converter = ThresholdBinaryImageConverter::new(100);
+        let roi = Roi::Rect(BoundingBox::new(1, 0, 1, 1));
+
+        // Act
+        converter.convert_to_binary_in_roi(&mut image, &roi);
+
+        // Assert
+        assert_eq!(Rgb([0, 0, 200]), *image.get_pixel(0, 0));
+        assert_eq!(Rgb([255, 255, 255]), *image.get_pixel(1, 0));
+        assert_eq!(Rgb([200, 200, 200]), *image.get_pixel(2, 0));
+    }
+
+    #[test]
+    fn threshold_cancel_test() {
+        // Arrange
+        let mut image = strokes_image();
+        let converter = ThresholdBinaryImageConverter::new(100);
+
+        // Act
+        let result =
+            converter.convert_to_binary_with_progress(&mut image, None, |_, _| Progress::Cancel);
+
+        // Assert
+        assert_eq!(Err(Cancelled), result);
+        assert_eq!(strokes_image(), image);
+    }
+
+    #[test]
+    fn convert_to_binary_image_test() {
+        // Arrange
+        let image = strokes_image();
+        let converter = ThresholdBinaryImageConverter::with_reduction(100, ChannelReduction::Min);
+
+        // Act
+        let binary_image = converter.convert_to_binary_image(&image, PixelColor::White);
+
+        // Assert
+        assert!(binary_image.is_fg(0, 0));
+        assert!(binary_image.is_fg(1, 0));
+        assert!(binary_image.is_bg(2, 0));
+    }
+
+    #[test]
+    fn convert_to_binary_image_polarity_test() {
+        // Arrange
+        let image = strokes_image();
+        let converter = ThresholdBinaryImageConverter::with_reduction(100, ChannelReduction::Min);
+
+        // Act
+        let binary_image = converter.convert_to_binary_image(&image, PixelColor::Black);
+
+        // Assert
+        assert!(binary_image.is_bg(0, 0));
+        assert!(binary_image.is_fg(2, 0));
+        assert_eq!(PixelColor::White, binary_image.get_color(2, 0));
+    }
+
+    #[test]
+    fn threshold_luma_test() {
+        // Arrange
+        let mut image = strokes_image();
+        let converter =
+            ThresholdBinaryImageConverter::with_reduction(100, ChannelReduction::Rec601Luma);
+
+        // Act
+        converter.convert_to_binary(&mut image);
+
+        // Assert
+        assert_eq!(Rgb([0, 0, 0]), *image.get_pixel(0, 0));
+        assert_eq!(Rgb([0, 0, 0]), *image.get_pixel(1, 0));
+        assert_eq!(Rgb([255, 255, 255]), *image.get_pixel(2, 0));
+    }
+
+    #[test]
+    fn threshold_single_channel_test() {
+        // Arrange
+        let mut image = strokes_image();
+        let converter = ThresholdBinaryImageConverter::with_reduction(
+            100,
+            ChannelReduction::Channel(ColorChannel::Red),
+        );
+
+        // Act
+        converter.convert_to_binary(&mut image);
+
+        // Assert
+        assert_eq!(Rgb([0, 0, 0]), *image.get_pixel(0, 0));
+        assert_eq!(Rgb([255, 255, 255]), *image.get_pixel(1, 0));
     }
 }
@@ -0,0 +1,118 @@
+// wolf_binary_image_converter.rs - Converts to binary using the Wolf-Jolion method
+// Copyright (C) 2019 Denis Karpovskiy
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use super::local_threshold::{
+    apply_local_threshold, compute_max_progress, for_each_row, LocalWindow,
+};
+use super::BinaryImageConverter;
+use crate::binary_image::{BinaryImage, PixelColor};
+use crate::channel_reduction::ChannelReduction;
+use crate::progress::{report, Cancelled, Progress};
+use image::GenericImageView;
+use std::convert::TryInto;
+
+pub struct WolfBinaryImageConverter {
+    window_size: u32,
+    k: f64,
+    reduction: ChannelReduction,
+}
+
+impl WolfBinaryImageConverter {
+    pub fn new(window_size: u32, k: f64) -> Self {
+        Self::with_reduction(window_size, k, ChannelReduction::Rec709Luma)
+    }
+
+    pub fn with_reduction(window_size: u32, k: f64, reduction: ChannelReduction) -> Self {
+        WolfBinaryImageConverter {
+            window_size,
+            k,
+            reduction,
+        }
+    }
+}
+
+impl BinaryImageConverter for WolfBinaryImageConverter {
+    fn convert_to_binary_image_with_progress<Img, F>(
+        &self,
+        image: &Img,
+        bg_color: PixelColor,
+        report_progress: F,
+    ) -> Result<BinaryImage, Cancelled>
+    where
+        Img: GenericImageView,
+        F: Fn(i32, i32) -> Progress,
+    {
+        let max_progress: i32 = compute_max_progress(image.width(), image.height(), 2)
+            .try_into()
+            .unwrap();
+        let window = LocalWindow::new(self.window_size, self.reduction);
+
+        // the first pass finds the darkest pixel and the greatest deviation
+        // the thresholds are scaled by
+        let mut min_value = f64::MAX;
+        let mut max_deviation: f64 = 0.0;
+        let mut current_progress = 0;
+        for_each_row(image, window, |_, luminances, statistics| {
+            for (&luminance, &(_, deviation)) in luminances.iter().zip(statistics.iter()) {
+                min_value = min_value.min(luminance);
+                max_deviation = max_deviation.max(deviation);
+            }
+
+            current_progress += 1;
+            report(&report_progress, current_progress, max_progress)
+        })?;
+        let max_deviation = max_deviation.max(f64::EPSILON);
+
+        apply_local_threshold(
+            image,
+            bg_color,
+            window,
+            &report_progress,
+            current_progress,
+            max_progress,
+            |mean, deviation| {
+                (1.0 - self.k) * mean
+                    + self.k * min_value
+                    + self.k * deviation / max_deviation * (mean - min_value)
+            },
+        )
+    }
+}
+
+impl Default for WolfBinaryImageConverter {
+    fn default() -> Self {
+        WolfBinaryImageConverter::new(15, 0.5)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::binary_image_converters::local_threshold::test_images::*;
+
+    #[test]
+    fn wolf_uneven_lighting_test() {
+        // Arrange
+        let mut image = uneven_lighting_image();
+        let converter = WolfBinaryImageConverter::new(15, 0.5);
+
+        // Act
+        converter.convert_to_binary(&mut image);
+
+        // Assert
+        assert_strokes_found(&image);
+    }
+}
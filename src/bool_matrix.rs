@@ -14,23 +14,134 @@
 // You should have received a copy of the GNU General Public License
 // along with this program.  If not, see <https://www.gnu.org/licenses/>.
 
+pub const WORD_BITS: usize = 64;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct BoolMatrix {
-    data: Vec<Vec<bool>>,
+    data: Vec<u64>,
+    width: usize,
+    height: usize,
+    words_per_row: usize,
 }
 
 impl BoolMatrix {
     pub fn new(width: usize, height: usize, default_value: bool) -> BoolMatrix {
-        BoolMatrix {
-            data: vec![vec![default_value; width]; height],
+        let words_per_row = width.div_ceil(WORD_BITS);
+        let mut matrix = BoolMatrix {
+            data: vec![0; words_per_row * height],
+            width,
+            height,
+            words_per_row,
+        };
+
+        if default_value {
+            matrix.fill(true);
         }
+
+        matrix
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    pub fn words_per_row(&self) -> usize {
+        self.words_per_row
     }
 
     pub fn check(&self, x: usize, y: usize) -> bool {
-        self.data[y][x]
+        let (index, bit) = self.locate(x, y);
+        self.data[index] & bit != 0
     }
 
     pub fn set(&mut self, x: usize, y: usize) {
-        self.data[y][x] = true;
+        let (index, bit) = self.locate(x, y);
+        self.data[index] |= bit;
+    }
+
+    pub fn unset(&mut self, x: usize, y: usize) {
+        let (index, bit) = self.locate(x, y);
+        self.data[index] &= !bit;
+    }
+
+    pub fn set_value(&mut self, x: usize, y: usize, value: bool) {
+        if value {
+            self.set(x, y);
+        } else {
+            self.unset(x, y);
+        }
+    }
+
+    pub fn fill(&mut self, value: bool) {
+        let word = if value { !0 } else { 0 };
+        for y in 0..self.height {
+            for word_x in 0..self.words_per_row {
+                self.set_word(word_x, y, word);
+            }
+        }
+    }
+
+    pub fn invert(&mut self) {
+        for y in 0..self.height {
+            for word_x in 0..self.words_per_row {
+                let word = self.word(word_x, y);
+                self.set_word(word_x, y, !word);
+            }
+        }
+    }
+
+    pub fn row(&self, y: usize) -> &[u64] {
+        assert!(y < self.height, "row {} is out of bounds", y);
+        let start = y * self.words_per_row;
+        &self.data[start..start + self.words_per_row]
+    }
+
+    pub fn word(&self, word_x: usize, y: usize) -> u64 {
+        self.row(y)[word_x]
+    }
+
+    // Bits past the right edge of a row are always kept cleared, so callers
+    // can count or compare whole words without masking them out first.
+    pub fn set_word(&mut self, word_x: usize, y: usize, value: u64) {
+        assert!(word_x < self.words_per_row && y < self.height);
+        let mask = if word_x == self.words_per_row - 1 {
+            self.last_word_mask()
+        } else {
+            !0
+        };
+
+        self.data[y * self.words_per_row + word_x] = value & mask;
+    }
+
+    pub fn count_ones(&self) -> usize {
+        self.data
+            .iter()
+            .map(|word| word.count_ones() as usize)
+            .sum()
+    }
+
+    fn last_word_mask(&self) -> u64 {
+        match self.width % WORD_BITS {
+            0 => !0,
+            rest => (1 << rest) - 1,
+        }
+    }
+
+    fn locate(&self, x: usize, y: usize) -> (usize, u64) {
+        assert!(
+            x < self.width && y < self.height,
+            "({}, {}) is out of bounds of a {}x{} matrix",
+            x,
+            y,
+            self.width,
+            self.height
+        );
+
+        (y * self.words_per_row + x / WORD_BITS, 1 << (x % WORD_BITS))
     }
 }
 
@@ -46,7 +157,7 @@ mod tests {
         // Assert
         for y in 0..3 {
             for x in 0..3 {
-                assert_eq!(false, matrix.data[y][x]);
+                assert!(!matrix.check(x, y));
             }
         }
     }
@@ -59,7 +170,7 @@ mod tests {
         // Assert
         for y in 0..3 {
             for x in 0..3 {
-                assert_eq!(true, matrix.data[y][x]);
+                assert!(matrix.check(x, y));
             }
         }
     }
@@ -68,20 +179,20 @@ mod tests {
     fn check_true_test() {
         // Arrange
         let mut matrix = BoolMatrix::new(3, 3, false);
-        matrix.data[1][1] = true;
+        matrix.data[1] = 0b010;
 
         // Act & Assert
-        assert_eq!(true, matrix.check(1, 1));
+        assert!(matrix.check(1, 1));
     }
 
     #[test]
     fn check_false_test() {
         // Arrange
-        let mut matrix = BoolMatrix::new(3, 3, false);
-        matrix.data[1][1] = false;
+        let mut matrix = BoolMatrix::new(3, 3, true);
+        matrix.data[1] = 0b101;
 
         // Act & Assert
-        assert_eq!(false, matrix.check(1, 1));
+        assert!(!matrix.check(1, 1));
     }
 
     #[test]
@@ -93,6 +204,73 @@ mod tests {
         matrix.set(1, 1);
 
         // Assert
-        assert_eq!(true, matrix.data[1][1]);
+        assert_eq!(0b010, matrix.data[1]);
+    }
+
+    #[test]
+    fn unset_test() {
+        // Arrange
+        let mut matrix = BoolMatrix::new(3, 3, true);
+
+        // Act
+        matrix.unset(1, 1);
+
+        // Assert
+        assert_eq!(0b101, matrix.data[1]);
+    }
+
+    #[test]
+    fn rows_are_word_aligned_test() {
+        // Arrange
+        let mut matrix = BoolMatrix::new(70, 2, false);
+
+        // Act
+        matrix.set(65, 0);
+        matrix.set(0, 1);
+
+        // Assert
+        assert_eq!(2, matrix.words_per_row());
+        assert_eq!(&[0, 0b10], matrix.row(0));
+        assert_eq!(&[1, 0], matrix.row(1));
+    }
+
+    #[test]
+    fn fill_keeps_padding_cleared_test() {
+        // Arrange
+        let mut matrix = BoolMatrix::new(70, 2, false);
+
+        // Act
+        matrix.fill(true);
+
+        // Assert
+        assert_eq!(!0, matrix.word(0, 1));
+        assert_eq!(0b11_1111, matrix.word(1, 1));
+        assert_eq!(140, matrix.count_ones());
+    }
+
+    #[test]
+    fn invert_test() {
+        // Arrange
+        let mut matrix = BoolMatrix::new(70, 1, false);
+        matrix.set(3, 0);
+
+        // Act
+        matrix.invert();
+
+        // Assert
+        assert!(!matrix.check(3, 0));
+        assert_eq!(69, matrix.count_ones());
+    }
+
+    #[test]
+    fn set_word_masks_padding_test() {
+        // Arrange
+        let mut matrix = BoolMatrix::new(3, 1, false);
+
+        // Act
+        matrix.set_word(0, 0, !0);
+
+        // Assert
+        assert_eq!(0b111, matrix.word(0, 0));
     }
 }
@@ -0,0 +1,172 @@
+// channel_reduction.rs - Reduces the channels of a pixel to a single value
+// Copyright (C) 2019 Denis Karpovskiy
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use image::Pixel;
+use num_traits::Bounded;
+
+const REC_601_LUMA: [f64; 3] = [0.299, 0.587, 0.114];
+const REC_709_LUMA: [f64; 3] = [0.2126, 0.7152, 0.0722];
+
+// A policy for turning a pixel into the single value that gets thresholded.
+// All values are in the subpixel units of the pixel.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum ChannelReduction {
+    // The maximum of every channel, alpha included. This is how pixels were
+    // always reduced, so it is the default.
+    #[default]
+    MaxOfAllChannels,
+    Rec601Luma,
+    Rec709Luma,
+    Average,
+    Max,
+    Min,
+    Channel(ColorChannel),
+    // Composites the pixel over an opaque background and takes the Rec. 709
+    // luma of the result. The background components are in 0.0..=1.0.
+    AlphaOver {
+        background: [f64; 3],
+    },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ColorChannel {
+    Red,
+    Green,
+    Blue,
+    Alpha,
+}
+
+impl ChannelReduction {
+    pub fn reduce<Pix: Pixel>(&self, pixel: &Pix) -> f64 {
+        let channels = pixel.channels();
+        let max_value: f64 = num_traits::cast(<Pix::Subpixel as Bounded>::max_value()).unwrap();
+        let (rgb, alpha) = Self::split_channels::<Pix>(channels);
+
+        match self {
+            ChannelReduction::MaxOfAllChannels => channels
+                .iter()
+                .map(|c| -> f64 { num_traits::cast(*c).unwrap() })
+                .fold(0.0, f64::max),
+            ChannelReduction::Rec601Luma => Self::weighted(&rgb, &REC_601_LUMA),
+            ChannelReduction::Rec709Luma => Self::weighted(&rgb, &REC_709_LUMA),
+            ChannelReduction::Average => (rgb[0] + rgb[1] + rgb[2]) / 3.0,
+            ChannelReduction::Max => rgb[0].max(rgb[1]).max(rgb[2]),
+            ChannelReduction::Min => rgb[0].min(rgb[1]).min(rgb[2]),
+            ChannelReduction::Channel(ColorChannel::Red) => rgb[0],
+            ChannelReduction::Channel(ColorChannel::Green) => rgb[1],
+            ChannelReduction::Channel(ColorChannel::Blue) => rgb[2],
+            ChannelReduction::Channel(ColorChannel::Alpha) => alpha.unwrap_or(max_value),
+            ChannelReduction::AlphaOver { background } => {
+                let opacity = alpha.map_or(1.0, |a| a / max_value);
+                let mut composite = [0.0; 3];
+                for i in 0..3 {
+                    composite[i] = opacity * rgb[i] + (1.0 - opacity) * background[i] * max_value;
+                }
+
+                Self::weighted(&composite, &REC_709_LUMA)
+            }
+        }
+    }
+
+    // Returns the color channels in the RGB order and the alpha channel, if any.
+    fn split_channels<Pix: Pixel>(channels: &[Pix::Subpixel]) -> ([f64; 3], Option<f64>) {
+        let value = |i: usize| -> f64 { num_traits::cast(channels[i]).unwrap() };
+
+        match Pix::COLOR_MODEL {
+            "RGB" => ([value(0), value(1), value(2)], None),
+            "RGBA" => ([value(0), value(1), value(2)], Some(value(3))),
+            "BGR" => ([value(2), value(1), value(0)], None),
+            "BGRA" => ([value(2), value(1), value(0)], Some(value(3))),
+            "YA" => ([value(0); 3], Some(value(1))),
+            _ => ([value(0); 3], None),
+        }
+    }
+
+    fn weighted(rgb: &[f64; 3], weights: &[f64; 3]) -> f64 {
+        weights[0] * rgb[0] + weights[1] * rgb[1] + weights[2] * rgb[2]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::{Bgr, LumaA, Rgb, Rgba};
+
+    #[test]
+    fn max_of_all_channels_includes_alpha_test() {
+        // Arrange
+        let pixel = Rgba([0u8, 0, 0, 255]);
+
+        // Act & Assert
+        assert_eq!(255.0, ChannelReduction::MaxOfAllChannels.reduce(&pixel));
+        assert_eq!(0.0, ChannelReduction::Max.reduce(&pixel));
+    }
+
+    #[test]
+    fn luma_test() {
+        // Arrange
+        let blue = Rgb([0u8, 0, 200]);
+
+        // Act
+        let rec601 = ChannelReduction::Rec601Luma.reduce(&blue);
+        let rec709 = ChannelReduction::Rec709Luma.reduce(&blue);
+
+        // Assert
+        assert!((rec601 - 22.8).abs() < 1e-9);
+        assert!((rec709 - 14.44).abs() < 1e-9);
+    }
+
+    #[test]
+    fn average_min_max_test() {
+        // Arrange
+        let pixel = Rgb([30u8, 60, 90]);
+
+        // Act & Assert
+        assert_eq!(60.0, ChannelReduction::Average.reduce(&pixel));
+        assert_eq!(30.0, ChannelReduction::Min.reduce(&pixel));
+        assert_eq!(90.0, ChannelReduction::Max.reduce(&pixel));
+    }
+
+    #[test]
+    fn channel_bgr_order_test() {
+        // Arrange
+        let pixel = Bgr([10u8, 20, 30]);
+
+        // Act & Assert
+        let red = ChannelReduction::Channel(ColorChannel::Red);
+        let alpha = ChannelReduction::Channel(ColorChannel::Alpha);
+        assert_eq!(30.0, red.reduce(&pixel));
+        assert_eq!(255.0, alpha.reduce(&pixel));
+    }
+
+    #[test]
+    fn alpha_over_test() {
+        // Arrange
+        let transparent = Rgba([0u8, 0, 0, 0]);
+        let half_gray = LumaA([0u8, 128]);
+        let reduction = ChannelReduction::AlphaOver {
+            background: [1.0, 1.0, 1.0],
+        };
+
+        // Act
+        let transparent_value = reduction.reduce(&transparent);
+        let half_gray_value = reduction.reduce(&half_gray);
+
+        // Assert
+        assert!((transparent_value - 255.0).abs() < 1e-9);
+        assert!((half_gray_value - 127.0).abs() < 1e-9);
+    }
+}
@@ -0,0 +1,540 @@
+// contours.rs - Border following with the hierarchy of outer borders and holes
+// Copyright (C) 2019 Denis Karpovskiy
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use crate::binary_image::BinaryImage;
+use crate::skeletonizers::AdjacencyMode;
+use std::fmt::Write;
+
+// (delta x, delta y) counterclockwise starting from the east neighbour
+const EIGHT_DIRECTIONS: [(i32, i32); 8] = [
+    (1, 0),
+    (1, -1),
+    (0, -1),
+    (-1, -1),
+    (-1, 0),
+    (-1, 1),
+    (0, 1),
+    (1, 1),
+];
+const FOUR_DIRECTIONS: [(i32, i32); 4] = [(1, 0), (0, -1), (-1, 0), (0, 1)];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ContourKind {
+    Outer,
+    Hole,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Contour {
+    kind: ContourKind,
+    points: Vec<(usize, usize)>,
+    parent: Option<usize>,
+    children: Vec<usize>,
+}
+
+impl Contour {
+    pub fn kind(&self) -> ContourKind {
+        self.kind
+    }
+
+    pub fn is_hole(&self) -> bool {
+        self.kind == ContourKind::Hole
+    }
+
+    // The border pixels in the order they were followed, the last one is
+    // next to the first.
+    pub fn points(&self) -> &[(usize, usize)] {
+        &self.points
+    }
+
+    // The hole an outer border is in or the outer border around a hole.
+    pub fn parent(&self) -> Option<usize> {
+        self.parent
+    }
+
+    pub fn children(&self) -> &[usize] {
+        &self.children
+    }
+
+    // Keeps the points that are needed to stay within `epsilon` pixels of
+    // the border.
+    pub fn simplified(&self, epsilon: f64) -> Contour {
+        let points: Vec<(f64, f64)> = self
+            .points
+            .iter()
+            .map(|&(x, y)| (x as f64, y as f64))
+            .collect();
+
+        Contour {
+            points: simplify_closed(&points, epsilon)
+                .into_iter()
+                .map(|i| self.points[i])
+                .collect(),
+            ..self.clone()
+        }
+    }
+
+    // The points are the centers of the pixels.
+    pub fn to_svg_path(&self) -> String {
+        let mut path = String::new();
+        for (i, (x, y)) in self.points.iter().enumerate() {
+            let command = if i == 0 { 'M' } else { 'L' };
+            write!(path, "{}{} {} ", command, *x as f64 + 0.5, *y as f64 + 0.5).unwrap();
+        }
+        path.push('Z');
+
+        path
+    }
+}
+
+// Suzuki and Abe border following. The contours are in the order their first
+// pixels come in the raster order, so a parent is always before its children.
+pub fn find_contours(image: &BinaryImage, mode: AdjacencyMode) -> Vec<Contour> {
+    let directions: &[(i32, i32)] = match mode {
+        AdjacencyMode::Four => &FOUR_DIRECTIONS,
+        AdjacencyMode::Eight => &EIGHT_DIRECTIONS,
+    };
+
+    let mut tracer = Tracer::new(image, directions);
+    tracer.trace_all();
+
+    tracer.contours
+}
+
+// The whole SVG document with the contours as one path, the holes are cut out
+// by the even-odd rule.
+pub fn contours_to_svg(contours: &[Contour], width: usize, height: usize) -> String {
+    let path: Vec<String> = contours.iter().map(Contour::to_svg_path).collect();
+
+    format!(
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{0}\" height=\"{1}\" \
+         viewBox=\"0 0 {0} {1}\"><path fill-rule=\"evenodd\" d=\"{2}\"/></svg>",
+        width,
+        height,
+        path.join(" ")
+    )
+}
+
+// Keeps the points of an open polyline that are needed to stay within
+// `epsilon` of it, the first and the last points are always kept.
+pub fn douglas_peucker(points: &[(f64, f64)], epsilon: f64) -> Vec<(f64, f64)> {
+    simplify_open(points, epsilon)
+        .into_iter()
+        .map(|i| points[i])
+        .collect()
+}
+
+// The indices of the points that are kept.
+pub(crate) fn simplify_open(points: &[(f64, f64)], epsilon: f64) -> Vec<usize> {
+    if points.len() < 3 {
+        return (0..points.len()).collect();
+    }
+
+    let mut is_kept = vec![false; points.len()];
+    is_kept[0] = true;
+    is_kept[points.len() - 1] = true;
+
+    let mut ranges = vec![(0, points.len() - 1)];
+    while let Some((first, last)) = ranges.pop() {
+        let mut farthest = first;
+        let mut max_distance = 0.0;
+        for i in first + 1..last {
+            let distance = distance_to_segment(points[i], points[first], points[last]);
+            if distance > max_distance {
+                farthest = i;
+                max_distance = distance;
+            }
+        }
+
+        // a negative epsilon would split at `first` again and again
+        if farthest > first && max_distance > epsilon {
+            is_kept[farthest] = true;
+            ranges.push((first, farthest));
+            ranges.push((farthest, last));
+        }
+    }
+
+    (0..points.len()).filter(|&i| is_kept[i]).collect()
+}
+
+// A closed polyline is split at the point farthest from the first one.
+pub(crate) fn simplify_closed(points: &[(f64, f64)], epsilon: f64) -> Vec<usize> {
+    if points.len() < 4 {
+        return (0..points.len()).collect();
+    }
+
+    let first = points[0];
+    let distance = |i: usize| (points[i].0 - first.0).hypot(points[i].1 - first.1);
+    let farthest = (1..points.len())
+        .max_by(|&a, &b| distance(a).partial_cmp(&distance(b)).unwrap())
+        .unwrap();
+
+    let mut closed = points.to_vec();
+    closed.push(first);
+
+    let mut indices = simplify_open(&closed[..=farthest], epsilon);
+    indices.pop();
+    indices.extend(
+        simplify_open(&closed[farthest..], epsilon)
+            .into_iter()
+            .map(|i| i + farthest),
+    );
+    indices.pop();
+
+    indices
+}
+
+fn distance_to_segment(point: (f64, f64), start: (f64, f64), end: (f64, f64)) -> f64 {
+    let (delta_x, delta_y) = (end.0 - start.0, end.1 - start.1);
+    let length_squared = delta_x * delta_x + delta_y * delta_y;
+    if length_squared == 0.0 {
+        return (point.0 - start.0).hypot(point.1 - start.1);
+    }
+
+    let t = (((point.0 - start.0) * delta_x + (point.1 - start.1) * delta_y) / length_squared)
+        .clamp(0.0, 1.0);
+
+    (point.0 - start.0 - t * delta_x).hypot(point.1 - start.1 - t * delta_y)
+}
+
+struct Tracer<'a> {
+    // The image framed by one background pixel, foreground is 1 and the
+    // followed borders are marked with their numbers, negative on the east
+    // side of a border.
+    labels: Vec<i32>,
+    width: usize,
+    directions: &'a [(i32, i32)],
+    contours: Vec<Contour>,
+}
+
+// Border numbers start from 2, the frame of the image is border 1.
+const FRAME: i32 = 1;
+
+impl<'a> Tracer<'a> {
+    fn new(image: &BinaryImage, directions: &'a [(i32, i32)]) -> Self {
+        let width = image.width() + 2;
+        let mut labels = vec![0; width * (image.height() + 2)];
+        for (x, y) in image.pixels_iter() {
+            if image.is_fg(x, y) {
+                labels[(y + 1) * width + x + 1] = 1;
+            }
+        }
+
+        Tracer {
+            labels,
+            width,
+            directions,
+            contours: Vec::new(),
+        }
+    }
+
+    fn trace_all(&mut self) {
+        let height = self.labels.len() / self.width;
+        for y in 1..height - 1 {
+            let mut last_border = FRAME;
+
+            for x in 1..self.width - 1 {
+                let value = self.get(x, y);
+
+                if value == 1 && self.get(x - 1, y) == 0 {
+                    self.follow(x, y, (x - 1, y), ContourKind::Outer, last_border);
+                } else if value >= 1 && self.get(x + 1, y) == 0 {
+                    if value > 1 {
+                        last_border = value;
+                    }
+                    self.follow(x, y, (x + 1, y), ContourKind::Hole, last_border);
+                }
+
+                let value = self.get(x, y);
+                if value != 0 && value != 1 {
+                    last_border = value.abs();
+                }
+            }
+        }
+    }
+
+    fn follow(
+        &mut self,
+        x: usize,
+        y: usize,
+        from: (usize, usize),
+        kind: ContourKind,
+        last_border: i32,
+    ) {
+        let index = self.contours.len();
+        let number = index as i32 + 2;
+        let parent = self.parent_of(kind, last_border);
+        if let Some(parent) = parent {
+            self.contours[parent].children.push(index);
+        }
+        self.contours.push(Contour {
+            kind,
+            points: Vec::new(),
+            parent,
+            children: Vec::new(),
+        });
+
+        let mut points = Vec::new();
+
+        // clockwise from the background pixel for the last pixel of the border
+        let start_direction = self.direction(x, y, from);
+        let first = (0..self.directions.len())
+            .map(|i| (start_direction + self.directions.len() - i) % self.directions.len())
+            .map(|direction| self.neighbour(x, y, direction))
+            .find(|&(x, y)| self.get(x, y) != 0);
+
+        let mut previous = match first {
+            Some(first) => first,
+            None => {
+                // a single pixel
+                self.set(x, y, -number);
+                points.push((x - 1, y - 1));
+                self.contours.last_mut().unwrap().points = points;
+                return;
+            }
+        };
+        let last = previous;
+        let mut current = (x, y);
+
+        loop {
+            points.push((current.0 - 1, current.1 - 1));
+
+            // counterclockwise from the pixel after the previous one
+            let start_direction = self.direction(current.0, current.1, previous) + 1;
+            let mut is_east_examined = false;
+            let mut next = current;
+            for i in 0..self.directions.len() {
+                let direction = (start_direction + i) % self.directions.len();
+                let neighbour = self.neighbour(current.0, current.1, direction);
+                if self.get(neighbour.0, neighbour.1) != 0 {
+                    next = neighbour;
+                    break;
+                }
+                if direction == 0 {
+                    is_east_examined = true;
+                }
+            }
+
+            if is_east_examined {
+                self.set(current.0, current.1, -number);
+            } else if self.get(current.0, current.1) == 1 {
+                self.set(current.0, current.1, number);
+            }
+
+            if next == (x, y) && current == last {
+                break;
+            }
+
+            previous = current;
+            current = next;
+        }
+
+        self.contours.last_mut().unwrap().points = points;
+    }
+
+    fn parent_of(&self, kind: ContourKind, last_border: i32) -> Option<usize> {
+        let last_kind = if last_border == FRAME {
+            ContourKind::Hole
+        } else {
+            self.contours[last_border as usize - 2].kind
+        };
+
+        if kind == last_kind {
+            // a sibling of the last border
+            if last_border == FRAME {
+                None
+            } else {
+                self.contours[last_border as usize - 2].parent
+            }
+        } else if last_border == FRAME {
+            None
+        } else {
+            Some(last_border as usize - 2)
+        }
+    }
+
+    fn direction(&self, x: usize, y: usize, to: (usize, usize)) -> usize {
+        let delta = (to.0 as i32 - x as i32, to.1 as i32 - y as i32);
+        self.directions.iter().position(|&d| d == delta).unwrap()
+    }
+
+    fn neighbour(&self, x: usize, y: usize, direction: usize) -> (usize, usize) {
+        let (delta_x, delta_y) = self.directions[direction];
+        ((x as i32 + delta_x) as usize, (y as i32 + delta_y) as usize)
+    }
+
+    fn get(&self, x: usize, y: usize) -> i32 {
+        self.labels[y * self.width + x]
+    }
+
+    fn set(&mut self, x: usize, y: usize, value: i32) {
+        self.labels[y * self.width + x] = value;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::skeletonizers::test_shapes::from_ascii;
+
+    fn sorted(points: &[(usize, usize)]) -> Vec<(usize, usize)> {
+        let mut points = points.to_vec();
+        points.sort();
+        points
+    }
+
+    #[test]
+    fn find_contours_hierarchy_test() {
+        // Arrange
+        let image = from_ascii(&[
+            "#######..", //
+            "#.....#..", //
+            "#.##..#.#", //
+            "#.....#..", //
+            "#######..", //
+        ]);
+
+        // Act
+        let contours = find_contours(&image, AdjacencyMode::Eight);
+
+        // Assert
+        assert_eq!(4, contours.len());
+
+        let kinds: Vec<ContourKind> = contours.iter().map(Contour::kind).collect();
+        assert_eq!(
+            vec![
+                ContourKind::Outer,
+                ContourKind::Hole,
+                ContourKind::Outer,
+                ContourKind::Outer
+            ],
+            kinds
+        );
+        assert_eq!(None, contours[0].parent());
+        assert_eq!(&[1], contours[0].children());
+        assert_eq!(Some(0), contours[1].parent());
+        assert_eq!(&[2], contours[1].children());
+        assert_eq!(Some(1), contours[2].parent());
+        assert_eq!(None, contours[3].parent());
+
+        assert_eq!(20, contours[0].points().len());
+        assert_eq!(vec![(2, 2), (3, 2)], sorted(contours[2].points()));
+        assert_eq!(vec![(8, 2)], contours[3].points().to_vec());
+    }
+
+    #[test]
+    fn find_contours_hole_points_test() {
+        // Arrange
+        let image = from_ascii(&[
+            "###", //
+            "#.#", //
+            "###", //
+        ]);
+
+        // Act
+        let contours = find_contours(&image, AdjacencyMode::Eight);
+
+        // Assert
+        assert_eq!(2, contours.len());
+        assert!(contours[1].is_hole());
+        assert_eq!(
+            vec![(0, 1), (1, 0), (1, 2), (2, 1)],
+            sorted(contours[1].points())
+        );
+    }
+
+    #[test]
+    fn find_contours_adjacency_test() {
+        // Arrange
+        let image = from_ascii(&[
+            "#..", //
+            ".#.", //
+            "..#", //
+        ]);
+
+        // Act
+        let eight = find_contours(&image, AdjacencyMode::Eight);
+        let four = find_contours(&image, AdjacencyMode::Four);
+
+        // Assert
+        assert_eq!(1, eight.len());
+        assert_eq!(
+            vec![(0, 0), (1, 1), (2, 2), (1, 1)],
+            eight[0].points().to_vec()
+        );
+        assert_eq!(3, four.len());
+    }
+
+    #[test]
+    fn find_contours_four_adjacency_hole_test() {
+        // Arrange
+        let image = from_ascii(&[
+            ".#.", //
+            "#.#", //
+            ".#.", //
+        ]);
+
+        // Act
+        let eight = find_contours(&image, AdjacencyMode::Eight);
+        let four = find_contours(&image, AdjacencyMode::Four);
+
+        // Assert
+        assert_eq!(2, eight.len());
+        assert!(eight[1].is_hole());
+        assert_eq!(4, four.len());
+        assert!(four.iter().all(|contour| !contour.is_hole()));
+    }
+
+    #[test]
+    fn simplified_test() {
+        // Arrange
+        let mut image = BinaryImage::new(10, 6, crate::PixelColor::White);
+        for (x, y) in image.pixels_iter() {
+            image.set_fg(x, y);
+        }
+        let contour = &find_contours(&image, AdjacencyMode::Eight)[0];
+
+        // Act
+        let simplified = contour.simplified(0.5);
+
+        // Assert
+        assert_eq!(
+            vec![(0, 0), (0, 5), (9, 0), (9, 5)],
+            sorted(simplified.points())
+        );
+        assert_eq!(
+            "M0.5 0.5 L0.5 5.5 L9.5 5.5 L9.5 0.5 Z",
+            simplified.to_svg_path()
+        );
+    }
+
+    #[test]
+    fn douglas_peucker_test() {
+        // Arrange
+        let points = [(0.0, 0.0), (1.0, 0.1), (2.0, -0.1), (3.0, 5.0), (4.0, 6.0)];
+
+        // Act
+        let simplified = douglas_peucker(&points, 0.5);
+        let negative = douglas_peucker(&points, -1.0);
+
+        // Assert
+        assert_eq!(
+            vec![(0.0, 0.0), (2.0, -0.1), (3.0, 5.0), (4.0, 6.0)],
+            simplified
+        );
+        assert_eq!(douglas_peucker(&points, 0.0), negative);
+    }
+}
@@ -0,0 +1,393 @@
+// distance_transform.rs - Distance from foreground pixels to the background
+// Copyright (C) 2019 Denis Karpovskiy
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use crate::binary_image::BinaryImage;
+use crate::grid::Grid;
+use crate::skeletonizers::AdjacencyMode;
+
+// Every transform gives the distance from a foreground pixel to the nearest
+// background pixel, background pixels get zero. Pixels outside of the image
+// count as background.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DistanceMetric {
+    Euclidean,
+    Chamfer34,
+    Chamfer5711,
+    CityBlock,
+    Chessboard,
+}
+
+impl DistanceMetric {
+    // The metric in which the steps between adjacent pixels are all one.
+    pub fn from_adjacency_mode(mode: AdjacencyMode) -> Self {
+        match mode {
+            AdjacencyMode::Four => DistanceMetric::CityBlock,
+            AdjacencyMode::Eight => DistanceMetric::Chessboard,
+        }
+    }
+}
+
+// (delta x, delta y, weight) for the half of a mask that comes before the
+// center in raster order, the other half is mirrored.
+const CITY_BLOCK_MASK: [(i32, i32, u32); 2] = [(0, -1, 1), (-1, 0, 1)];
+const CHESSBOARD_MASK: [(i32, i32, u32); 4] = [(-1, -1, 1), (0, -1, 1), (1, -1, 1), (-1, 0, 1)];
+const CHAMFER_34_MASK: [(i32, i32, u32); 4] = [(-1, -1, 4), (0, -1, 3), (1, -1, 4), (-1, 0, 3)];
+const CHAMFER_5711_MASK: [(i32, i32, u32); 8] = [
+    (-1, -2, 11),
+    (1, -2, 11),
+    (-2, -1, 11),
+    (-1, -1, 7),
+    (0, -1, 5),
+    (1, -1, 7),
+    (2, -1, 11),
+    (-1, 0, 5),
+];
+
+// The distance in pixels for any metric.
+pub fn distance_transform(image: &BinaryImage, metric: DistanceMetric) -> Grid<f32> {
+    let (distances, unit) = match metric {
+        DistanceMetric::Euclidean => return euclidean(image),
+        DistanceMetric::Chamfer34 => (chamfer_34(image), 3.0),
+        DistanceMetric::Chamfer5711 => (chamfer_5711(image), 5.0),
+        DistanceMetric::CityBlock => (city_block(image), 1.0),
+        DistanceMetric::Chessboard => (chessboard(image), 1.0),
+    };
+
+    let mut result = Grid::new(distances.width(), distances.height(), 0.0);
+    for (x, y) in distances.pixels_iter() {
+        result.set(x, y, distances.get(x, y) as f32 / unit);
+    }
+
+    result
+}
+
+pub fn euclidean(image: &BinaryImage) -> Grid<f32> {
+    let squared = squared_euclidean(image);
+
+    let mut distances = Grid::new(squared.width(), squared.height(), 0.0);
+    for (x, y) in squared.pixels_iter() {
+        distances.set(x, y, (squared.get(x, y) as f32).sqrt());
+    }
+
+    distances
+}
+
+// Exact squared Euclidean distances by Felzenszwalb and Huttenlocher, one pass
+// over the columns and one over the rows.
+pub fn squared_euclidean(image: &BinaryImage) -> Grid<u32> {
+    let width = image.width();
+    let height = image.height();
+    let mut columns = Grid::new(width, height, 0.0f64);
+
+    let mut line = Vec::new();
+    let mut result = Vec::new();
+
+    for x in 0..width {
+        line.clear();
+        line.push(0.0);
+        for y in 0..height {
+            line.push(if image.is_fg(x, y) {
+                f64::INFINITY
+            } else {
+                0.0
+            });
+        }
+        line.push(0.0);
+
+        squared_distance_1d(&line, &mut result);
+        for y in 0..height {
+            columns.set(x, y, result[y + 1]);
+        }
+    }
+
+    let mut distances = Grid::new(width, height, 0);
+    for y in 0..height {
+        line.clear();
+        line.push(0.0);
+        for x in 0..width {
+            line.push(columns.get(x, y));
+        }
+        line.push(0.0);
+
+        squared_distance_1d(&line, &mut result);
+        for x in 0..width {
+            distances.set(x, y, result[x + 1] as u32);
+        }
+    }
+
+    distances
+}
+
+// In the units where a horizontal step costs 3 and a diagonal one 4.
+pub fn chamfer_34(image: &BinaryImage) -> Grid<u32> {
+    chamfer(image, &CHAMFER_34_MASK)
+}
+
+// In the units where a horizontal step costs 5, a diagonal one 7 and a
+// knight's move 11.
+pub fn chamfer_5711(image: &BinaryImage) -> Grid<u32> {
+    chamfer(image, &CHAMFER_5711_MASK)
+}
+
+// The length of the shortest 4-connected path to the background.
+pub fn city_block(image: &BinaryImage) -> Grid<u32> {
+    chamfer(image, &CITY_BLOCK_MASK)
+}
+
+// The length of the shortest 8-connected path to the background.
+pub fn chessboard(image: &BinaryImage) -> Grid<u32> {
+    chamfer(image, &CHESSBOARD_MASK)
+}
+
+// Two raster scans, the forward one with the mask and the backward one with
+// the mask mirrored around the center.
+fn chamfer(image: &BinaryImage, mask: &[(i32, i32, u32)]) -> Grid<u32> {
+    let width = image.width() as i32;
+    let height = image.height() as i32;
+    let mut distances = Grid::new(image.width(), image.height(), 0);
+
+    let get = |distances: &Grid<u32>, x: i32, y: i32| -> u32 {
+        if x < 0 || y < 0 || x >= width || y >= height {
+            0
+        } else {
+            distances.get(x as usize, y as usize)
+        }
+    };
+
+    for y in 0..height {
+        for x in 0..width {
+            if image.is_bg(x as usize, y as usize) {
+                continue;
+            }
+
+            let distance = mask
+                .iter()
+                .map(|&(delta_x, delta_y, weight)| {
+                    get(&distances, x + delta_x, y + delta_y).saturating_add(weight)
+                })
+                .min()
+                .unwrap_or(u32::MAX);
+            distances.set(x as usize, y as usize, distance);
+        }
+    }
+
+    for y in (0..height).rev() {
+        for x in (0..width).rev() {
+            let current = distances.get(x as usize, y as usize);
+            if current == 0 {
+                continue;
+            }
+
+            let distance = mask
+                .iter()
+                .map(|&(delta_x, delta_y, weight)| {
+                    get(&distances, x - delta_x, y - delta_y).saturating_add(weight)
+                })
+                .fold(current, u32::min);
+            distances.set(x as usize, y as usize, distance);
+        }
+    }
+
+    distances
+}
+
+// The lower envelope of the parabolas rooted at every sample.
+fn squared_distance_1d(f: &[f64], d: &mut Vec<f64>) {
+    let n = f.len();
+    let mut v = vec![0usize; n];
+    let mut z = vec![0.0f64; n + 1];
+    let mut k = 0;
+    z[0] = f64::NEG_INFINITY;
+    z[1] = f64::INFINITY;
+
+    for q in 1..n {
+        if f[q].is_infinite() {
+            continue;
+        }
+
+        loop {
+            let p = v[k];
+            let s = if f[p].is_infinite() {
+                f64::NEG_INFINITY
+            } else {
+                ((f[q] + (q * q) as f64) - (f[p] + (p * p) as f64)) / (2.0 * (q - p) as f64)
+            };
+
+            if s <= z[k] && k > 0 {
+                k -= 1;
+            } else {
+                k += 1;
+                v[k] = q;
+                z[k] = s;
+                z[k + 1] = f64::INFINITY;
+                break;
+            }
+        }
+    }
+
+    d.clear();
+    k = 0;
+    for q in 0..n {
+        while z[k + 1] < q as f64 {
+            k += 1;
+        }
+
+        let p = v[k];
+        d.push((q as f64 - p as f64).powi(2) + f[p]);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::PixelColor;
+
+    fn square_image(size: usize) -> BinaryImage {
+        let mut image = BinaryImage::new(size, size, PixelColor::White);
+        for y in 1..size - 1 {
+            for x in 1..size - 1 {
+                image.set_fg(x, y);
+            }
+        }
+
+        image
+    }
+
+    // Scattered background pixels and a vertical gap in the middle.
+    fn irregular_image() -> BinaryImage {
+        let mut image = BinaryImage::new(13, 11, PixelColor::White);
+        for (x, y) in image.pixels_iter() {
+            if (x * 7 + y * 3) % 11 != 0 && !(x == 6 && y > 2 && y < 8) {
+                image.set_fg(x, y);
+            }
+        }
+
+        image
+    }
+
+    fn brute_force_squared(image: &BinaryImage, x: usize, y: usize) -> u32 {
+        if image.is_bg(x, y) {
+            return 0;
+        }
+
+        let (x, y) = (x as i32, y as i32);
+        let mut best = i32::MAX;
+        for other_y in -1..=image.height() as i32 {
+            for other_x in -1..=image.width() as i32 {
+                if image.is_bg_or_default(other_x, other_y) {
+                    best = best.min((other_x - x).pow(2) + (other_y - y).pow(2));
+                }
+            }
+        }
+
+        best as u32
+    }
+
+    #[test]
+    fn squared_euclidean_brute_force_test() {
+        // Arrange
+        let image = irregular_image();
+
+        // Act
+        let distances = squared_euclidean(&image);
+
+        // Assert
+        for (x, y) in image.pixels_iter() {
+            assert_eq!(brute_force_squared(&image, x, y), distances.get(x, y));
+        }
+    }
+
+    #[test]
+    fn euclidean_image_border_test() {
+        // Arrange
+        let mut image = BinaryImage::new(3, 3, PixelColor::White);
+        image.fill(PixelColor::Black);
+
+        // Act
+        let distances = euclidean(&image);
+
+        // Assert
+        assert_eq!(1.0, distances.get(0, 0));
+        assert_eq!(2.0, distances.get(1, 1));
+    }
+
+    #[test]
+    fn city_block_and_chessboard_test() {
+        // Arrange
+        let mut image = square_image(9);
+        image.set_bg(4, 4);
+
+        // Act
+        let city_block = city_block(&image);
+        let chessboard = chessboard(&image);
+
+        // Assert
+        assert_eq!(0, city_block.get(4, 4));
+        assert_eq!(2, city_block.get(3, 3));
+        assert_eq!(1, chessboard.get(3, 3));
+        assert_eq!(2, city_block.get(2, 2));
+        assert_eq!(2, chessboard.get(2, 2));
+        assert_eq!(1, city_block.get(1, 4));
+    }
+
+    #[test]
+    fn chamfer_test() {
+        // Arrange
+        let mut image = square_image(13);
+        image.set_bg(6, 6);
+
+        // Act
+        let chamfer_34 = chamfer_34(&image);
+        let chamfer_5711 = chamfer_5711(&image);
+
+        // Assert
+        assert_eq!(4, chamfer_34.get(5, 5));
+        assert_eq!(3, chamfer_34.get(6, 5));
+        assert_eq!(7, chamfer_34.get(5, 4));
+        assert_eq!(7, chamfer_5711.get(5, 5));
+        assert_eq!(11, chamfer_5711.get(5, 4));
+        assert_eq!(10, chamfer_5711.get(6, 4));
+    }
+
+    #[test]
+    fn distance_transform_units_test() {
+        // Arrange
+        let image = square_image(9);
+
+        // Act
+        let euclidean = distance_transform(&image, DistanceMetric::Euclidean);
+        let chamfer_34 = distance_transform(&image, DistanceMetric::Chamfer34);
+        let chamfer_5711 = distance_transform(&image, DistanceMetric::Chamfer5711);
+
+        // Assert
+        assert_eq!(4.0, euclidean.get(4, 4));
+        assert_eq!(4.0, chamfer_34.get(4, 4));
+        assert_eq!(4.0, chamfer_5711.get(4, 4));
+    }
+
+    #[test]
+    fn from_adjacency_mode_test() {
+        // Arrange & Act & Assert
+        assert_eq!(
+            DistanceMetric::CityBlock,
+            DistanceMetric::from_adjacency_mode(AdjacencyMode::Four)
+        );
+        assert_eq!(
+            DistanceMetric::Chessboard,
+            DistanceMetric::from_adjacency_mode(AdjacencyMode::Eight)
+        );
+    }
+}
@@ -14,18 +14,85 @@
 // You should have received a copy of the GNU General Public License
 // along with this program.  If not, see <https://www.gnu.org/licenses/>.
 
-use crate::PixelColor;
-use image::{DynamicImage, ImageError, ImageOutputFormat, RgbImage};
+use crate::{PixelColor, Polarity};
+use image::error::{ParameterError, ParameterErrorKind};
+use image::{DynamicImage, ImageError, ImageOutputFormat, Rgb, RgbImage};
+use std::cell::RefCell;
+use std::ffi::CString;
+use std::os::raw::c_char;
+use std::panic::{self, AssertUnwindSafe};
 
 use crate::{
-    binary_image::BinaryImage, AdjacencyMode, BinaryImageConverter, EberlySkeletonizer,
-    RosenfeldSkeletonizer, Skeletonizer, ThresholdBinaryImageConverter, ZhangSuenSkeletonizer,
+    binary_image::BinaryImage, contours, find_contours, hit_or_miss, morphology, AdjacencyMode,
+    BinaryImageConverter, BoundingBox, CancellationToken, Cancelled, Contour, EberlySkeletonizer,
+    GuoHallSkeletonizer, HuangSkeletonizer, K3MSkeletonizer, MeanCBinaryImageConverter,
+    MedialAxisSkeletonizer, NiblackBinaryImageConverter, OtsuBinaryImageConverter, Progress, Roi,
+    RosenfeldSkeletonizer, SauvolaBinaryImageConverter, SkeletonGraph, SkeletonVectorizer,
+    Skeletonizer, SpurLength, SpurPruner, StentifordSkeletonizer, StructuringElement, Template,
+    TemplateCell, ThresholdBinaryImageConverter, UpdateMode, VectorFormat,
+    WolfBinaryImageConverter, ZhangSuenSkeletonizer,
 };
 
+// A `polarity` is 0 for black strokes on white, 1 for white strokes on black
+// and 2 for strokes of the colour with fewer pixels, any other is rejected.
+// The resulting images have black strokes on white whatever it was. The
+// entry points from before it could be chosen take black strokes.
+const BLACK_FOREGROUND: i32 = 0;
+
+// Every call reports what went wrong in the returned buffer, the data of a
+// failed call is null and `improc_petrsu_last_error_message` tells why.
+#[repr(i32)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ErrorCode {
+    Ok = 0,
+    InvalidArgument = 1,
+    Decoding = 2,
+    Encoding = 3,
+    UnsupportedFormat = 4,
+    Limits = 5,
+    Io = 6,
+    Panic = 7,
+    Cancelled = 8,
+}
+
 #[repr(C)]
 pub struct Buffer {
     data: *mut u8,
     len: usize,
+    error: ErrorCode,
+}
+
+thread_local! {
+    static LAST_ERROR: RefCell<Option<(ErrorCode, CString)>> = const { RefCell::new(None) };
+}
+
+// The message of the last failed call on the calling thread as a UTF-8 C
+// string, null if none failed yet. It stays valid until the next failed call
+// on the same thread and must not be freed.
+#[no_mangle]
+pub extern "C" fn improc_petrsu_last_error_message() -> *const c_char {
+    catch_panic(std::ptr::null(), || {
+        LAST_ERROR.with(|error| {
+            error
+                .borrow()
+                .as_ref()
+                .map_or(std::ptr::null(), |(_, message)| message.as_ptr())
+        })
+    })
+}
+
+// The error of the last failed call on the calling thread, for the calls that
+// return a handle and so have no room for it.
+#[no_mangle]
+pub extern "C" fn improc_petrsu_last_error_code() -> ErrorCode {
+    catch_panic(ErrorCode::Panic, || {
+        LAST_ERROR.with(|error| {
+            error
+                .borrow()
+                .as_ref()
+                .map_or(ErrorCode::Ok, |(code, _)| *code)
+        })
+    })
 }
 
 #[no_mangle]
@@ -35,21 +102,146 @@ pub extern "C" fn improc_petrsu_threshold_binary_image_converter_process(
     threshold: u32,
     report_progress: unsafe extern "C" fn(i32, i32),
 ) -> Buffer {
-    match get_rgb_image_from_raw_data(image_bytes, len) {
-        Ok(mut img) => {
-            let converter = ThresholdBinaryImageConverter::new(threshold);
-            converter
-                .convert_to_binary_with_progress(&mut img, |a, b| unsafe { report_progress(a, b) });
-            rgb_image_to_raw_buffer(img)
+    catch_panic(failed_buffer(ErrorCode::Panic), || {
+        let converter = ThresholdBinaryImageConverter::new(threshold);
+
+        match convert(
+            image_bytes,
+            len,
+            &converter,
+            cancellable_progress(Some(report_progress), std::ptr::null()),
+        ) {
+            Ok(img) => img,
+            Err(err) => error_buffer(err),
         }
-        Err(err) => {
-            eprintln!("Error: {}", err.to_string());
-            Buffer {
-                data: std::ptr::null_mut::<u8>(),
-                len: 0,
+    })
+}
+
+// The converters from here on take a `report_progress` and a
+// `cancellation_token` that may be null, a cancelled call returns `Cancelled`.
+#[no_mangle]
+pub extern "C" fn improc_petrsu_otsu_binary_image_converter_process(
+    image_bytes: *const u8,
+    len: usize,
+    threshold: *mut u32,
+    report_progress: Option<unsafe extern "C" fn(i32, i32)>,
+    cancellation_token: *const CancellationToken,
+) -> Buffer {
+    catch_panic(failed_buffer(ErrorCode::Panic), || {
+        let converter = OtsuBinaryImageConverter::new();
+
+        match convert(
+            image_bytes,
+            len,
+            &converter,
+            cancellable_progress(report_progress, cancellation_token),
+        ) {
+            Ok(img) => {
+                write_out_value(threshold, converter.last_threshold().unwrap_or(0));
+                img
             }
+            Err(err) => error_buffer(err),
         }
-    }
+    })
+}
+
+#[no_mangle]
+pub extern "C" fn improc_petrsu_mean_c_binary_image_converter_process(
+    image_bytes: *const u8,
+    len: usize,
+    window_size: u32,
+    c: f64,
+    report_progress: Option<unsafe extern "C" fn(i32, i32)>,
+    cancellation_token: *const CancellationToken,
+) -> Buffer {
+    catch_panic(failed_buffer(ErrorCode::Panic), || {
+        let converter = MeanCBinaryImageConverter::new(window_size, c);
+
+        match convert(
+            image_bytes,
+            len,
+            &converter,
+            cancellable_progress(report_progress, cancellation_token),
+        ) {
+            Ok(img) => img,
+            Err(err) => error_buffer(err),
+        }
+    })
+}
+
+#[no_mangle]
+pub extern "C" fn improc_petrsu_niblack_binary_image_converter_process(
+    image_bytes: *const u8,
+    len: usize,
+    window_size: u32,
+    k: f64,
+    report_progress: Option<unsafe extern "C" fn(i32, i32)>,
+    cancellation_token: *const CancellationToken,
+) -> Buffer {
+    catch_panic(failed_buffer(ErrorCode::Panic), || {
+        let converter = NiblackBinaryImageConverter::new(window_size, k);
+
+        match convert(
+            image_bytes,
+            len,
+            &converter,
+            cancellable_progress(report_progress, cancellation_token),
+        ) {
+            Ok(img) => img,
+            Err(err) => error_buffer(err),
+        }
+    })
+}
+
+// A non-positive `r` is taken as half of the subpixel range, 128 for 8-bit
+// images.
+#[no_mangle]
+pub extern "C" fn improc_petrsu_sauvola_binary_image_converter_process(
+    image_bytes: *const u8,
+    len: usize,
+    window_size: u32,
+    k: f64,
+    r: f64,
+    report_progress: Option<unsafe extern "C" fn(i32, i32)>,
+    cancellation_token: *const CancellationToken,
+) -> Buffer {
+    catch_panic(failed_buffer(ErrorCode::Panic), || {
+        let converter = get_sauvola_converter(window_size, k, r);
+
+        match convert(
+            image_bytes,
+            len,
+            &converter,
+            cancellable_progress(report_progress, cancellation_token),
+        ) {
+            Ok(img) => img,
+            Err(err) => error_buffer(err),
+        }
+    })
+}
+
+#[no_mangle]
+pub extern "C" fn improc_petrsu_wolf_binary_image_converter_process(
+    image_bytes: *const u8,
+    len: usize,
+    window_size: u32,
+    k: f64,
+    report_progress: Option<unsafe extern "C" fn(i32, i32)>,
+    cancellation_token: *const CancellationToken,
+) -> Buffer {
+    catch_panic(failed_buffer(ErrorCode::Panic), || {
+        let converter = WolfBinaryImageConverter::new(window_size, k);
+
+        match convert(
+            image_bytes,
+            len,
+            &converter,
+            cancellable_progress(report_progress, cancellation_token),
+        ) {
+            Ok(img) => img,
+            Err(err) => error_buffer(err),
+        }
+    })
 }
 
 #[no_mangle]
@@ -58,17 +250,20 @@ pub extern "C" fn improc_petrsu_zhang_suen_skeletonizer_process(
     len: usize,
     report_progress: unsafe extern "C" fn(i32, i32),
 ) -> Buffer {
-    let skeletonizer = ZhangSuenSkeletonizer::new();
+    catch_panic(failed_buffer(ErrorCode::Panic), || {
+        let skeletonizer = ZhangSuenSkeletonizer::new();
 
-    match skeletonize(image_bytes, len, skeletonizer, |a, b| unsafe {
-        report_progress(a, b)
-    }) {
-        Ok(img) => img,
-        Err(_) => Buffer {
-            data: std::ptr::null_mut::<u8>(),
-            len: 0,
-        },
-    }
+        match skeletonize(
+            image_bytes,
+            len,
+            skeletonizer,
+            BLACK_FOREGROUND,
+            cancellable_progress(Some(report_progress), std::ptr::null()),
+        ) {
+            Ok(img) => img,
+            Err(err) => error_buffer(err),
+        }
+    })
 }
 
 #[no_mangle]
@@ -78,22 +273,20 @@ pub extern "C" fn improc_petrsu_rosenfeld_skeletonizer_process(
     adjacency_mode: i32,
     report_progress: unsafe extern "C" fn(i32, i32),
 ) -> Buffer {
-    let mode = if adjacency_mode == 0 {
-        AdjacencyMode::Eight
-    } else {
-        AdjacencyMode::Four
-    };
-    let skeletonizer = RosenfeldSkeletonizer::new(mode);
-
-    match skeletonize(image_bytes, len, skeletonizer, |a, b| unsafe {
-        report_progress(a, b)
-    }) {
-        Ok(img) => img,
-        Err(_) => Buffer {
-            data: std::ptr::null_mut::<u8>(),
-            len: 0,
-        },
-    }
+    catch_panic(failed_buffer(ErrorCode::Panic), || {
+        let skeletonizer = RosenfeldSkeletonizer::new(get_adjacency_mode(adjacency_mode));
+
+        match skeletonize(
+            image_bytes,
+            len,
+            skeletonizer,
+            BLACK_FOREGROUND,
+            cancellable_progress(Some(report_progress), std::ptr::null()),
+        ) {
+            Ok(img) => img,
+            Err(err) => error_buffer(err),
+        }
+    })
 }
 
 #[no_mangle]
@@ -102,73 +295,2365 @@ pub extern "C" fn improc_petrsu_eberly_skeletonizer_process(
     len: usize,
     report_progress: unsafe extern "C" fn(i32, i32),
 ) -> Buffer {
-    let skeletonizer = EberlySkeletonizer::new();
+    catch_panic(failed_buffer(ErrorCode::Panic), || {
+        let skeletonizer = EberlySkeletonizer::new();
 
-    match skeletonize(image_bytes, len, skeletonizer, |a, b| unsafe {
-        report_progress(a, b)
-    }) {
-        Ok(img) => img,
-        Err(_) => Buffer {
-            data: std::ptr::null_mut::<u8>(),
-            len: 0,
-        },
-    }
+        match skeletonize(
+            image_bytes,
+            len,
+            skeletonizer,
+            BLACK_FOREGROUND,
+            cancellable_progress(Some(report_progress), std::ptr::null()),
+        ) {
+            Ok(img) => img,
+            Err(err) => error_buffer(err),
+        }
+    })
 }
 
+// These skeletonizers take the same optional callback and token as the
+// converters above.
 #[no_mangle]
-pub extern "C" fn improc_petrsu_free(buf: Buffer) {
-    let slice = unsafe { std::slice::from_raw_parts_mut(buf.data, buf.len) };
-    let ptr = slice.as_mut_ptr();
+pub extern "C" fn improc_petrsu_guo_hall_skeletonizer_process(
+    image_bytes: *const u8,
+    len: usize,
+    polarity: i32,
+    report_progress: Option<unsafe extern "C" fn(i32, i32)>,
+    cancellation_token: *const CancellationToken,
+) -> Buffer {
+    catch_panic(failed_buffer(ErrorCode::Panic), || {
+        let skeletonizer = GuoHallSkeletonizer::new();
 
-    unsafe {
-        Box::from_raw(ptr);
-    }
+        match skeletonize(
+            image_bytes,
+            len,
+            skeletonizer,
+            polarity,
+            cancellable_progress(report_progress, cancellation_token),
+        ) {
+            Ok(img) => img,
+            Err(err) => error_buffer(err),
+        }
+    })
 }
 
-fn skeletonize<T, F>(
+#[no_mangle]
+pub extern "C" fn improc_petrsu_k3m_skeletonizer_process(
     image_bytes: *const u8,
     len: usize,
-    skeletonizer: T,
-    increment_progress: F,
-) -> Result<Buffer, ImageError>
-where
-    T: Skeletonizer,
-    F: Fn(i32, i32),
-{
-    let original_image = get_rgb_image_from_raw_data(image_bytes, len)?;
+    polarity: i32,
+    report_progress: Option<unsafe extern "C" fn(i32, i32)>,
+    cancellation_token: *const CancellationToken,
+) -> Buffer {
+    catch_panic(failed_buffer(ErrorCode::Panic), || {
+        let skeletonizer = K3MSkeletonizer::new();
 
-    let mut binary_image = BinaryImage::from_image(&original_image, PixelColor::White);
+        match skeletonize(
+            image_bytes,
+            len,
+            skeletonizer,
+            polarity,
+            cancellable_progress(report_progress, cancellation_token),
+        ) {
+            Ok(img) => img,
+            Err(err) => error_buffer(err),
+        }
+    })
+}
 
-    skeletonizer.process_with_progress(&mut binary_image, increment_progress);
+#[no_mangle]
+pub extern "C" fn improc_petrsu_huang_skeletonizer_process(
+    image_bytes: *const u8,
+    len: usize,
+    polarity: i32,
+    report_progress: Option<unsafe extern "C" fn(i32, i32)>,
+    cancellation_token: *const CancellationToken,
+) -> Buffer {
+    catch_panic(failed_buffer(ErrorCode::Panic), || {
+        let skeletonizer = HuangSkeletonizer::new();
 
-    let result_image = binary_image.to_rgb_image();
+        match skeletonize(
+            image_bytes,
+            len,
+            skeletonizer,
+            polarity,
+            cancellable_progress(report_progress, cancellation_token),
+        ) {
+            Ok(img) => img,
+            Err(err) => error_buffer(err),
+        }
+    })
+}
 
-    Ok(rgb_image_to_raw_buffer(result_image))
+#[no_mangle]
+pub extern "C" fn improc_petrsu_stentiford_skeletonizer_process(
+    image_bytes: *const u8,
+    len: usize,
+    polarity: i32,
+    report_progress: Option<unsafe extern "C" fn(i32, i32)>,
+    cancellation_token: *const CancellationToken,
+) -> Buffer {
+    catch_panic(failed_buffer(ErrorCode::Panic), || {
+        let skeletonizer = StentifordSkeletonizer::new();
+
+        match skeletonize(
+            image_bytes,
+            len,
+            skeletonizer,
+            polarity,
+            cancellable_progress(report_progress, cancellation_token),
+        ) {
+            Ok(img) => img,
+            Err(err) => error_buffer(err),
+        }
+    })
 }
 
-fn get_rgb_image_from_raw_data(image_bytes: *const u8, len: usize) -> Result<RgbImage, ImageError> {
-    let slice = unsafe { std::slice::from_raw_parts(image_bytes, len) };
+// `operation` is 0 for erosion, 1 for dilation, 2 for opening, 3 for closing
+// and 4 for the gradient, `element` is 0 for a square, 1 for a cross and 2
+// for a disk.
+#[no_mangle]
+pub extern "C" fn improc_petrsu_morphology_process(
+    image_bytes: *const u8,
+    len: usize,
+    operation: i32,
+    element: i32,
+    radius: u32,
+    polarity: i32,
+) -> Buffer {
+    catch_panic(failed_buffer(ErrorCode::Panic), || {
+        let result = get_structuring_element(element, radius as usize)
+            .and_then(|element| apply_morphology(image_bytes, len, operation, &element, polarity));
 
-    Ok(image::load_from_memory(slice)?.to_rgb8())
+        match result {
+            Ok(buffer) => buffer,
+            Err(err) => error_buffer(err),
+        }
+    })
 }
 
-fn rgb_image_to_raw_buffer(image: RgbImage) -> Buffer {
-    let dyn_image = DynamicImage::ImageRgb8(image);
-    let mut vector = Vec::new();
-    match dyn_image.write_to(&mut vector, ImageOutputFormat::Png) {
-        Ok(_) => {
-            let slice = vector.into_boxed_slice();
-            let len = slice.len();
-            let data = Box::into_raw(slice);
-
-            Buffer {
-                data: data as *mut u8,
-                len,
+// The element is a `mask_width` by `mask_height` mask of bytes, the non-zero
+// ones are its pixels.
+#[no_mangle]
+pub extern "C" fn improc_petrsu_morphology_custom_process(
+    image_bytes: *const u8,
+    len: usize,
+    operation: i32,
+    mask: *const u8,
+    mask_width: u32,
+    mask_height: u32,
+    origin_x: u32,
+    origin_y: u32,
+    polarity: i32,
+) -> Buffer {
+    catch_panic(failed_buffer(ErrorCode::Panic), || {
+        let result = get_structuring_element_from_mask(
+            mask,
+            mask_width as usize,
+            mask_height as usize,
+            (origin_x as usize, origin_y as usize),
+        )
+        .and_then(|element| apply_morphology(image_bytes, len, operation, &element, polarity));
+
+        match result {
+            Ok(buffer) => buffer,
+            Err(err) => error_buffer(err),
+        }
+    })
+}
+
+// The templates are `template_count` masks of `template_width` by
+// `template_height` bytes one after another, 1 is foreground, 0 is background
+// and the rest do not matter, the origin is in the middle. `mode` 0 applies
+// them one after another and 1 in parallel. A negative `max_passes` repeats
+// until nothing changes.
+#[no_mangle]
+pub extern "C" fn improc_petrsu_hit_or_miss_process(
+    image_bytes: *const u8,
+    len: usize,
+    templates: *const u8,
+    template_count: u32,
+    template_width: u32,
+    template_height: u32,
+    mode: i32,
+    is_thickening: i32,
+    max_passes: i32,
+    polarity: i32,
+) -> Buffer {
+    catch_panic(failed_buffer(ErrorCode::Panic), || {
+        let max_passes = if max_passes < 0 {
+            None
+        } else {
+            Some(max_passes as usize)
+        };
+
+        let apply = || -> Result<Buffer, ImageError> {
+            let mode = get_update_mode(mode)?;
+            let templates = get_templates_from_raw_data(
+                templates,
+                template_count as usize,
+                template_width as usize,
+                template_height as usize,
+            )?;
+            let mut image = get_binary_image_from_raw_data(image_bytes, len, polarity)?;
+
+            if is_thickening != 0 {
+                hit_or_miss::thicken(&mut image, &templates, mode, max_passes);
+            } else {
+                hit_or_miss::thin(&mut image, &templates, mode, max_passes);
+            }
+
+            Ok(rgb_image_to_raw_buffer(image.to_rgb_image()))
+        };
+
+        match apply() {
+            Ok(buffer) => buffer,
+            Err(err) => error_buffer(err),
+        }
+    })
+}
+
+// Prunes a skeleton image, `shape_bytes` can be null or hold the image before
+// the skeletonization to measure the stroke widths on. With `length_mode` 0
+// the maximum length is in pixels and with 1 a fraction of the stroke width.
+#[no_mangle]
+pub extern "C" fn improc_petrsu_spur_pruner_process(
+    skeleton_bytes: *const u8,
+    skeleton_len: usize,
+    shape_bytes: *const u8,
+    shape_len: usize,
+    max_length: f64,
+    length_mode: i32,
+    is_iterative: i32,
+    adjacency_mode: i32,
+    polarity: i32,
+) -> Buffer {
+    catch_panic(failed_buffer(ErrorCode::Panic), || {
+        let prune = || -> Result<Buffer, ImageError> {
+            let pruner = SpurPruner::new(
+                get_spur_length(length_mode, max_length)?,
+                get_adjacency_mode(adjacency_mode),
+                is_iterative != 0,
+            );
+            let mut skeleton =
+                get_binary_image_from_raw_data(skeleton_bytes, skeleton_len, polarity)?;
+            let shape = if shape_bytes.is_null() {
+                None
+            } else {
+                Some(get_binary_image_from_raw_data(
+                    shape_bytes,
+                    shape_len,
+                    polarity,
+                )?)
+            };
+
+            pruner.prune(&mut skeleton, shape.as_ref());
+
+            Ok(rgb_image_to_raw_buffer(skeleton.to_rgb_image()))
+        };
+
+        match prune() {
+            Ok(buffer) => buffer,
+            Err(err) => error_buffer(err),
+        }
+    })
+}
+
+// The outlines of the objects as an SVG document, simplified to stay within
+// `epsilon` pixels of the borders.
+#[no_mangle]
+pub extern "C" fn improc_petrsu_contours_process(
+    image_bytes: *const u8,
+    len: usize,
+    adjacency_mode: i32,
+    epsilon: f64,
+    polarity: i32,
+) -> Buffer {
+    catch_panic(
+        failed_buffer(ErrorCode::Panic),
+        || match get_binary_image_from_raw_data(image_bytes, len, polarity) {
+            Ok(image) => {
+                let contours: Vec<Contour> =
+                    find_contours(&image, get_adjacency_mode(adjacency_mode))
+                        .iter()
+                        .map(|contour| contour.simplified(epsilon))
+                        .collect();
+                let svg = contours::contours_to_svg(&contours, image.width(), image.height());
+                bytes_to_buffer(svg.into_bytes())
             }
+            Err(err) => error_buffer(err),
+        },
+    )
+}
+
+// The branches of a skeleton image as polylines, `format` is 0 for SVG, 1 for
+// GeoJSON and 2 for JSON.
+#[no_mangle]
+pub extern "C" fn improc_petrsu_skeleton_vectorizer_process(
+    image_bytes: *const u8,
+    len: usize,
+    adjacency_mode: i32,
+    smoothing_passes: u32,
+    epsilon: f64,
+    format: i32,
+    polarity: i32,
+) -> Buffer {
+    catch_panic(failed_buffer(ErrorCode::Panic), || {
+        let vectorizer = SkeletonVectorizer::new(
+            get_adjacency_mode(adjacency_mode),
+            smoothing_passes as usize,
+            epsilon,
+        );
+        let vectorize = || -> Result<Buffer, ImageError> {
+            let format = get_vector_format(format)?;
+            let image = get_binary_image_from_raw_data(image_bytes, len, polarity)?;
+
+            Ok(bytes_to_buffer(
+                vectorizer.vectorize_to_string(&image, format).into_bytes(),
+            ))
+        };
+
+        match vectorize() {
+            Ok(buffer) => buffer,
+            Err(err) => error_buffer(err),
         }
-        Err(_) => Buffer {
-            data: std::ptr::null_mut::<u8>(),
-            len: 0,
+    })
+}
+
+// The graph of a skeleton image as UTF-8 JSON with the nodes and the edges.
+#[no_mangle]
+pub extern "C" fn improc_petrsu_skeleton_graph_process(
+    image_bytes: *const u8,
+    len: usize,
+    adjacency_mode: i32,
+    polarity: i32,
+) -> Buffer {
+    catch_panic(
+        failed_buffer(ErrorCode::Panic),
+        || match get_binary_image_from_raw_data(image_bytes, len, polarity) {
+            Ok(image) => {
+                let graph = SkeletonGraph::new(&image, get_adjacency_mode(adjacency_mode));
+                bytes_to_buffer(graph.to_json().into_bytes())
+            }
+            Err(err) => error_buffer(err),
         },
+    )
+}
+
+// Removes the foreground components whose area lies outside
+// `min_area..=max_area` or whose bounding box aspect ratio (width / height)
+// lies outside `min_aspect_ratio..=max_aspect_ratio`. A zero `max_area` and a
+// non-positive `max_aspect_ratio` mean no upper limit.
+#[no_mangle]
+pub extern "C" fn improc_petrsu_area_filter_process(
+    image_bytes: *const u8,
+    len: usize,
+    min_area: usize,
+    max_area: usize,
+    min_aspect_ratio: f64,
+    max_aspect_ratio: f64,
+    adjacency_mode: i32,
+    polarity: i32,
+) -> Buffer {
+    catch_panic(failed_buffer(ErrorCode::Panic), || {
+        let max_area = if max_area == 0 { usize::MAX } else { max_area };
+        let max_aspect_ratio = if max_aspect_ratio <= 0.0 {
+            f64::INFINITY
+        } else {
+            max_aspect_ratio
+        };
+
+        match get_binary_image_from_raw_data(image_bytes, len, polarity) {
+            Ok(mut image) => {
+                image.retain_components(get_adjacency_mode(adjacency_mode), |component| {
+                    let aspect_ratio = component.bounding_box().aspect_ratio();
+                    (min_area..=max_area).contains(&component.area())
+                        && (min_aspect_ratio..=max_aspect_ratio).contains(&aspect_ratio)
+                });
+                rgb_image_to_raw_buffer(image.to_rgb_image())
+            }
+            Err(err) => error_buffer(err),
+        }
+    })
+}
+
+// Skeletonizes only the pixels inside of a region of interest. The region is
+// the mask if `mask_bytes` is not null, an image encoded like the input one
+// whose foreground marks the pixels inside, otherwise the rectangle. The
+// `algorithm` is 0 for Zhang-Suen, 1 for Rosenfeld, 2 for Eberly, 3 for
+// Guo-Hall, 4 for K3M, 5 for Huang, 6 for Stentiford and 7 for the medial
+// axis, `adjacency_mode` is only used by Rosenfeld. `report_progress` and
+// `cancellation_token` may be null, a cancelled call returns `Cancelled`.
+#[no_mangle]
+pub extern "C" fn improc_petrsu_skeletonizer_roi_process(
+    image_bytes: *const u8,
+    len: usize,
+    algorithm: i32,
+    adjacency_mode: i32,
+    roi_x: usize,
+    roi_y: usize,
+    roi_width: usize,
+    roi_height: usize,
+    mask_bytes: *const u8,
+    mask_len: usize,
+    polarity: i32,
+    report_progress: Option<unsafe extern "C" fn(i32, i32)>,
+    cancellation_token: *const CancellationToken,
+) -> Buffer {
+    catch_panic(failed_buffer(ErrorCode::Panic), || {
+        let roi = if mask_bytes.is_null() {
+            Ok(Roi::Rect(BoundingBox::new(
+                roi_x, roi_y, roi_width, roi_height,
+            )))
+        } else {
+            get_binary_image_from_raw_data(mask_bytes, mask_len, polarity).map(Roi::Mask)
+        };
+
+        let result = roi.and_then(|roi| {
+            let algorithm = get_algorithm(algorithm)?;
+            let mut image = get_binary_image_from_raw_data(image_bytes, len, polarity)?;
+            let result = skeletonize_with_algorithm(
+                &mut image,
+                algorithm,
+                get_adjacency_mode(adjacency_mode),
+                Some(&roi),
+                cancellable_progress(report_progress, cancellation_token),
+            );
+            Ok(result.map(|()| image))
+        });
+
+        match result {
+            Ok(Ok(image)) => rgb_image_to_raw_buffer(image.to_rgb_image()),
+            Ok(Err(Cancelled)) => cancelled_buffer(),
+            Err(err) => error_buffer(err),
+        }
+    })
+}
+
+// Skeletonizes an encoded image with any of the algorithms, the parameters
+// are as for `improc_petrsu_skeletonizer_roi_process`.
+#[no_mangle]
+pub extern "C" fn improc_petrsu_skeletonizer_process(
+    image_bytes: *const u8,
+    len: usize,
+    algorithm: i32,
+    adjacency_mode: i32,
+    polarity: i32,
+    report_progress: Option<unsafe extern "C" fn(i32, i32)>,
+    cancellation_token: *const CancellationToken,
+) -> Buffer {
+    catch_panic(failed_buffer(ErrorCode::Panic), || {
+        let result = get_algorithm(algorithm).and_then(|algorithm| {
+            let mut image = get_binary_image_from_raw_data(image_bytes, len, polarity)?;
+            Ok(skeletonize_with_algorithm(
+                &mut image,
+                algorithm,
+                get_adjacency_mode(adjacency_mode),
+                None,
+                cancellable_progress(report_progress, cancellation_token),
+            )
+            .map(|()| image))
+        });
+
+        match result {
+            Ok(Ok(image)) => rgb_image_to_raw_buffer(image.to_rgb_image()),
+            Ok(Err(Cancelled)) => cancelled_buffer(),
+            Err(err) => error_buffer(err),
+        }
+    })
+}
+
+// Skeletonizes raw pixels without decoding or encoding them. The `pixels`
+// are `height` rows `stride` bytes apart in the `pixel_format`: 0 for Gray8,
+// 1 for RGB8, 2 for RGBA8 and 3 for BGRA8, the alpha is ignored just like the
+// alpha of the decoded images is. The result is written in the same
+// format into `output` with rows `output_stride` bytes apart, which may be the
+// input itself, and an empty buffer is returned. With a null `output` the
+// returned buffer holds the result rows without gaps. `algorithm` is the one
+// of `improc_petrsu_skeletonizer_roi_process`. A cancelled call, see
+// `improc_petrsu_cancellation_token_new`, leaves `output` untouched.
+// `report_progress` and `cancellation_token` may be null.
+#[no_mangle]
+pub extern "C" fn improc_petrsu_skeletonizer_raw_process(
+    pixels: *const u8,
+    width: u32,
+    height: u32,
+    stride: usize,
+    pixel_format: i32,
+    algorithm: i32,
+    adjacency_mode: i32,
+    polarity: i32,
+    output: *mut u8,
+    output_stride: usize,
+    report_progress: Option<unsafe extern "C" fn(i32, i32)>,
+    cancellation_token: *const CancellationToken,
+) -> Buffer {
+    catch_panic(failed_buffer(ErrorCode::Panic), || {
+        let skeletonize = || -> Result<Buffer, ImageError> {
+            let format = get_pixel_format(pixel_format)?;
+            let polarity = get_polarity(polarity)?;
+            let algorithm = get_algorithm(algorithm)?;
+            let image = get_rgb_image_from_raw_pixels(pixels, width, height, stride, format)?;
+
+            let mut binary_image = BinaryImage::from_image(&image, PixelColor::White);
+            binary_image.set_polarity(polarity);
+            let result = skeletonize_with_algorithm(
+                &mut binary_image,
+                algorithm,
+                get_adjacency_mode(adjacency_mode),
+                None,
+                cancellable_progress(report_progress, cancellation_token),
+            );
+            if result.is_err() {
+                return Ok(cancelled_buffer());
+            }
+
+            write_raw_pixels(&binary_image.to_rgb_image(), format, output, output_stride)
+        };
+
+        match skeletonize() {
+            Ok(buffer) => buffer,
+            Err(err) => error_buffer(err),
+        }
+    })
+}
+
+// `converter` is 0 for a fixed threshold, 1 for Otsu, 2 for mean-C, 3 for
+// Niblack, 4 for Sauvola and 5 for Wolf, the fields a converter does not take
+// are ignored. A non-positive `r` is taken as for
+// `improc_petrsu_sauvola_binary_image_converter_process`.
+#[repr(C)]
+pub struct ConverterOptions {
+    converter: i32,
+    threshold: u32,
+    window_size: u32,
+    k: f64,
+    c: f64,
+    r: f64,
+}
+
+// Converts an encoded image to black and white with any of the converters.
+// `report_progress` and `cancellation_token` may be null, a cancelled call
+// returns `Cancelled`.
+#[no_mangle]
+pub extern "C" fn improc_petrsu_binary_image_converter_process(
+    image_bytes: *const u8,
+    len: usize,
+    options: *const ConverterOptions,
+    report_progress: Option<unsafe extern "C" fn(i32, i32)>,
+    cancellation_token: *const CancellationToken,
+) -> Buffer {
+    catch_panic(failed_buffer(ErrorCode::Panic), || {
+        let convert = || -> Result<Buffer, ImageError> {
+            let options = get_converter_options(options)?;
+            let converter = get_converter(options.converter)?;
+            let image = get_rgb_image_from_raw_data(image_bytes, len)?;
+
+            match convert_with_options(
+                &image,
+                converter,
+                options,
+                PixelColor::White,
+                cancellable_progress(report_progress, cancellation_token),
+            ) {
+                Ok(binary_image) => Ok(rgb_image_to_raw_buffer(binary_image.to_rgb_image())),
+                Err(Cancelled) => Ok(cancelled_buffer()),
+            }
+        };
+
+        match convert() {
+            Ok(buffer) => buffer,
+            Err(err) => error_buffer(err),
+        }
+    })
+}
+
+// Converts raw pixels to black and white without decoding or encoding them,
+// the pixels and the output are laid out as for
+// `improc_petrsu_skeletonizer_raw_process`.
+#[no_mangle]
+pub extern "C" fn improc_petrsu_binary_image_converter_raw_process(
+    pixels: *const u8,
+    width: u32,
+    height: u32,
+    stride: usize,
+    pixel_format: i32,
+    options: *const ConverterOptions,
+    output: *mut u8,
+    output_stride: usize,
+    report_progress: Option<unsafe extern "C" fn(i32, i32)>,
+    cancellation_token: *const CancellationToken,
+) -> Buffer {
+    catch_panic(failed_buffer(ErrorCode::Panic), || {
+        let convert = || -> Result<Buffer, ImageError> {
+            let options = get_converter_options(options)?;
+            let converter = get_converter(options.converter)?;
+            let format = get_pixel_format(pixel_format)?;
+            let image = get_rgb_image_from_raw_pixels(pixels, width, height, stride, format)?;
+
+            let binary_image = match convert_with_options(
+                &image,
+                converter,
+                options,
+                PixelColor::White,
+                cancellable_progress(report_progress, cancellation_token),
+            ) {
+                Ok(binary_image) => binary_image,
+                Err(Cancelled) => return Ok(cancelled_buffer()),
+            };
+
+            write_raw_pixels(&binary_image.to_rgb_image(), format, output, output_stride)
+        };
+
+        match convert() {
+            Ok(buffer) => buffer,
+            Err(err) => error_buffer(err),
+        }
+    })
+}
+
+// An empty `width` by `height` image with black foreground. The handles of
+// the calls below are freed with `improc_petrsu_binary_image_free`, the calls
+// returning one return null on failure, `improc_petrsu_last_error_code` then
+// tells why.
+#[no_mangle]
+pub extern "C" fn improc_petrsu_binary_image_new(width: u32, height: u32) -> *mut BinaryImage {
+    catch_panic(std::ptr::null_mut(), || {
+        image_to_handle(Ok(BinaryImage::new(
+            width as usize,
+            height as usize,
+            PixelColor::White,
+        )))
+    })
+}
+
+#[no_mangle]
+pub extern "C" fn improc_petrsu_binary_image_from_bytes(
+    image_bytes: *const u8,
+    len: usize,
+    polarity: i32,
+) -> *mut BinaryImage {
+    catch_panic(std::ptr::null_mut(), || {
+        image_to_handle(get_binary_image_from_raw_data(image_bytes, len, polarity))
+    })
+}
+
+// The pixels are laid out as for `improc_petrsu_skeletonizer_raw_process`.
+#[no_mangle]
+pub extern "C" fn improc_petrsu_binary_image_from_raw(
+    pixels: *const u8,
+    width: u32,
+    height: u32,
+    stride: usize,
+    pixel_format: i32,
+    polarity: i32,
+) -> *mut BinaryImage {
+    catch_panic(std::ptr::null_mut(), || {
+        let from_raw = || -> Result<BinaryImage, ImageError> {
+            let format = get_pixel_format(pixel_format)?;
+            let polarity = get_polarity(polarity)?;
+            let image = get_rgb_image_from_raw_pixels(pixels, width, height, stride, format)?;
+
+            let mut binary_image = BinaryImage::from_image(&image, PixelColor::White);
+            binary_image.set_polarity(polarity);
+
+            Ok(binary_image)
+        };
+
+        image_to_handle(from_raw())
+    })
+}
+
+// Thresholds raw pixels with the converter of the `options`.
+#[no_mangle]
+pub extern "C" fn improc_petrsu_binary_image_convert_raw(
+    pixels: *const u8,
+    width: u32,
+    height: u32,
+    stride: usize,
+    pixel_format: i32,
+    options: *const ConverterOptions,
+    polarity: i32,
+) -> *mut BinaryImage {
+    catch_panic(std::ptr::null_mut(), || {
+        let convert = || -> Result<Result<BinaryImage, Cancelled>, ImageError> {
+            let options = get_converter_options(options)?;
+            let converter = get_converter(options.converter)?;
+            let format = get_pixel_format(pixel_format)?;
+            let polarity = get_polarity(polarity)?;
+            let image = get_rgb_image_from_raw_pixels(pixels, width, height, stride, format)?;
+
+            let result =
+                convert_with_options(&image, converter, options, PixelColor::White, |_, _| {
+                    Progress::Continue
+                });
+
+            Ok(result.map(|mut binary_image| {
+                binary_image.set_polarity(polarity);
+                binary_image
+            }))
+        };
+
+        match convert() {
+            Ok(Ok(binary_image)) => image_to_handle(Ok(binary_image)),
+            // nothing can cancel it without a callback
+            Ok(Err(Cancelled)) => {
+                record_cancelled();
+                std::ptr::null_mut()
+            }
+            Err(err) => image_to_handle(Err(err)),
+        }
+    })
+}
+
+#[no_mangle]
+pub extern "C" fn improc_petrsu_binary_image_clone(handle: *const BinaryImage) -> *mut BinaryImage {
+    catch_panic(std::ptr::null_mut(), || {
+        image_to_handle(get_image(handle).cloned())
+    })
+}
+
+#[no_mangle]
+pub extern "C" fn improc_petrsu_binary_image_free(handle: *mut BinaryImage) {
+    catch_panic((), || {
+        if !handle.is_null() {
+            drop_handle(handle);
+        }
+    })
+}
+
+#[no_mangle]
+pub extern "C" fn improc_petrsu_binary_image_width(handle: *const BinaryImage) -> u32 {
+    catch_panic(0, || {
+        get_image(handle).map_or(0, |image| image.width() as u32)
+    })
+}
+
+#[no_mangle]
+pub extern "C" fn improc_petrsu_binary_image_height(handle: *const BinaryImage) -> u32 {
+    catch_panic(0, || {
+        get_image(handle).map_or(0, |image| image.height() as u32)
+    })
+}
+
+// 1 for a foreground pixel, 0 for a background one and -1 on failure.
+#[no_mangle]
+pub extern "C" fn improc_petrsu_binary_image_get_pixel(
+    handle: *const BinaryImage,
+    x: u32,
+    y: u32,
+) -> i32 {
+    catch_panic(-1, || {
+        let get_pixel = || -> Result<bool, ImageError> {
+            let image = get_image(handle)?;
+            check_pixel_position(image, x, y)?;
+
+            Ok(image.is_fg(x as usize, y as usize))
+        };
+
+        match get_pixel() {
+            Ok(is_fg) => is_fg as i32,
+            Err(err) => {
+                record_error(err);
+                -1
+            }
+        }
+    })
+}
+
+#[no_mangle]
+pub extern "C" fn improc_petrsu_binary_image_set_pixel(
+    handle: *mut BinaryImage,
+    x: u32,
+    y: u32,
+    is_fg: i32,
+) -> ErrorCode {
+    catch_panic(ErrorCode::Panic, || {
+        to_error_code(get_image_mut(handle).and_then(|image| {
+            check_pixel_position(image, x, y)?;
+
+            if is_fg != 0 {
+                image.set_fg(x as usize, y as usize);
+            } else {
+                image.set_bg(x as usize, y as usize);
+            }
+
+            Ok(())
+        }))
+    })
+}
+
+// Options of `improc_petrsu_skeletonize_handle`, null options mean the
+// 8-adjacency, the whole image, no progress reports and no cancellation.
+#[repr(C)]
+pub struct SkeletonizerOptions {
+    adjacency_mode: i32,
+    // the foreground of this image is the region of interest, null for the
+    // whole image
+    roi_mask: *const BinaryImage,
+    report_progress: Option<unsafe extern "C" fn(i32, i32)>,
+    // checked between the iterations, null if the call cannot be cancelled
+    cancellation_token: *const CancellationToken,
+}
+
+// Skeletonizes the image in place, `algorithm` is the one of
+// `improc_petrsu_skeletonizer_roi_process`. A cancelled call returns
+// `Cancelled` and leaves the image as it was.
+#[no_mangle]
+pub extern "C" fn improc_petrsu_skeletonize_handle(
+    handle: *mut BinaryImage,
+    algorithm: i32,
+    options: *const SkeletonizerOptions,
+) -> ErrorCode {
+    catch_panic(ErrorCode::Panic, || {
+        let skeletonize = || -> Result<ErrorCode, ImageError> {
+            let algorithm = get_algorithm(algorithm)?;
+            // the mask may be the image itself, so it is copied before the
+            // image is borrowed to be changed
+            let (mode, roi, report_progress, token) = match get_skeletonizer_options(options) {
+                Some(options) => (
+                    get_adjacency_mode(options.adjacency_mode),
+                    get_optional_image(options.roi_mask).map(|mask| Roi::Mask(mask.clone())),
+                    options.report_progress,
+                    options.cancellation_token,
+                ),
+                None => (AdjacencyMode::Eight, None, None, std::ptr::null()),
+            };
+            let image = get_image_mut(handle)?;
+
+            let result = skeletonize_with_algorithm(
+                image,
+                algorithm,
+                mode,
+                roi.as_ref(),
+                cancellable_progress(report_progress, token),
+            );
+
+            Ok(match result {
+                Ok(()) => ErrorCode::Ok,
+                Err(Cancelled) => record_cancelled(),
+            })
+        };
+
+        skeletonize().unwrap_or_else(record_error)
+    })
+}
+
+// A token to cancel the calls it is passed to, from any thread, with
+// `improc_petrsu_cancellation_token_cancel`. It is freed with
+// `improc_petrsu_cancellation_token_free` once no call uses it.
+#[no_mangle]
+pub extern "C" fn improc_petrsu_cancellation_token_new() -> *mut CancellationToken {
+    catch_panic(std::ptr::null_mut(), || {
+        Box::into_raw(Box::new(CancellationToken::new()))
+    })
+}
+
+#[no_mangle]
+pub extern "C" fn improc_petrsu_cancellation_token_cancel(
+    token: *const CancellationToken,
+) -> ErrorCode {
+    catch_panic(ErrorCode::Panic, || match get_token(token) {
+        Some(token) => {
+            token.cancel();
+            ErrorCode::Ok
+        }
+        None => record_error(invalid_argument("the cancellation token is null")),
+    })
+}
+
+#[no_mangle]
+pub extern "C" fn improc_petrsu_cancellation_token_free(token: *mut CancellationToken) {
+    catch_panic((), || {
+        if !token.is_null() {
+            drop_token(token);
+        }
+    })
+}
+
+// Removes the foreground components with fewer than `min_area` pixels.
+#[no_mangle]
+pub extern "C" fn improc_petrsu_area_open_handle(
+    handle: *mut BinaryImage,
+    min_area: usize,
+    adjacency_mode: i32,
+) -> ErrorCode {
+    catch_panic(ErrorCode::Panic, || {
+        to_error_code(get_image_mut(handle).map(|image| {
+            image.area_open(min_area, get_adjacency_mode(adjacency_mode));
+        }))
+    })
+}
+
+// Prunes a skeleton in place, `shape` may be null or the image before the
+// skeletonization, the rest is as for `improc_petrsu_spur_pruner_process`.
+#[no_mangle]
+pub extern "C" fn improc_petrsu_spur_pruner_handle(
+    handle: *mut BinaryImage,
+    shape: *const BinaryImage,
+    max_length: f64,
+    length_mode: i32,
+    is_iterative: i32,
+    adjacency_mode: i32,
+) -> ErrorCode {
+    catch_panic(ErrorCode::Panic, || {
+        let prune = || -> Result<(), ImageError> {
+            let pruner = SpurPruner::new(
+                get_spur_length(length_mode, max_length)?,
+                get_adjacency_mode(adjacency_mode),
+                is_iterative != 0,
+            );
+
+            // the shape may be the image itself, then it is pruned against a copy
+            let shape_copy = if std::ptr::eq(shape, handle) {
+                get_optional_image(shape).cloned()
+            } else {
+                None
+            };
+            let shape = shape_copy.as_ref().or_else(|| get_optional_image(shape));
+
+            pruner.prune(get_image_mut(handle)?, shape);
+
+            Ok(())
+        };
+
+        to_error_code(prune())
+    })
+}
+
+// The image encoded as PNG.
+#[no_mangle]
+pub extern "C" fn improc_petrsu_binary_image_to_bytes(handle: *const BinaryImage) -> Buffer {
+    catch_panic(failed_buffer(ErrorCode::Panic), || {
+        match get_image(handle) {
+            Ok(image) => rgb_image_to_raw_buffer(image.to_rgb_image()),
+            Err(err) => error_buffer(err),
+        }
+    })
+}
+
+// The image as raw pixels, laid out as the output of
+// `improc_petrsu_skeletonizer_raw_process`.
+#[no_mangle]
+pub extern "C" fn improc_petrsu_binary_image_to_raw(
+    handle: *const BinaryImage,
+    pixel_format: i32,
+    output: *mut u8,
+    output_stride: usize,
+) -> Buffer {
+    catch_panic(failed_buffer(ErrorCode::Panic), || {
+        let to_raw = || -> Result<Buffer, ImageError> {
+            let image = get_image(handle)?;
+            let format = get_pixel_format(pixel_format)?;
+
+            write_raw_pixels(&image.to_rgb_image(), format, output, output_stride)
+        };
+
+        match to_raw() {
+            Ok(buffer) => buffer,
+            Err(err) => error_buffer(err),
+        }
+    })
+}
+
+// One bit per pixel, 1 for the foreground. Every row starts on a new byte and
+// pixel `x` is bit `x % 8` of byte `x / 8` of its row.
+#[no_mangle]
+pub extern "C" fn improc_petrsu_binary_image_to_bits(handle: *const BinaryImage) -> Buffer {
+    catch_panic(failed_buffer(ErrorCode::Panic), || {
+        match get_image(handle) {
+            Ok(image) => bytes_to_buffer(binary_image_to_bits(image)),
+            Err(err) => error_buffer(err),
+        }
+    })
+}
+
+#[no_mangle]
+pub extern "C" fn improc_petrsu_free(buf: Buffer) {
+    catch_panic((), || {
+        // failed calls return a null buffer, there is nothing to free then
+        if buf.data.is_null() {
+            return;
+        }
+
+        // the data is a boxed slice, it has to be freed with its length
+        let slice = std::ptr::slice_from_raw_parts_mut(buf.data, buf.len);
+
+        unsafe {
+            drop(Box::from_raw(slice));
+        }
+    })
+}
+
+fn skeletonize<T, F>(
+    image_bytes: *const u8,
+    len: usize,
+    skeletonizer: T,
+    polarity: i32,
+    report_progress: F,
+) -> Result<Buffer, ImageError>
+where
+    T: Skeletonizer,
+    F: Fn(i32, i32) -> Progress,
+{
+    let mut binary_image = get_binary_image_from_raw_data(image_bytes, len, polarity)?;
+
+    if skeletonizer
+        .process_with_progress(&mut binary_image, None, report_progress)
+        .is_err()
+    {
+        return Ok(cancelled_buffer());
+    }
+
+    let result_image = binary_image.to_rgb_image();
+
+    Ok(rgb_image_to_raw_buffer(result_image))
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Algorithm {
+    ZhangSuen,
+    Rosenfeld,
+    Eberly,
+    GuoHall,
+    K3M,
+    Huang,
+    Stentiford,
+    MedialAxis,
+}
+
+fn get_algorithm(algorithm: i32) -> Result<Algorithm, ImageError> {
+    match algorithm {
+        0 => Ok(Algorithm::ZhangSuen),
+        1 => Ok(Algorithm::Rosenfeld),
+        2 => Ok(Algorithm::Eberly),
+        3 => Ok(Algorithm::GuoHall),
+        4 => Ok(Algorithm::K3M),
+        5 => Ok(Algorithm::Huang),
+        6 => Ok(Algorithm::Stentiford),
+        7 => Ok(Algorithm::MedialAxis),
+        _ => Err(invalid_argument("the skeletonization algorithm is unknown")),
+    }
+}
+
+fn skeletonize_with_algorithm<F>(
+    image: &mut BinaryImage,
+    algorithm: Algorithm,
+    mode: AdjacencyMode,
+    roi: Option<&Roi>,
+    report_progress: F,
+) -> Result<(), Cancelled>
+where
+    F: Fn(i32, i32) -> Progress,
+{
+    match algorithm {
+        Algorithm::ZhangSuen => {
+            ZhangSuenSkeletonizer::new().process_with_progress(image, roi, report_progress)
+        }
+        Algorithm::Rosenfeld => {
+            RosenfeldSkeletonizer::new(mode).process_with_progress(image, roi, report_progress)
+        }
+        Algorithm::Eberly => {
+            EberlySkeletonizer::new().process_with_progress(image, roi, report_progress)
+        }
+        Algorithm::GuoHall => {
+            GuoHallSkeletonizer::new().process_with_progress(image, roi, report_progress)
+        }
+        Algorithm::K3M => K3MSkeletonizer::new().process_with_progress(image, roi, report_progress),
+        Algorithm::Huang => {
+            HuangSkeletonizer::new().process_with_progress(image, roi, report_progress)
+        }
+        Algorithm::Stentiford => {
+            StentifordSkeletonizer::new().process_with_progress(image, roi, report_progress)
+        }
+        Algorithm::MedialAxis => {
+            MedialAxisSkeletonizer::new().process_with_progress(image, roi, report_progress)
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Converter {
+    Threshold,
+    Otsu,
+    MeanC,
+    Niblack,
+    Sauvola,
+    Wolf,
+}
+
+fn get_converter(converter: i32) -> Result<Converter, ImageError> {
+    match converter {
+        0 => Ok(Converter::Threshold),
+        1 => Ok(Converter::Otsu),
+        2 => Ok(Converter::MeanC),
+        3 => Ok(Converter::Niblack),
+        4 => Ok(Converter::Sauvola),
+        5 => Ok(Converter::Wolf),
+        _ => Err(invalid_argument("the converter is unknown")),
+    }
+}
+
+fn convert_with_options<F>(
+    image: &RgbImage,
+    converter: Converter,
+    options: &ConverterOptions,
+    bg_color: PixelColor,
+    report_progress: F,
+) -> Result<BinaryImage, Cancelled>
+where
+    F: Fn(i32, i32) -> Progress,
+{
+    let (window_size, k) = (options.window_size, options.k);
+
+    match converter {
+        Converter::Threshold => ThresholdBinaryImageConverter::new(options.threshold)
+            .convert_to_binary_image_with_progress(image, bg_color, report_progress),
+        Converter::Otsu => OtsuBinaryImageConverter::new().convert_to_binary_image_with_progress(
+            image,
+            bg_color,
+            report_progress,
+        ),
+        Converter::MeanC => MeanCBinaryImageConverter::new(window_size, options.c)
+            .convert_to_binary_image_with_progress(image, bg_color, report_progress),
+        Converter::Niblack => NiblackBinaryImageConverter::new(window_size, k)
+            .convert_to_binary_image_with_progress(image, bg_color, report_progress),
+        Converter::Sauvola => get_sauvola_converter(window_size, k, options.r)
+            .convert_to_binary_image_with_progress(image, bg_color, report_progress),
+        Converter::Wolf => WolfBinaryImageConverter::new(window_size, k)
+            .convert_to_binary_image_with_progress(image, bg_color, report_progress),
+    }
+}
+
+fn get_sauvola_converter(window_size: u32, k: f64, r: f64) -> SauvolaBinaryImageConverter {
+    if r > 0.0 {
+        SauvolaBinaryImageConverter::new(window_size, k, r)
+    } else {
+        SauvolaBinaryImageConverter::with_default_range(window_size, k)
+    }
+}
+
+fn apply_morphology(
+    image_bytes: *const u8,
+    len: usize,
+    operation: i32,
+    element: &StructuringElement,
+    polarity: i32,
+) -> Result<Buffer, ImageError> {
+    let operation = get_morphology_operation(operation)?;
+    let image = get_binary_image_from_raw_data(image_bytes, len, polarity)?;
+
+    Ok(rgb_image_to_raw_buffer(
+        operation(&image, element).to_rgb_image(),
+    ))
+}
+
+fn get_morphology_operation(
+    operation: i32,
+) -> Result<fn(&BinaryImage, &StructuringElement) -> BinaryImage, ImageError> {
+    match operation {
+        0 => Ok(morphology::erode),
+        1 => Ok(morphology::dilate),
+        2 => Ok(morphology::open),
+        3 => Ok(morphology::close),
+        4 => Ok(morphology::gradient),
+        _ => Err(invalid_argument("the morphology operation is unknown")),
+    }
+}
+
+fn get_structuring_element(element: i32, radius: usize) -> Result<StructuringElement, ImageError> {
+    match element {
+        0 => Ok(StructuringElement::square(radius)),
+        1 => Ok(StructuringElement::cross(radius)),
+        2 => Ok(StructuringElement::disk(radius)),
+        _ => Err(invalid_argument("the structuring element is unknown")),
+    }
+}
+
+fn get_structuring_element_from_mask(
+    mask: *const u8,
+    width: usize,
+    height: usize,
+    (origin_x, origin_y): (usize, usize),
+) -> Result<StructuringElement, ImageError> {
+    if mask.is_null() {
+        return Err(invalid_argument("the structuring element mask is null"));
+    }
+
+    let len = width
+        .checked_mul(height)
+        .ok_or_else(|| invalid_argument("the structuring element mask is too large"))?;
+    let mask = unsafe { std::slice::from_raw_parts(mask, len) };
+
+    let offsets = (0..height)
+        .flat_map(|y| (0..width).map(move |x| (x, y)))
+        .filter(|&(x, y)| mask[y * width + x] != 0)
+        .map(|(x, y)| (x as i32 - origin_x as i32, y as i32 - origin_y as i32))
+        .collect();
+
+    Ok(StructuringElement::from_offsets(offsets))
+}
+
+fn get_templates_from_raw_data(
+    templates: *const u8,
+    count: usize,
+    width: usize,
+    height: usize,
+) -> Result<Vec<Template>, ImageError> {
+    if count == 0 || width == 0 || height == 0 {
+        return Err(invalid_argument("the templates are empty"));
+    }
+
+    if templates.is_null() {
+        return Err(invalid_argument("the templates are null"));
+    }
+
+    let len = count
+        .checked_mul(width)
+        .and_then(|len| len.checked_mul(height))
+        .ok_or_else(|| invalid_argument("the templates are too large"))?;
+    let cells = unsafe { std::slice::from_raw_parts(templates, len) };
+
+    Ok(cells
+        .chunks(width * height)
+        .map(|mask| {
+            let cells = mask
+                .iter()
+                .map(|&cell| match cell {
+                    1 => TemplateCell::Foreground,
+                    0 => TemplateCell::Background,
+                    _ => TemplateCell::DontCare,
+                })
+                .collect();
+            Template::new(width, height, width / 2, height / 2, cells)
+        })
+        .collect())
+}
+
+fn convert<T, F>(
+    image_bytes: *const u8,
+    len: usize,
+    converter: &T,
+    report_progress: F,
+) -> Result<Buffer, ImageError>
+where
+    T: BinaryImageConverter,
+    F: Fn(i32, i32) -> Progress,
+{
+    let mut image = get_rgb_image_from_raw_data(image_bytes, len)?;
+
+    if converter
+        .convert_to_binary_with_progress(&mut image, None, report_progress)
+        .is_err()
+    {
+        return Ok(cancelled_buffer());
+    }
+
+    Ok(rgb_image_to_raw_buffer(image))
+}
+
+fn get_polarity(polarity: i32) -> Result<Polarity, ImageError> {
+    match polarity {
+        0 => Ok(Polarity::BlackForeground),
+        1 => Ok(Polarity::WhiteForeground),
+        2 => Ok(Polarity::Auto),
+        _ => Err(invalid_argument("the polarity is unknown")),
+    }
+}
+
+fn get_spur_length(length_mode: i32, max_length: f64) -> Result<SpurLength, ImageError> {
+    match length_mode {
+        0 => Ok(SpurLength::Pixels(max_length)),
+        1 => Ok(SpurLength::StrokeWidth(max_length)),
+        _ => Err(invalid_argument("the length mode is unknown")),
+    }
+}
+
+fn get_vector_format(format: i32) -> Result<VectorFormat, ImageError> {
+    match format {
+        0 => Ok(VectorFormat::Svg),
+        1 => Ok(VectorFormat::GeoJson),
+        2 => Ok(VectorFormat::Json),
+        _ => Err(invalid_argument("the vector format is unknown")),
+    }
+}
+
+fn get_update_mode(mode: i32) -> Result<UpdateMode, ImageError> {
+    match mode {
+        0 => Ok(UpdateMode::Sequential),
+        1 => Ok(UpdateMode::Parallel),
+        _ => Err(invalid_argument("the update mode is unknown")),
+    }
+}
+
+fn get_adjacency_mode(adjacency_mode: i32) -> AdjacencyMode {
+    if adjacency_mode == 0 {
+        AdjacencyMode::Eight
+    } else {
+        AdjacencyMode::Four
+    }
+}
+
+fn get_binary_image_from_raw_data(
+    image_bytes: *const u8,
+    len: usize,
+    polarity: i32,
+) -> Result<BinaryImage, ImageError> {
+    let polarity = get_polarity(polarity)?;
+    let original_image = get_rgb_image_from_raw_data(image_bytes, len)?;
+
+    let mut binary_image = BinaryImage::from_image(&original_image, PixelColor::White);
+    binary_image.set_polarity(polarity);
+
+    Ok(binary_image)
+}
+
+fn get_rgb_image_from_raw_data(image_bytes: *const u8, len: usize) -> Result<RgbImage, ImageError> {
+    if image_bytes.is_null() {
+        return Err(invalid_argument("the image bytes are null"));
+    }
+
+    let slice = unsafe { std::slice::from_raw_parts(image_bytes, len) };
+
+    Ok(image::load_from_memory(slice)?.to_rgb8())
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum PixelFormat {
+    Gray8,
+    Rgb8,
+    Rgba8,
+    Bgra8,
+}
+
+impl PixelFormat {
+    fn bytes_per_pixel(self) -> usize {
+        match self {
+            PixelFormat::Gray8 => 1,
+            PixelFormat::Rgb8 => 3,
+            PixelFormat::Rgba8 | PixelFormat::Bgra8 => 4,
+        }
+    }
+}
+
+fn get_pixel_format(pixel_format: i32) -> Result<PixelFormat, ImageError> {
+    match pixel_format {
+        0 => Ok(PixelFormat::Gray8),
+        1 => Ok(PixelFormat::Rgb8),
+        2 => Ok(PixelFormat::Rgba8),
+        3 => Ok(PixelFormat::Bgra8),
+        _ => Err(invalid_argument("unknown pixel format")),
+    }
+}
+
+fn get_converter_options<'a>(
+    options: *const ConverterOptions,
+) -> Result<&'a ConverterOptions, ImageError> {
+    if options.is_null() {
+        return Err(invalid_argument("the converter options are null"));
+    }
+
+    Ok(unsafe { &*options })
+}
+
+// The length a buffer of `height` rows `stride` bytes apart needs, the last
+// row may end right after its pixels.
+fn get_raw_pixels_len(
+    width: usize,
+    height: usize,
+    stride: usize,
+    format: PixelFormat,
+) -> Result<usize, ImageError> {
+    let too_large = || invalid_argument("the pixels are too large");
+
+    let row_len = width
+        .checked_mul(format.bytes_per_pixel())
+        .ok_or_else(too_large)?;
+    if stride < row_len {
+        return Err(invalid_argument("the stride is shorter than a row"));
+    }
+
+    match height.checked_sub(1) {
+        None => Ok(0),
+        Some(rows) => rows
+            .checked_mul(stride)
+            .and_then(|len| len.checked_add(row_len))
+            .ok_or_else(too_large),
+    }
+}
+
+fn get_rgb_image_from_raw_pixels(
+    pixels: *const u8,
+    width: u32,
+    height: u32,
+    stride: usize,
+    format: PixelFormat,
+) -> Result<RgbImage, ImageError> {
+    if pixels.is_null() {
+        return Err(invalid_argument("the pixels are null"));
+    }
+
+    let len = get_raw_pixels_len(width as usize, height as usize, stride, format)?;
+    let pixels = unsafe { std::slice::from_raw_parts(pixels, len) };
+
+    let mut image = RgbImage::new(width, height);
+    if width == 0 || height == 0 {
+        return Ok(image);
+    }
+
+    // the last row may be shorter than the stride, but not than the pixels
+    for (source, target) in pixels.chunks(stride).zip(image.rows_mut()) {
+        for (pixel, target) in source.chunks_exact(format.bytes_per_pixel()).zip(target) {
+            *target = match format {
+                PixelFormat::Gray8 => Rgb([pixel[0], pixel[0], pixel[0]]),
+                PixelFormat::Rgb8 | PixelFormat::Rgba8 => Rgb([pixel[0], pixel[1], pixel[2]]),
+                PixelFormat::Bgra8 => Rgb([pixel[2], pixel[1], pixel[0]]),
+            };
+        }
+    }
+
+    Ok(image)
+}
+
+// Writes the image into `output` in the `format`, or returns the rows without
+// gaps if `output` is null. The alpha of the result is opaque.
+fn write_raw_pixels(
+    image: &RgbImage,
+    format: PixelFormat,
+    output: *mut u8,
+    output_stride: usize,
+) -> Result<Buffer, ImageError> {
+    let (width, height) = (image.width() as usize, image.height() as usize);
+    let bytes_per_pixel = format.bytes_per_pixel();
+
+    let stride = if output.is_null() {
+        width.saturating_mul(bytes_per_pixel)
+    } else {
+        output_stride
+    };
+    let len = get_raw_pixels_len(width, height, stride, format)?;
+
+    let mut bytes = if output.is_null() {
+        vec![0; len]
+    } else {
+        Vec::new()
+    };
+    let pixels = if output.is_null() {
+        &mut bytes[..]
+    } else {
+        unsafe { std::slice::from_raw_parts_mut(output, len) }
+    };
+
+    if width == 0 || height == 0 {
+        return Ok(bytes_to_buffer(bytes));
+    }
+
+    for (target, source) in pixels.chunks_mut(stride).zip(image.rows()) {
+        for (pixel, &Rgb([r, g, b])) in target.chunks_exact_mut(bytes_per_pixel).zip(source) {
+            match format {
+                PixelFormat::Gray8 => pixel[0] = r,
+                PixelFormat::Rgb8 => pixel.copy_from_slice(&[r, g, b]),
+                PixelFormat::Rgba8 => pixel.copy_from_slice(&[r, g, b, 255]),
+                PixelFormat::Bgra8 => pixel.copy_from_slice(&[b, g, r, 255]),
+            }
+        }
+    }
+
+    Ok(bytes_to_buffer(bytes))
+}
+
+fn image_to_handle(result: Result<BinaryImage, ImageError>) -> *mut BinaryImage {
+    match result {
+        Ok(image) => Box::into_raw(Box::new(image)),
+        Err(err) => {
+            record_error(err);
+            std::ptr::null_mut()
+        }
+    }
+}
+
+fn drop_handle(handle: *mut BinaryImage) {
+    unsafe {
+        drop(Box::from_raw(handle));
+    }
+}
+
+fn get_image<'a>(handle: *const BinaryImage) -> Result<&'a BinaryImage, ImageError> {
+    get_optional_image(handle).ok_or_else(|| invalid_argument("the image handle is null"))
+}
+
+fn get_image_mut<'a>(handle: *mut BinaryImage) -> Result<&'a mut BinaryImage, ImageError> {
+    if handle.is_null() {
+        return Err(invalid_argument("the image handle is null"));
+    }
+
+    Ok(unsafe { &mut *handle })
+}
+
+fn get_optional_image<'a>(handle: *const BinaryImage) -> Option<&'a BinaryImage> {
+    if handle.is_null() {
+        None
+    } else {
+        Some(unsafe { &*handle })
+    }
+}
+
+fn get_skeletonizer_options<'a>(
+    options: *const SkeletonizerOptions,
+) -> Option<&'a SkeletonizerOptions> {
+    if options.is_null() {
+        None
+    } else {
+        Some(unsafe { &*options })
+    }
+}
+
+fn get_token<'a>(token: *const CancellationToken) -> Option<&'a CancellationToken> {
+    if token.is_null() {
+        None
+    } else {
+        Some(unsafe { &*token })
+    }
+}
+
+fn drop_token(token: *mut CancellationToken) {
+    unsafe {
+        drop(Box::from_raw(token));
+    }
+}
+
+// Forwards the progress to the optional callback and cancels once the
+// optional token is cancelled.
+fn cancellable_progress(
+    report_progress: Option<unsafe extern "C" fn(i32, i32)>,
+    token: *const CancellationToken,
+) -> impl Fn(i32, i32) -> Progress {
+    let token = get_token(token);
+
+    move |current, max| {
+        if let Some(report_progress) = report_progress {
+            unsafe { report_progress(current, max) }
+        }
+
+        token.map_or(Progress::Continue, CancellationToken::progress)
+    }
+}
+
+fn check_pixel_position(image: &BinaryImage, x: u32, y: u32) -> Result<(), ImageError> {
+    if x as usize >= image.width() || y as usize >= image.height() {
+        return Err(invalid_argument("the pixel is outside of the image"));
+    }
+
+    Ok(())
+}
+
+fn binary_image_to_bits(image: &BinaryImage) -> Vec<u8> {
+    let row_len = image.width().div_ceil(8);
+    let mut bits = Vec::with_capacity(row_len * image.height());
+
+    for y in 0..image.height() {
+        let row: Vec<u8> = image
+            .row_words(y)
+            .iter()
+            .flat_map(|word| word.to_le_bytes())
+            .take(row_len)
+            .collect();
+        bits.extend(row);
+    }
+
+    bits
+}
+
+fn write_out_value<T>(destination: *mut T, value: T) {
+    if !destination.is_null() {
+        unsafe {
+            *destination = value;
+        }
+    }
+}
+
+fn rgb_image_to_raw_buffer(image: RgbImage) -> Buffer {
+    let dyn_image = DynamicImage::ImageRgb8(image);
+    let mut vector = Vec::new();
+    match dyn_image.write_to(&mut vector, ImageOutputFormat::Png) {
+        Ok(_) => bytes_to_buffer(vector),
+        Err(err) => error_buffer(err),
+    }
+}
+
+fn bytes_to_buffer(bytes: Vec<u8>) -> Buffer {
+    let slice = bytes.into_boxed_slice();
+    let len = slice.len();
+    let data = Box::into_raw(slice);
+
+    Buffer {
+        data: data as *mut u8,
+        len,
+        error: ErrorCode::Ok,
+    }
+}
+
+fn error_buffer(err: ImageError) -> Buffer {
+    failed_buffer(record_error(err))
+}
+
+fn cancelled_buffer() -> Buffer {
+    failed_buffer(record_cancelled())
+}
+
+fn failed_buffer(error: ErrorCode) -> Buffer {
+    Buffer {
+        data: std::ptr::null_mut::<u8>(),
+        len: 0,
+        error,
+    }
+}
+
+// Unwinding into the caller is undefined behaviour, so every exported call
+// runs in here and a panic turns into `on_panic` with its message kept for
+// `improc_petrsu_last_error_message`.
+fn catch_panic<T, F>(on_panic: T, f: F) -> T
+where
+    F: FnOnce() -> T,
+{
+    match panic::catch_unwind(AssertUnwindSafe(f)) {
+        Ok(value) => value,
+        Err(payload) => {
+            let message = if let Some(message) = payload.downcast_ref::<&str>() {
+                message.to_string()
+            } else if let Some(message) = payload.downcast_ref::<String>() {
+                message.clone()
+            } else {
+                "unknown panic".to_string()
+            };
+            record_message(ErrorCode::Panic, format!("Internal error: {}", message));
+
+            on_panic
+        }
+    }
+}
+
+fn to_error_code(result: Result<(), ImageError>) -> ErrorCode {
+    match result {
+        Ok(()) => ErrorCode::Ok,
+        Err(err) => record_error(err),
+    }
+}
+
+// Keeps the error for `improc_petrsu_last_error_message` and returns its code.
+fn record_error(err: ImageError) -> ErrorCode {
+    let code = match err {
+        ImageError::Decoding(_) => ErrorCode::Decoding,
+        ImageError::Encoding(_) => ErrorCode::Encoding,
+        ImageError::Parameter(_) => ErrorCode::InvalidArgument,
+        ImageError::Limits(_) => ErrorCode::Limits,
+        ImageError::Unsupported(_) => ErrorCode::UnsupportedFormat,
+        ImageError::IoError(_) => ErrorCode::Io,
+    };
+
+    record_message(code, err.to_string());
+
+    code
+}
+
+fn record_cancelled() -> ErrorCode {
+    record_message(ErrorCode::Cancelled, Cancelled.to_string());
+
+    ErrorCode::Cancelled
+}
+
+fn record_message(code: ErrorCode, message: String) {
+    // a message with an inner nul is cut there instead of getting lost
+    let bytes: Vec<u8> = message
+        .into_bytes()
+        .into_iter()
+        .take_while(|&b| b != 0)
+        .collect();
+    let message = CString::new(bytes).unwrap_or_default();
+
+    LAST_ERROR.with(|error| *error.borrow_mut() = Some((code, message)));
+}
+
+fn invalid_argument(message: &str) -> ImageError {
+    ImageError::Parameter(ParameterError::from_kind(ParameterErrorKind::Generic(
+        message.to_string(),
+    )))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::ffi::CStr;
+
+    fn last_error_message() -> String {
+        let message = improc_petrsu_last_error_message();
+        assert!(!message.is_null());
+
+        unsafe { CStr::from_ptr(message) }
+            .to_string_lossy()
+            .into_owned()
+    }
+
+    #[test]
+    fn error_code_test() {
+        // Arrange
+        let garbage = [1u8, 2, 3, 4];
+        let mut png = Vec::new();
+        DynamicImage::ImageRgb8(RgbImage::new(4, 4))
+            .write_to(&mut png, ImageOutputFormat::Png)
+            .unwrap();
+
+        // Act
+        let unknown = improc_petrsu_skeleton_graph_process(garbage.as_ptr(), garbage.len(), 0, 0);
+        let unknown_message = last_error_message();
+        let null = improc_petrsu_skeleton_graph_process(std::ptr::null(), 0, 0, 0);
+        let null_message = last_error_message();
+        let ok = improc_petrsu_skeleton_graph_process(png.as_ptr(), png.len(), 0, 0);
+
+        // Assert
+        assert_eq!(ErrorCode::UnsupportedFormat, unknown.error);
+        assert!(unknown.data.is_null());
+        assert!(!unknown_message.is_empty());
+        assert_eq!(ErrorCode::InvalidArgument, null.error);
+        assert!(null_message.ends_with("the image bytes are null"));
+        assert_eq!(ErrorCode::Ok, ok.error);
+        assert!(!ok.data.is_null());
+        improc_petrsu_free(ok);
+    }
+
+    #[test]
+    fn hit_or_miss_null_templates_test() {
+        // Act
+        let buffer = improc_petrsu_hit_or_miss_process(
+            std::ptr::null(),
+            0,
+            std::ptr::null(),
+            1,
+            3,
+            3,
+            0,
+            0,
+            -1,
+            0,
+        );
+
+        // Assert
+        assert_eq!(ErrorCode::InvalidArgument, buffer.error);
+        assert!(last_error_message().ends_with("the templates are null"));
+    }
+
+    #[test]
+    fn hit_or_miss_invalid_templates_test() {
+        // Arrange
+        let templates = [1u8; 9];
+
+        // Act
+        let empty = improc_petrsu_hit_or_miss_process(
+            std::ptr::null(),
+            0,
+            templates.as_ptr(),
+            0,
+            3,
+            3,
+            0,
+            0,
+            -1,
+            0,
+        );
+        let empty_message = last_error_message();
+        let too_large = improc_petrsu_hit_or_miss_process(
+            std::ptr::null(),
+            0,
+            templates.as_ptr(),
+            u32::MAX,
+            u32::MAX,
+            u32::MAX,
+            0,
+            0,
+            -1,
+            0,
+        );
+        let too_large_message = last_error_message();
+        let mode = improc_petrsu_hit_or_miss_process(
+            std::ptr::null(),
+            0,
+            templates.as_ptr(),
+            1,
+            3,
+            3,
+            2,
+            0,
+            -1,
+            0,
+        );
+        let mode_message = last_error_message();
+
+        // Assert
+        assert_eq!(ErrorCode::InvalidArgument, empty.error);
+        assert!(empty_message.ends_with("the templates are empty"));
+        assert_eq!(ErrorCode::InvalidArgument, too_large.error);
+        assert!(too_large_message.ends_with("the templates are too large"));
+        assert_eq!(ErrorCode::InvalidArgument, mode.error);
+        assert!(mode_message.ends_with("the update mode is unknown"));
+    }
+
+    #[test]
+    fn morphology_unknown_codes_test() {
+        // Arrange
+        let mut png = Vec::new();
+        DynamicImage::ImageRgb8(RgbImage::new(4, 4))
+            .write_to(&mut png, ImageOutputFormat::Png)
+            .unwrap();
+
+        // Act
+        let ok = improc_petrsu_morphology_process(png.as_ptr(), png.len(), 3, 2, 1, 0);
+        let operation = improc_petrsu_morphology_process(png.as_ptr(), png.len(), 5, 0, 1, 0);
+        let operation_message = last_error_message();
+        let element = improc_petrsu_morphology_process(png.as_ptr(), png.len(), 0, 3, 1, 0);
+        let element_message = last_error_message();
+
+        // Assert
+        assert_eq!(ErrorCode::Ok, ok.error);
+        improc_petrsu_free(ok);
+        assert_eq!(ErrorCode::InvalidArgument, operation.error);
+        assert!(operation_message.ends_with("the morphology operation is unknown"));
+        assert_eq!(ErrorCode::InvalidArgument, element.error);
+        assert!(element_message.ends_with("the structuring element is unknown"));
+    }
+
+    unsafe extern "C" fn ignore_progress(_: i32, _: i32) {}
+
+    #[test]
+    fn skeletonizer_process_polarity_test() {
+        // Arrange
+        let image = RgbImage::from_fn(7, 5, |_, y| {
+            if (1..4).contains(&y) {
+                Rgb([255, 255, 255])
+            } else {
+                Rgb([0, 0, 0])
+            }
+        });
+        let mut png = Vec::new();
+        DynamicImage::ImageRgb8(image)
+            .write_to(&mut png, ImageOutputFormat::Png)
+            .unwrap();
+
+        // Act
+        let buffer = improc_petrsu_skeletonizer_process(
+            png.as_ptr(),
+            png.len(),
+            0,
+            0,
+            1,
+            Some(ignore_progress),
+            std::ptr::null(),
+        );
+        let unknown = improc_petrsu_skeletonizer_process(
+            png.as_ptr(),
+            png.len(),
+            0,
+            0,
+            3,
+            Some(ignore_progress),
+            std::ptr::null(),
+        );
+
+        // Assert
+        assert_eq!(ErrorCode::Ok, buffer.error);
+        let bytes = unsafe { std::slice::from_raw_parts(buffer.data, buffer.len) };
+        let result = image::load_from_memory(bytes).unwrap().to_rgb8();
+        assert_eq!(Rgb([0, 0, 0]), *result.get_pixel(3, 2));
+        assert_eq!(Rgb([255, 255, 255]), *result.get_pixel(3, 1));
+        assert_eq!(Rgb([255, 255, 255]), *result.get_pixel(0, 0));
+        assert_eq!(ErrorCode::InvalidArgument, unknown.error);
+        assert!(last_error_message().ends_with("the polarity is unknown"));
+        improc_petrsu_free(buffer);
+    }
+
+    #[test]
+    fn unknown_algorithm_test() {
+        // Arrange
+        let mut png = Vec::new();
+        DynamicImage::ImageRgb8(RgbImage::new(4, 4))
+            .write_to(&mut png, ImageOutputFormat::Png)
+            .unwrap();
+        let handle = improc_petrsu_binary_image_new(4, 4);
+
+        // Act
+        let buffer = improc_petrsu_skeletonizer_process(
+            png.as_ptr(),
+            png.len(),
+            8,
+            0,
+            0,
+            None,
+            std::ptr::null(),
+        );
+        let buffer_message = last_error_message();
+        let error = improc_petrsu_skeletonize_handle(handle, -1, std::ptr::null());
+
+        // Assert
+        assert_eq!(ErrorCode::InvalidArgument, buffer.error);
+        assert!(buffer_message.ends_with("the skeletonization algorithm is unknown"));
+        assert_eq!(ErrorCode::InvalidArgument, error);
+        assert!(last_error_message().ends_with("the skeletonization algorithm is unknown"));
+        improc_petrsu_binary_image_free(handle);
+    }
+
+    #[test]
+    fn unknown_converter_test() {
+        // Arrange
+        let mut png = Vec::new();
+        DynamicImage::ImageRgb8(RgbImage::new(4, 4))
+            .write_to(&mut png, ImageOutputFormat::Png)
+            .unwrap();
+        let pixels = [0u8; 16];
+        let options = ConverterOptions {
+            converter: 6,
+            threshold: 100,
+            window_size: 0,
+            k: 0.0,
+            c: 0.0,
+            r: 0.0,
+        };
+
+        // Act
+        let buffer = improc_petrsu_binary_image_converter_process(
+            png.as_ptr(),
+            png.len(),
+            &options,
+            None,
+            std::ptr::null(),
+        );
+        let buffer_message = last_error_message();
+        let handle =
+            improc_petrsu_binary_image_convert_raw(pixels.as_ptr(), 4, 4, 4, 0, &options, 0);
+
+        // Assert
+        assert_eq!(ErrorCode::InvalidArgument, buffer.error);
+        assert!(buffer_message.ends_with("the converter is unknown"));
+        assert!(handle.is_null());
+        assert_eq!(ErrorCode::InvalidArgument, improc_petrsu_last_error_code());
+    }
+
+    #[test]
+    fn unknown_vector_format_test() {
+        // Arrange
+        let mut png = Vec::new();
+        DynamicImage::ImageRgb8(RgbImage::new(4, 4))
+            .write_to(&mut png, ImageOutputFormat::Png)
+            .unwrap();
+
+        // Act
+        let json =
+            improc_petrsu_skeleton_vectorizer_process(png.as_ptr(), png.len(), 0, 0, 1.0, 2, 0);
+        let unknown =
+            improc_petrsu_skeleton_vectorizer_process(png.as_ptr(), png.len(), 0, 0, 1.0, 3, 0);
+
+        // Assert
+        assert_eq!(ErrorCode::Ok, json.error);
+        improc_petrsu_free(json);
+        assert_eq!(ErrorCode::InvalidArgument, unknown.error);
+        assert!(unknown.data.is_null());
+        assert!(last_error_message().ends_with("the vector format is unknown"));
+    }
+
+    #[test]
+    fn unknown_length_mode_test() {
+        // Arrange
+        let mut png = Vec::new();
+        DynamicImage::ImageRgb8(RgbImage::new(4, 4))
+            .write_to(&mut png, ImageOutputFormat::Png)
+            .unwrap();
+        let handle = improc_petrsu_binary_image_new(4, 4);
+
+        // Act
+        let buffer = improc_petrsu_spur_pruner_process(
+            png.as_ptr(),
+            png.len(),
+            std::ptr::null(),
+            0,
+            2.0,
+            2,
+            0,
+            0,
+            0,
+        );
+        let buffer_message = last_error_message();
+        let error = improc_petrsu_spur_pruner_handle(handle, std::ptr::null(), 2.0, -1, 0, 0);
+        let ok = improc_petrsu_spur_pruner_handle(handle, std::ptr::null(), 2.0, 0, 0, 0);
+
+        // Assert
+        assert_eq!(ErrorCode::InvalidArgument, buffer.error);
+        assert!(buffer_message.ends_with("the length mode is unknown"));
+        assert_eq!(ErrorCode::InvalidArgument, error);
+        assert_eq!(ErrorCode::Ok, ok);
+        improc_petrsu_binary_image_free(handle);
+    }
+
+    #[test]
+    fn skeletonizer_raw_process_test() {
+        // Arrange
+        // a black 5 by 3 bar on white, BGRA rows of 28 bytes padded to 32
+        let (width, height, stride) = (7, 5, 32);
+        let mut pixels = vec![0xAA; height * stride];
+        for y in 0..height {
+            for x in 0..width {
+                let is_bar = (1..6).contains(&x) && (1..4).contains(&y);
+                let value = if is_bar { 0 } else { 255 };
+                pixels[y * stride + x * 4..y * stride + x * 4 + 4]
+                    .copy_from_slice(&[value, value, value, 255]);
+            }
+        }
+
+        // Act
+        let buffer = improc_petrsu_skeletonizer_raw_process(
+            pixels.as_ptr(),
+            width as u32,
+            height as u32,
+            stride,
+            3,
+            0,
+            0,
+            0,
+            std::ptr::null_mut(),
+            0,
+            Some(ignore_progress),
+            std::ptr::null(),
+        );
+
+        // Assert
+        assert_eq!(ErrorCode::Ok, buffer.error);
+        assert_eq!(width * height * 4, buffer.len);
+        let result = unsafe { std::slice::from_raw_parts(buffer.data, buffer.len) };
+        let black: Vec<(usize, usize)> = (0..width * height)
+            .filter(|i| result[i * 4] == 0)
+            .map(|i| (i % width, i / width))
+            .collect();
+        assert_eq!(vec![(2, 2), (3, 2)], black);
+        assert!(result.chunks(4).all(|pixel| pixel[3] == 255));
+        improc_petrsu_free(buffer);
+    }
+
+    #[test]
+    fn binary_image_converter_raw_process_test() {
+        // Arrange
+        let mut pixels = vec![10, 200, 99, 0, 101, 50, 255, 0];
+        let options = ConverterOptions {
+            converter: 0,
+            threshold: 100,
+            window_size: 0,
+            k: 0.0,
+            c: 0.0,
+            r: 0.0,
+        };
+        let pixels_ptr = pixels.as_mut_ptr();
+
+        // Act
+        let buffer = improc_petrsu_binary_image_converter_raw_process(
+            pixels_ptr,
+            3,
+            2,
+            4,
+            0,
+            &options,
+            pixels_ptr,
+            4,
+            Some(ignore_progress),
+            std::ptr::null(),
+        );
+
+        // Assert
+        assert_eq!(ErrorCode::Ok, buffer.error);
+        assert_eq!(0, buffer.len);
+        assert_eq!(vec![0, 255, 0, 0, 255, 0, 255, 0], pixels);
+        improc_petrsu_free(buffer);
+    }
+
+    #[test]
+    fn raw_process_short_stride_test() {
+        // Arrange
+        let pixels = [0u8; 16];
+
+        // Act
+        let buffer = improc_petrsu_skeletonizer_raw_process(
+            pixels.as_ptr(),
+            4,
+            2,
+            8,
+            1,
+            0,
+            0,
+            0,
+            std::ptr::null_mut(),
+            0,
+            Some(ignore_progress),
+            std::ptr::null(),
+        );
+
+        // Assert
+        assert_eq!(ErrorCode::InvalidArgument, buffer.error);
+        assert!(last_error_message().ends_with("the stride is shorter than a row"));
+    }
+
+    #[test]
+    fn raw_process_too_large_test() {
+        // Arrange
+        let pixels = [0u8; 16];
+
+        // Act
+        let buffer = improc_petrsu_skeletonizer_raw_process(
+            pixels.as_ptr(),
+            u32::MAX,
+            u32::MAX,
+            usize::MAX,
+            2,
+            0,
+            0,
+            0,
+            std::ptr::null_mut(),
+            0,
+            Some(ignore_progress),
+            std::ptr::null(),
+        );
+
+        // Assert
+        assert_eq!(ErrorCode::InvalidArgument, buffer.error);
+        assert!(last_error_message().ends_with("the pixels are too large"));
+    }
+
+    #[test]
+    fn binary_image_handle_pipeline_test() {
+        // Arrange
+        // a dark 5 by 3 bar and a speck on a light 9 by 5 gray image
+        let (width, height) = (9, 5);
+        let mut pixels = vec![200u8; width * height];
+        for y in 1..4 {
+            for x in 1..6 {
+                pixels[y * width + x] = 20;
+            }
+        }
+        pixels[2 * width + 8] = 20;
+        let options = ConverterOptions {
+            converter: 0,
+            threshold: 100,
+            window_size: 0,
+            k: 0.0,
+            c: 0.0,
+            r: 0.0,
+        };
+
+        // Act
+        let handle = improc_petrsu_binary_image_convert_raw(
+            pixels.as_ptr(),
+            width as u32,
+            height as u32,
+            width,
+            0,
+            &options,
+            0,
+        );
+        let open_error = improc_petrsu_area_open_handle(handle, 2, 0);
+        let skeletonize_error = improc_petrsu_skeletonize_handle(handle, 0, std::ptr::null());
+        let bits = improc_petrsu_binary_image_to_bits(handle);
+
+        // Assert
+        assert_eq!(ErrorCode::Ok, open_error);
+        assert_eq!(ErrorCode::Ok, skeletonize_error);
+        assert_eq!(9, improc_petrsu_binary_image_width(handle));
+        assert_eq!(1, improc_petrsu_binary_image_get_pixel(handle, 2, 2));
+        assert_eq!(0, improc_petrsu_binary_image_get_pixel(handle, 8, 2));
+        assert_eq!(10, bits.len);
+        let bits_slice = unsafe { std::slice::from_raw_parts(bits.data, bits.len) };
+        assert_eq!([0, 0, 0, 0, 0b1100, 0, 0, 0, 0, 0], bits_slice);
+        improc_petrsu_free(bits);
+        improc_petrsu_binary_image_free(handle);
+    }
+
+    #[test]
+    fn binary_image_handle_as_own_mask_test() {
+        // Arrange
+        let handle = improc_petrsu_binary_image_new(5, 3);
+        for x in 0..5 {
+            improc_petrsu_binary_image_set_pixel(handle, x, 1, 1);
+        }
+        let options = SkeletonizerOptions {
+            adjacency_mode: 0,
+            roi_mask: handle,
+            report_progress: None,
+            cancellation_token: std::ptr::null(),
+        };
+
+        // Act
+        let skeletonize_error = improc_petrsu_skeletonize_handle(handle, 0, &options);
+        let prune_error = improc_petrsu_spur_pruner_handle(handle, handle, 1.0, 0, 0, 0);
+
+        // Assert
+        assert_eq!(ErrorCode::Ok, skeletonize_error);
+        assert_eq!(ErrorCode::Ok, prune_error);
+        assert_eq!(1, improc_petrsu_binary_image_get_pixel(handle, 2, 1));
+        improc_petrsu_binary_image_free(handle);
+    }
+
+    #[test]
+    fn binary_image_handle_errors_test() {
+        // Arrange
+        let handle = improc_petrsu_binary_image_new(2, 2);
+
+        // Act
+        let set_error = improc_petrsu_binary_image_set_pixel(handle, 1, 1, 1);
+        let outside_error = improc_petrsu_binary_image_set_pixel(handle, 2, 0, 1);
+        let null_handle = improc_petrsu_binary_image_from_bytes(std::ptr::null(), 0, 0);
+
+        // Assert
+        assert_eq!(ErrorCode::Ok, set_error);
+        assert_eq!(1, improc_petrsu_binary_image_get_pixel(handle, 1, 1));
+        assert_eq!(ErrorCode::InvalidArgument, outside_error);
+        assert!(null_handle.is_null());
+        assert_eq!(ErrorCode::InvalidArgument, improc_petrsu_last_error_code());
+        assert_eq!(-1, improc_petrsu_binary_image_get_pixel(null_handle, 0, 0));
+        improc_petrsu_binary_image_free(handle);
+        improc_petrsu_binary_image_free(null_handle);
+    }
+
+    #[test]
+    fn cancellation_token_test() {
+        // Arrange
+        let handle = improc_petrsu_binary_image_new(3, 3);
+        for y in 0..3 {
+            for x in 0..3 {
+                improc_petrsu_binary_image_set_pixel(handle, x, y, 1);
+            }
+        }
+        let token = improc_petrsu_cancellation_token_new();
+        let options = SkeletonizerOptions {
+            adjacency_mode: 1,
+            roi_mask: std::ptr::null(),
+            report_progress: None,
+            cancellation_token: token,
+        };
+
+        // Act
+        let cancel_error = improc_petrsu_cancellation_token_cancel(token);
+        let error = improc_petrsu_skeletonize_handle(handle, 0, &options);
+
+        // Assert
+        assert_eq!(ErrorCode::Ok, cancel_error);
+        assert_eq!(ErrorCode::Cancelled, error);
+        assert_eq!("the operation was cancelled", last_error_message());
+        assert_eq!(1, improc_petrsu_binary_image_get_pixel(handle, 0, 0));
+        assert_eq!(
+            ErrorCode::InvalidArgument,
+            improc_petrsu_cancellation_token_cancel(std::ptr::null())
+        );
+        improc_petrsu_cancellation_token_free(token);
+        improc_petrsu_binary_image_free(handle);
+    }
+
+    #[test]
+    fn encoded_image_cancellation_test() {
+        // Arrange
+        let mut png = Vec::new();
+        DynamicImage::ImageRgb8(RgbImage::new(6, 6))
+            .write_to(&mut png, ImageOutputFormat::Png)
+            .unwrap();
+        let options = ConverterOptions {
+            converter: 1,
+            threshold: 0,
+            window_size: 0,
+            k: 0.0,
+            c: 0.0,
+            r: 0.0,
+        };
+        let token = improc_petrsu_cancellation_token_new();
+        let (bytes, len) = (png.as_ptr(), png.len());
+
+        // Act
+        let converted =
+            improc_petrsu_binary_image_converter_process(bytes, len, &options, None, token);
+        let mut threshold = 0;
+        let otsu = improc_petrsu_otsu_binary_image_converter_process(
+            bytes,
+            len,
+            &mut threshold,
+            None,
+            token,
+        );
+        improc_petrsu_cancellation_token_cancel(token);
+        let k3m = improc_petrsu_k3m_skeletonizer_process(bytes, len, 0, None, token);
+        let skeleton = improc_petrsu_skeletonizer_process(bytes, len, 3, 0, 0, None, token);
+        let roi_skeleton = improc_petrsu_skeletonizer_roi_process(
+            bytes,
+            len,
+            3,
+            0,
+            1,
+            1,
+            4,
+            4,
+            std::ptr::null(),
+            0,
+            0,
+            None,
+            token,
+        );
+        let cancelled_conversion =
+            improc_petrsu_binary_image_converter_process(bytes, len, &options, None, token);
+
+        // Assert
+        assert_eq!(ErrorCode::Ok, converted.error);
+        improc_petrsu_free(converted);
+        assert_eq!(ErrorCode::Ok, otsu.error);
+        improc_petrsu_free(otsu);
+        assert_eq!(ErrorCode::Cancelled, k3m.error);
+        assert!(k3m.data.is_null());
+        assert_eq!(ErrorCode::Cancelled, skeleton.error);
+        assert!(skeleton.data.is_null());
+        assert_eq!(ErrorCode::Cancelled, roi_skeleton.error);
+        assert_eq!(ErrorCode::Cancelled, cancelled_conversion.error);
+        assert_eq!("the operation was cancelled", last_error_message());
+        improc_petrsu_cancellation_token_free(token);
+    }
+
+    #[test]
+    fn decoder_panic_test() {
+        // Arrange
+        // a frame taller than the screen makes the GIF decoder slice out of bounds
+        let mut gif = Vec::new();
+        DynamicImage::ImageRgb8(RgbImage::new(5, 4))
+            .write_to(&mut gif, ImageOutputFormat::Gif)
+            .unwrap();
+        let descriptor = gif
+            .windows(5)
+            .position(|bytes| bytes == [0x2c, 0, 0, 0, 0])
+            .unwrap();
+        gif[descriptor + 7] = 0xff;
+
+        // Act
+        let buffer = improc_petrsu_skeleton_graph_process(gif.as_ptr(), gif.len(), 0, 0);
+        let handle = improc_petrsu_binary_image_from_bytes(gif.as_ptr(), gif.len(), 0);
+
+        // Assert
+        assert_eq!(ErrorCode::Panic, buffer.error);
+        assert!(buffer.data.is_null());
+        assert!(handle.is_null());
+        assert_eq!(ErrorCode::Panic, improc_petrsu_last_error_code());
+        assert!(last_error_message().starts_with("Internal error: "));
+    }
+
+    #[test]
+    fn catch_panic_test() {
+        // Act
+        let buffer = catch_panic(failed_buffer(ErrorCode::Panic), || -> Buffer {
+            panic!("index out of bounds")
+        });
+        let value = catch_panic(-1, || 1);
+
+        // Assert
+        assert_eq!(ErrorCode::Panic, buffer.error);
+        assert!(buffer.data.is_null());
+        assert_eq!(ErrorCode::Panic, improc_petrsu_last_error_code());
+        assert_eq!("Internal error: index out of bounds", last_error_message());
+        assert_eq!(1, value);
     }
 }
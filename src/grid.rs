@@ -0,0 +1,141 @@
+// grid.rs - A helper struct to store a value per pixel
+// Copyright (C) 2019 Denis Karpovskiy
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use crate::binary_image::PixelIterator;
+use image::{GrayImage, Luma};
+use num_traits::ToPrimitive;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Grid<T> {
+    data: Vec<T>,
+    width: usize,
+    height: usize,
+}
+
+impl<T: Copy> Grid<T> {
+    pub fn new(width: usize, height: usize, value: T) -> Self {
+        Grid {
+            data: vec![value; width * height],
+            width,
+            height,
+        }
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    pub fn get(&self, x: usize, y: usize) -> T {
+        self.data[self.index(x, y)]
+    }
+
+    pub fn set(&mut self, x: usize, y: usize, value: T) {
+        let index = self.index(x, y);
+        self.data[index] = value;
+    }
+
+    pub fn pixels_iter(&self) -> PixelIterator {
+        PixelIterator::with_size(self.width, self.height)
+    }
+
+    pub fn values(&self) -> &[T] {
+        &self.data
+    }
+
+    fn index(&self, x: usize, y: usize) -> usize {
+        assert!(
+            x < self.width && y < self.height,
+            "({}, {}) is out of bounds of a {}x{} grid",
+            x,
+            y,
+            self.width,
+            self.height
+        );
+
+        y * self.width + x
+    }
+}
+
+impl<T: Copy + ToPrimitive> Grid<T> {
+    // A grayscale picture of the grid, the largest value is white and zero
+    // is black.
+    pub fn to_image(&self) -> GrayImage {
+        let max_value = self
+            .data
+            .iter()
+            .filter_map(|value| value.to_f64())
+            .filter(|value| value.is_finite())
+            .fold(0.0, f64::max);
+
+        GrayImage::from_fn(self.width as u32, self.height as u32, |x, y| {
+            let value = self.get(x as usize, y as usize).to_f64().unwrap_or(0.0);
+            if max_value <= 0.0 {
+                Luma([0])
+            } else {
+                Luma([(value.max(0.0).min(max_value) / max_value * 255.0).round() as u8])
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn constructor_test() {
+        // Arrange & Act
+        let grid = Grid::new(3, 2, 7u32);
+
+        // Assert
+        assert_eq!(3, grid.width());
+        assert_eq!(2, grid.height());
+        assert!(grid.values().iter().all(|&value| value == 7));
+    }
+
+    #[test]
+    fn to_image_test() {
+        // Arrange
+        let mut grid = Grid::new(3, 1, 0u32);
+        grid.set(1, 0, 2);
+        grid.set(2, 0, 4);
+
+        // Act
+        let image = grid.to_image();
+
+        // Assert
+        assert_eq!(0, image.get_pixel(0, 0)[0]);
+        assert_eq!(128, image.get_pixel(1, 0)[0]);
+        assert_eq!(255, image.get_pixel(2, 0)[0]);
+    }
+
+    #[test]
+    fn set_test() {
+        // Arrange
+        let mut grid = Grid::new(3, 2, 0.0f32);
+
+        // Act
+        grid.set(2, 1, 1.5);
+
+        // Assert
+        assert_eq!(1.5, grid.get(2, 1));
+        assert_eq!(1.5, grid.values()[5]);
+    }
+}
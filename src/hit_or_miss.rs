@@ -0,0 +1,477 @@
+// hit_or_miss.rs - Hit-or-miss transform with thinning and thickening
+// Copyright (C) 2019 Denis Karpovskiy
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use crate::binary_image::{BinaryImage, WORD_BITS};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TemplateCell {
+    Foreground,
+    Background,
+    DontCare,
+}
+
+// Pixels outside of the image count as background when matching.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Template {
+    cells: Vec<TemplateCell>,
+    width: usize,
+    height: usize,
+    origin_x: usize,
+    origin_y: usize,
+}
+
+impl Template {
+    // The cells go row by row.
+    pub fn new(
+        width: usize,
+        height: usize,
+        origin_x: usize,
+        origin_y: usize,
+        cells: Vec<TemplateCell>,
+    ) -> Self {
+        assert_eq!(width * height, cells.len(), "Template size does not match");
+        assert!(
+            origin_x < width && origin_y < height,
+            "Origin is outside of the template"
+        );
+
+        Template {
+            cells,
+            width,
+            height,
+            origin_x,
+            origin_y,
+        }
+    }
+
+    // '1' is foreground, '0' is background and anything else does not
+    // matter, the origin is in the middle.
+    pub fn from_rows(rows: &[&str]) -> Self {
+        let cells = rows
+            .iter()
+            .flat_map(|row| row.chars())
+            .map(|c| match c {
+                '1' => TemplateCell::Foreground,
+                '0' => TemplateCell::Background,
+                _ => TemplateCell::DontCare,
+            })
+            .collect();
+
+        let width = rows.first().map_or(0, |row| row.chars().count());
+        Template::new(width, rows.len(), width / 2, rows.len() / 2, cells)
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    pub fn get(&self, x: usize, y: usize) -> TemplateCell {
+        self.cells[y * self.width + x]
+    }
+
+    pub fn origin(&self) -> TemplateCell {
+        self.get(self.origin_x, self.origin_y)
+    }
+
+    // Turned by 90 degrees clockwise.
+    pub fn rotated(&self) -> Template {
+        let mut cells = Vec::with_capacity(self.cells.len());
+        for y in 0..self.width {
+            for x in 0..self.height {
+                cells.push(self.get(y, self.height - 1 - x));
+            }
+        }
+
+        Template::new(
+            self.height,
+            self.width,
+            self.height - 1 - self.origin_y,
+            self.origin_x,
+            cells,
+        )
+    }
+
+    // Turned by 45 degrees clockwise, every neighbour of a 3x3 template moves
+    // to the next one.
+    pub fn rotated_by_45(&self) -> Template {
+        assert!(
+            self.width == 3 && self.height == 3 && self.origin_x == 1 && self.origin_y == 1,
+            "Only 3x3 templates around the middle can be turned by 45 degrees"
+        );
+
+        let mut cells = self.cells.clone();
+        for i in 0..8 {
+            let (x, y) = RING[(i + 1) % 8];
+            let (from_x, from_y) = RING[i];
+            cells[y * 3 + x] = self.get(from_x, from_y);
+        }
+
+        Template::new(3, 3, 1, 1, cells)
+    }
+
+    // The template and its turns by 90 degrees.
+    pub fn rotations(&self) -> Vec<Template> {
+        let mut rotations = vec![self.clone()];
+        for i in 0..3 {
+            rotations.push(rotations[i].rotated());
+        }
+
+        rotations
+    }
+
+    // The template and its turns by 45 degrees.
+    pub fn rotations_by_45(&self) -> Vec<Template> {
+        let mut rotations = vec![self.clone()];
+        for i in 0..7 {
+            rotations.push(rotations[i].rotated_by_45());
+        }
+
+        rotations
+    }
+
+    pub fn matches(&self, image: &BinaryImage, x: usize, y: usize) -> bool {
+        let left = x as i32 - self.origin_x as i32;
+        let top = y as i32 - self.origin_y as i32;
+
+        self.cells.iter().enumerate().all(|(i, cell)| {
+            let is_fg = image.is_fg_or_default(
+                left + (i % self.width) as i32,
+                top + (i / self.width) as i32,
+            );
+            match cell {
+                TemplateCell::Foreground => is_fg,
+                TemplateCell::Background => !is_fg,
+                TemplateCell::DontCare => true,
+            }
+        })
+    }
+}
+
+// The positions of the neighbours in a 3x3 template clockwise from the north.
+const RING: [(usize, usize); 8] = [
+    (1, 0),
+    (2, 0),
+    (2, 1),
+    (2, 2),
+    (1, 2),
+    (0, 2),
+    (0, 1),
+    (0, 0),
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UpdateMode {
+    // The templates are applied one after another, each to the result of the
+    // previous one.
+    Sequential,
+    // The matches of all templates are found in the same image and applied
+    // together.
+    Parallel,
+}
+
+// The pixels where any of the templates matches.
+pub fn hit_or_miss(image: &BinaryImage, templates: &[Template]) -> BinaryImage {
+    let mut result = BinaryImage::new(image.width(), image.height(), image.get_bg_color());
+    for (x, y) in find_matches(image, templates) {
+        result.set_fg(x, y);
+    }
+
+    result
+}
+
+// Removes the matched pixels until nothing changes or for the given number
+// of passes over the templates. Returns the number of passes made.
+pub fn thin(
+    image: &mut BinaryImage,
+    templates: &[Template],
+    mode: UpdateMode,
+    max_passes: Option<usize>,
+) -> usize {
+    repeat(image, templates, mode, max_passes, false)
+}
+
+// Adds the matched pixels, the templates should have background at the
+// origin. Returns the number of passes made.
+pub fn thicken(
+    image: &mut BinaryImage,
+    templates: &[Template],
+    mode: UpdateMode,
+    max_passes: Option<usize>,
+) -> usize {
+    repeat(image, templates, mode, max_passes, true)
+}
+
+// The Golay L templates, thinning with them leaves a skeleton.
+pub fn golay_l_templates() -> Vec<Template> {
+    let mut templates = Template::from_rows(&["000", "*1*", "111"]).rotations();
+    let diagonal = Template::from_rows(&["*00", "110", "*1*"]).rotations();
+
+    // in the order of the turns by 45 degrees
+    for (i, template) in diagonal.into_iter().enumerate() {
+        templates.insert(2 * i + 1, template);
+    }
+
+    templates
+}
+
+// The foreground pixels with exactly one foreground neighbour.
+pub fn endpoint_templates() -> Vec<Template> {
+    Template::from_rows(&["010", "010", "000"]).rotations_by_45()
+}
+
+fn repeat(
+    image: &mut BinaryImage,
+    templates: &[Template],
+    mode: UpdateMode,
+    max_passes: Option<usize>,
+    is_thickening: bool,
+) -> usize {
+    let mut passes = 0;
+    while max_passes != Some(passes) {
+        let changed = match mode {
+            UpdateMode::Sequential => templates
+                .iter()
+                .map(|template| apply(image, std::slice::from_ref(template), is_thickening))
+                .sum(),
+            UpdateMode::Parallel => apply(image, templates, is_thickening),
+        };
+
+        passes += 1;
+
+        if changed == 0 {
+            break;
+        }
+    }
+
+    passes
+}
+
+fn apply(image: &mut BinaryImage, templates: &[Template], is_thickening: bool) -> usize {
+    let mut count = 0;
+    for (x, y) in find_matches(image, templates) {
+        if image.is_fg(x, y) != is_thickening {
+            if is_thickening {
+                image.set_fg(x, y);
+            } else {
+                image.set_bg(x, y);
+            }
+            count += 1;
+        }
+    }
+
+    count
+}
+
+fn find_matches(image: &BinaryImage, templates: &[Template]) -> Vec<(usize, usize)> {
+    let mut matches = Vec::new();
+
+    let need_fg = templates
+        .iter()
+        .all(|t| t.origin() == TemplateCell::Foreground);
+    for y in 0..image.height() {
+        for word_x in 0..image.words_per_row() {
+            // with the foreground at every origin only the set bits can match
+            let mut word = if need_fg {
+                image.get_word(word_x, y)
+            } else {
+                !0
+            };
+            while word != 0 {
+                let x = word_x * WORD_BITS + word.trailing_zeros() as usize;
+                word &= word - 1;
+
+                if x >= image.width() {
+                    break;
+                }
+
+                if templates
+                    .iter()
+                    .any(|template| template.matches(image, x, y))
+                {
+                    matches.push((x, y));
+                }
+            }
+        }
+    }
+
+    matches
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::labeling::label_components;
+    use crate::skeletonizers::test_shapes::{from_ascii, to_ascii, RECTANGLE};
+    use crate::skeletonizers::AdjacencyMode;
+
+    #[test]
+    fn rotated_test() {
+        // Arrange
+        let template = Template::new(
+            3,
+            2,
+            0,
+            1,
+            vec![
+                TemplateCell::Foreground,
+                TemplateCell::Background,
+                TemplateCell::DontCare,
+                TemplateCell::Background,
+                TemplateCell::Foreground,
+                TemplateCell::Foreground,
+            ],
+        );
+
+        // Act
+        let rotated = template.rotated();
+
+        // Assert
+        assert_eq!(2, rotated.width());
+        assert_eq!(3, rotated.height());
+        assert_eq!(TemplateCell::Background, rotated.origin());
+        assert_eq!(TemplateCell::Foreground, rotated.get(1, 0));
+        assert_eq!(TemplateCell::DontCare, rotated.get(1, 2));
+        assert_eq!(template, rotated.rotated().rotated().rotated());
+    }
+
+    #[test]
+    fn rotated_by_45_test() {
+        // Arrange
+        let template = Template::from_rows(&["10*", "*10", "000"]);
+
+        // Act
+        let rotated = template.rotated_by_45();
+
+        // Assert
+        assert_eq!(Template::from_rows(&["*10", "01*", "000"]), rotated);
+        assert_eq!(template.rotated(), rotated.rotated_by_45());
+    }
+
+    #[test]
+    fn hit_or_miss_endpoints_test() {
+        // Arrange
+        let image = from_ascii(&[
+            "#.......", //
+            ".#......", //
+            "..#####.", //
+            "....#...", //
+        ]);
+
+        // Act
+        let endpoints = hit_or_miss(&image, &endpoint_templates());
+
+        // Assert
+        assert_eq!(
+            vec!["#.......", "........", "......#.", "........"],
+            to_ascii(&endpoints)
+        );
+    }
+
+    #[test]
+    fn thin_golay_test() {
+        // Arrange
+        let mut image = from_ascii(&RECTANGLE);
+
+        // Act
+        let passes = thin(
+            &mut image,
+            &golay_l_templates(),
+            UpdateMode::Sequential,
+            None,
+        );
+
+        // Assert
+        assert!(passes > 1);
+        assert_eq!(
+            vec![
+                "............",
+                ".#........#.",
+                ".#.......#..",
+                "..########..",
+                "..#......#..",
+                ".#........#.",
+                "............",
+            ],
+            to_ascii(&image)
+        );
+        assert_eq!(
+            1,
+            label_components(&image, AdjacencyMode::Eight).component_count()
+        );
+        assert_eq!(0, hit_or_miss(&image, &golay_l_templates()).count_fg());
+    }
+
+    #[test]
+    fn thin_update_mode_test() {
+        // Arrange
+        let templates = [Template::from_rows(&["011"]), Template::from_rows(&["110"])];
+        let mut sequential = from_ascii(&[".##."]);
+        let mut parallel = from_ascii(&[".##."]);
+
+        // Act
+        thin(&mut sequential, &templates, UpdateMode::Sequential, Some(1));
+        thin(&mut parallel, &templates, UpdateMode::Parallel, Some(1));
+
+        // Assert
+        assert_eq!(vec!["..#."], to_ascii(&sequential));
+        assert_eq!(vec!["...."], to_ascii(&parallel));
+    }
+
+    #[test]
+    fn thin_max_passes_test() {
+        // Arrange
+        let mut image = from_ascii(&RECTANGLE);
+        let original_count = image.count_fg();
+
+        // Act
+        let passes = thin(
+            &mut image,
+            &golay_l_templates(),
+            UpdateMode::Sequential,
+            Some(1),
+        );
+
+        // Assert
+        assert_eq!(1, passes);
+        assert!(image.count_fg() < original_count);
+        assert!(image.count_fg() > 12);
+    }
+
+    #[test]
+    fn thicken_test() {
+        // Arrange
+        let mut image = from_ascii(&[
+            "#.#.#", //
+            ".....", //
+            "#.#.#", //
+        ]);
+        let templates = Template::from_rows(&["*1*", "*0*", "*1*"]).rotations();
+
+        let mut once = image.clone();
+
+        // Act
+        thicken(&mut once, &templates, UpdateMode::Parallel, Some(1));
+        thicken(&mut image, &templates, UpdateMode::Parallel, None);
+
+        // Assert
+        assert_eq!(vec!["#####", "#.#.#", "#####"], to_ascii(&once));
+        assert_eq!(vec!["#####", "#####", "#####"], to_ascii(&image));
+    }
+}
@@ -0,0 +1,515 @@
+// labeling.rs - Connected component labeling with per-component statistics
+// Copyright (C) 2019 Denis Karpovskiy
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use crate::binary_image::{BinaryImage, WORD_BITS};
+use crate::grid::Grid;
+use crate::skeletonizers::AdjacencyMode;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BoundingBox {
+    x: usize,
+    y: usize,
+    width: usize,
+    height: usize,
+}
+
+impl BoundingBox {
+    pub fn new(x: usize, y: usize, width: usize, height: usize) -> Self {
+        BoundingBox {
+            x,
+            y,
+            width,
+            height,
+        }
+    }
+
+    pub fn x(&self) -> usize {
+        self.x
+    }
+
+    pub fn y(&self) -> usize {
+        self.y
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    // Width divided by height.
+    pub fn aspect_ratio(&self) -> f64 {
+        self.width as f64 / self.height as f64
+    }
+
+    pub fn contains(&self, x: usize, y: usize) -> bool {
+        x >= self.x && x - self.x < self.width && y >= self.y && y - self.y < self.height
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Component {
+    label: u32,
+    area: usize,
+    bounding_box: BoundingBox,
+    centroid: (f64, f64),
+    perimeter: usize,
+    euler_number: i32,
+}
+
+impl Component {
+    pub fn label(&self) -> u32 {
+        self.label
+    }
+
+    // The number of pixels.
+    pub fn area(&self) -> usize {
+        self.area
+    }
+
+    pub fn bounding_box(&self) -> BoundingBox {
+        self.bounding_box
+    }
+
+    pub fn centroid(&self) -> (f64, f64) {
+        self.centroid
+    }
+
+    // The number of pixel sides between the component and the background,
+    // the image border included.
+    pub fn perimeter(&self) -> usize {
+        self.perimeter
+    }
+
+    // One minus the number of holes.
+    pub fn euler_number(&self) -> i32 {
+        self.euler_number
+    }
+
+    pub fn hole_count(&self) -> usize {
+        (1 - self.euler_number).max(0) as usize
+    }
+}
+
+// Labels start from 1, background pixels get 0.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Labeling {
+    labels: Grid<u32>,
+    components: Vec<Component>,
+}
+
+impl Labeling {
+    pub fn labels(&self) -> &Grid<u32> {
+        &self.labels
+    }
+
+    pub fn label(&self, x: usize, y: usize) -> u32 {
+        self.labels.get(x, y)
+    }
+
+    pub fn components(&self) -> std::slice::Iter<'_, Component> {
+        self.components.iter()
+    }
+
+    pub fn component(&self, label: u32) -> &Component {
+        &self.components[label as usize - 1]
+    }
+
+    pub fn component_count(&self) -> usize {
+        self.components.len()
+    }
+}
+
+// A horizontal run of foreground pixels, `end` is exclusive.
+#[derive(Debug, Clone, Copy)]
+struct Run {
+    y: usize,
+    start: usize,
+    end: usize,
+}
+
+// Two-pass labeling of the foreground runs, the runs that touch in adjacent
+// rows are merged with union-find.
+pub fn label_components(image: &BinaryImage, mode: AdjacencyMode) -> Labeling {
+    let mut runs = Vec::new();
+    let mut row_starts = Vec::with_capacity(image.height() + 1);
+    for y in 0..image.height() {
+        row_starts.push(runs.len());
+        find_runs(image, y, &mut runs);
+    }
+    row_starts.push(runs.len());
+
+    let mut parents: Vec<usize> = (0..runs.len()).collect();
+    for y in 1..image.height() {
+        let previous = row_starts[y - 1]..row_starts[y];
+        let current = row_starts[y]..row_starts[y + 1];
+        union_touching_runs(&runs, previous, current, mode, &mut parents);
+    }
+
+    let mut run_labels = vec![0; runs.len()];
+    let mut root_labels = vec![0; runs.len()];
+    let mut component_count = 0;
+    for (i, run_label) in run_labels.iter_mut().enumerate() {
+        let root = find(&mut parents, i);
+        if root_labels[root] == 0 {
+            component_count += 1;
+            root_labels[root] = component_count;
+        }
+        *run_label = root_labels[root];
+    }
+
+    let mut labels = Grid::new(image.width(), image.height(), 0);
+    for (run, &label) in runs.iter().zip(run_labels.iter()) {
+        for x in run.start..run.end {
+            labels.set(x, run.y, label);
+        }
+    }
+
+    let components = compute_statistics(image, &labels, &runs, &run_labels, component_count, mode);
+
+    Labeling { labels, components }
+}
+
+fn find_runs(image: &BinaryImage, y: usize, runs: &mut Vec<Run>) {
+    let mut run_start = None;
+
+    for word_x in 0..image.words_per_row() {
+        let word = image.get_word(word_x, y);
+        let word_start = word_x * WORD_BITS;
+
+        let mut bit = 0;
+        while bit < WORD_BITS {
+            let rest = word >> bit;
+            match run_start {
+                Some(start) => {
+                    bit += (!rest).trailing_zeros() as usize;
+                    if bit < WORD_BITS {
+                        runs.push(Run {
+                            y,
+                            start,
+                            end: word_start + bit,
+                        });
+                        run_start = None;
+                    }
+                }
+                None => {
+                    if rest == 0 {
+                        break;
+                    }
+                    bit += rest.trailing_zeros() as usize;
+                    run_start = Some(word_start + bit);
+                }
+            }
+        }
+    }
+
+    if let Some(start) = run_start {
+        runs.push(Run {
+            y,
+            start,
+            end: image.width(),
+        });
+    }
+}
+
+fn union_touching_runs(
+    runs: &[Run],
+    previous: std::ops::Range<usize>,
+    current: std::ops::Range<usize>,
+    mode: AdjacencyMode,
+    parents: &mut [usize],
+) {
+    // diagonal neighbours let the runs touch by a corner
+    let reach = match mode {
+        AdjacencyMode::Four => 0,
+        AdjacencyMode::Eight => 1,
+    };
+
+    let mut i = previous.start;
+    let mut j = current.start;
+    while i < previous.end && j < current.end {
+        let (upper, lower) = (runs[i], runs[j]);
+
+        if upper.start < lower.end + reach && lower.start < upper.end + reach {
+            union(parents, i, j);
+        }
+
+        // the run that ends first cannot touch the next run of the other row
+        if upper.end < lower.end {
+            i += 1;
+        } else {
+            j += 1;
+        }
+    }
+}
+
+fn find(parents: &mut [usize], i: usize) -> usize {
+    let mut root = i;
+    while parents[root] != root {
+        root = parents[root];
+    }
+
+    let mut i = i;
+    while parents[i] != root {
+        let next = parents[i];
+        parents[i] = root;
+        i = next;
+    }
+
+    root
+}
+
+fn union(parents: &mut [usize], a: usize, b: usize) {
+    let a = find(parents, a);
+    let b = find(parents, b);
+
+    // the smaller index stays the root so the labels follow the raster order
+    if a < b {
+        parents[b] = a;
+    } else {
+        parents[a] = b;
+    }
+}
+
+fn compute_statistics(
+    image: &BinaryImage,
+    labels: &Grid<u32>,
+    runs: &[Run],
+    run_labels: &[u32],
+    component_count: u32,
+    mode: AdjacencyMode,
+) -> Vec<Component> {
+    let count = component_count as usize;
+    let mut areas = vec![0; count];
+    let mut sums = vec![(0.0, 0.0); count];
+    let mut bounds = vec![(usize::MAX, usize::MAX, 0, 0); count];
+    let mut perimeters = vec![0; count];
+
+    for (run, &label) in runs.iter().zip(run_labels.iter()) {
+        let i = label as usize - 1;
+        let length = run.end - run.start;
+
+        areas[i] += length;
+        sums[i].0 += (run.start + run.end - 1) as f64 * length as f64 / 2.0;
+        sums[i].1 += (run.y * length) as f64;
+
+        let (min_x, min_y, max_x, max_y) = &mut bounds[i];
+        *min_x = (*min_x).min(run.start);
+        *min_y = (*min_y).min(run.y);
+        *max_x = (*max_x).max(run.end - 1);
+        *max_y = (*max_y).max(run.y);
+
+        // the ends of the run and the sides facing the background above and
+        // below, 4-adjacent foreground pixels always share the component
+        let (x_range, y) = (run.start as i32..run.end as i32, run.y as i32);
+        perimeters[i] += 2 + x_range
+            .map(|x| {
+                image.is_bg_or_default(x, y - 1) as usize
+                    + image.is_bg_or_default(x, y + 1) as usize
+            })
+            .sum::<usize>();
+    }
+
+    let euler_numbers = compute_euler_numbers(labels, count, mode);
+
+    (0..count)
+        .map(|i| {
+            let (min_x, min_y, max_x, max_y) = bounds[i];
+            let area = areas[i] as f64;
+
+            Component {
+                label: i as u32 + 1,
+                area: areas[i],
+                bounding_box: BoundingBox::new(min_x, min_y, max_x - min_x + 1, max_y - min_y + 1),
+                centroid: (sums[i].0 / area, sums[i].1 / area),
+                perimeter: perimeters[i],
+                euler_number: euler_numbers[i],
+            }
+        })
+        .collect()
+}
+
+// Counts the 2x2 bit quads of every component: the ones with a single pixel,
+// with three pixels and with two diagonal pixels.
+fn compute_euler_numbers(labels: &Grid<u32>, count: usize, mode: AdjacencyMode) -> Vec<i32> {
+    let mut quads = vec![(0i32, 0i32, 0i32); count];
+
+    let label_or_zero = |x: i32, y: i32| {
+        if x < 0 || y < 0 || x >= labels.width() as i32 || y >= labels.height() as i32 {
+            0
+        } else {
+            labels.get(x as usize, y as usize)
+        }
+    };
+
+    for y in -1..labels.height() as i32 {
+        for x in -1..labels.width() as i32 {
+            let window = [
+                label_or_zero(x, y),
+                label_or_zero(x + 1, y),
+                label_or_zero(x, y + 1),
+                label_or_zero(x + 1, y + 1),
+            ];
+
+            for (i, &label) in window.iter().enumerate() {
+                // every label of the window is counted once
+                if label == 0 || window[..i].contains(&label) {
+                    continue;
+                }
+
+                let is_set = |j: usize| window[j] == label;
+                let quad = &mut quads[label as usize - 1];
+                match (0..4).filter(|&j| is_set(j)).count() {
+                    1 => quad.0 += 1,
+                    3 => quad.1 += 1,
+                    2 if is_set(0) == is_set(3) => quad.2 += 1,
+                    _ => {}
+                }
+            }
+        }
+    }
+
+    quads
+        .into_iter()
+        .map(|(single, triple, diagonal)| match mode {
+            AdjacencyMode::Four => (single - triple + 2 * diagonal) / 4,
+            AdjacencyMode::Eight => (single - triple - 2 * diagonal) / 4,
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::binary_image::PixelColor;
+    use crate::skeletonizers::test_shapes::from_ascii;
+
+    #[test]
+    fn label_components_adjacency_test() {
+        // Arrange
+        let image = from_ascii(&[
+            "#.#..", //
+            ".#...", //
+            "....#", //
+        ]);
+
+        // Act
+        let four = label_components(&image, AdjacencyMode::Four);
+        let eight = label_components(&image, AdjacencyMode::Eight);
+
+        // Assert
+        assert_eq!(4, four.component_count());
+        assert_eq!(2, eight.component_count());
+        assert_eq!(1, eight.label(0, 0));
+        assert_eq!(1, eight.label(1, 1));
+        assert_eq!(1, eight.label(2, 0));
+        assert_eq!(2, eight.label(4, 2));
+        assert_eq!(0, eight.label(1, 0));
+    }
+
+    #[test]
+    fn label_components_u_shape_test() {
+        // Arrange
+        let image = from_ascii(&[
+            "#...#", //
+            "#...#", //
+            "#####", //
+        ]);
+
+        // Act
+        let labeling = label_components(&image, AdjacencyMode::Four);
+
+        // Assert
+        assert_eq!(1, labeling.component_count());
+        assert!(image
+            .pixels_iter()
+            .all(|(x, y)| image.is_fg(x, y) == (labeling.label(x, y) == 1)));
+    }
+
+    #[test]
+    fn label_components_wide_image_test() {
+        // Arrange
+        let mut image = BinaryImage::new(150, 3, PixelColor::White);
+        for x in 10..140 {
+            image.set_fg(x, 1);
+        }
+        image.set_fg(64, 0);
+        image.set_fg(149, 2);
+
+        // Act
+        let labeling = label_components(&image, AdjacencyMode::Four);
+
+        // Assert
+        assert_eq!(2, labeling.component_count());
+        assert_eq!(131, labeling.component(1).area());
+        assert_eq!(
+            BoundingBox::new(10, 0, 130, 2),
+            labeling.component(1).bounding_box()
+        );
+        assert_eq!(1, labeling.component(2).area());
+    }
+
+    #[test]
+    fn label_components_statistics_test() {
+        // Arrange
+        let image = from_ascii(&[
+            "###....", //
+            "###....", //
+            ".....##", //
+        ]);
+
+        // Act
+        let labeling = label_components(&image, AdjacencyMode::Eight);
+
+        // Assert
+        let rectangle = labeling.component(1);
+        assert_eq!(6, rectangle.area());
+        assert_eq!(BoundingBox::new(0, 0, 3, 2), rectangle.bounding_box());
+        assert_eq!((1.0, 0.5), rectangle.centroid());
+        assert_eq!(10, rectangle.perimeter());
+        assert_eq!(1, rectangle.euler_number());
+
+        let bar = labeling.component(2);
+        assert_eq!(2, bar.area());
+        assert_eq!((5.5, 2.0), bar.centroid());
+        assert_eq!(6, bar.perimeter());
+    }
+
+    #[test]
+    fn label_components_euler_number_test() {
+        // Arrange
+        let image = from_ascii(&[
+            "#######..#.", //
+            "#.#.#.#.#.#", //
+            "#######..#.", //
+        ]);
+
+        // Act
+        let four = label_components(&image, AdjacencyMode::Four);
+        let eight = label_components(&image, AdjacencyMode::Eight);
+
+        // Assert
+        assert_eq!(-2, four.component(1).euler_number());
+        assert_eq!(3, four.component(1).hole_count());
+        assert_eq!(5, four.component_count());
+        assert_eq!(-2, eight.component(1).euler_number());
+        assert_eq!(2, eight.component_count());
+        assert_eq!(0, eight.component(2).euler_number());
+        assert_eq!(1, eight.component(2).hole_count());
+    }
+}
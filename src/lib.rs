@@ -17,17 +17,54 @@
 mod binary_image;
 pub mod binary_image_converters;
 mod bool_matrix;
+mod channel_reduction;
+pub mod contours;
+pub mod distance_transform;
 pub mod ffi;
+mod grid;
+pub mod hit_or_miss;
+pub mod labeling;
+pub mod morphology;
+pub mod progress;
+pub mod roi;
+pub mod skeleton_graph;
 pub mod skeletonizers;
+pub mod spur_pruner;
+pub mod vectorizer;
 
 // re-exports
 pub use skeletonizers::eberly_skeletonizer::EberlySkeletonizer;
+pub use skeletonizers::guo_hall_skeletonizer::GuoHallSkeletonizer;
+pub use skeletonizers::huang_skeletonizer::HuangSkeletonizer;
+pub use skeletonizers::k3m_skeletonizer::K3MSkeletonizer;
+pub use skeletonizers::medial_axis_skeletonizer::MedialAxisSkeletonizer;
 pub use skeletonizers::rosenfeld_skeletonizer::RosenfeldSkeletonizer;
+pub use skeletonizers::stentiford_skeletonizer::StentifordSkeletonizer;
 pub use skeletonizers::zhangsuen_skeletonizer::ZhangSuenSkeletonizer;
 pub use skeletonizers::Skeletonizer;
 
 pub use binary_image::BinaryImage;
 pub use binary_image::PixelColor;
+pub use binary_image::Polarity;
+pub use binary_image::WORD_BITS;
+pub use binary_image_converters::mean_c_binary_image_converter::MeanCBinaryImageConverter;
+pub use binary_image_converters::niblack_binary_image_converter::NiblackBinaryImageConverter;
+pub use binary_image_converters::otsu_binary_image_converter::OtsuBinaryImageConverter;
+pub use binary_image_converters::sauvola_binary_image_converter::SauvolaBinaryImageConverter;
 pub use binary_image_converters::threshold_binary_image_converter::ThresholdBinaryImageConverter;
+pub use binary_image_converters::wolf_binary_image_converter::WolfBinaryImageConverter;
 pub use binary_image_converters::BinaryImageConverter;
+pub use channel_reduction::ChannelReduction;
+pub use channel_reduction::ColorChannel;
+pub use contours::{find_contours, Contour, ContourKind};
+pub use distance_transform::DistanceMetric;
+pub use grid::Grid;
+pub use hit_or_miss::{Template, TemplateCell, UpdateMode};
+pub use labeling::{label_components, BoundingBox, Component, Labeling};
+pub use morphology::StructuringElement;
+pub use progress::{CancellationToken, Cancelled, Progress};
+pub use roi::Roi;
+pub use skeleton_graph::{Edge, Node, NodeKind, SkeletonGraph};
 pub use skeletonizers::AdjacencyMode;
+pub use spur_pruner::{PrunedSkeletonizer, SpurLength, SpurPruner};
+pub use vectorizer::{Polyline, SkeletonVectorizer, VectorFormat};
@@ -0,0 +1,612 @@
+// morphology.rs - Erosion, dilation and the operations built on them
+// Copyright (C) 2019 Denis Karpovskiy
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use crate::binary_image::{BinaryImage, WORD_BITS};
+use std::cmp::min;
+
+// Pixels outside of the image never change the result, the erosion counts them
+// as foreground and the dilation as background. So the closing keeps every
+// foreground pixel and the opening adds none.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StructuringElement {
+    shape: Shape,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Shape {
+    Square(usize),
+    Cross(usize),
+    Disk(usize),
+    Custom(Vec<(i32, i32)>),
+}
+
+impl StructuringElement {
+    // A square with the side of `2 * radius + 1` pixels.
+    pub fn square(radius: usize) -> Self {
+        StructuringElement {
+            shape: Shape::Square(radius),
+        }
+    }
+
+    // A horizontal and a vertical line of `2 * radius + 1` pixels.
+    pub fn cross(radius: usize) -> Self {
+        StructuringElement {
+            shape: Shape::Cross(radius),
+        }
+    }
+
+    // The pixels no further than `radius` from the center.
+    pub fn disk(radius: usize) -> Self {
+        StructuringElement {
+            shape: Shape::Disk(radius),
+        }
+    }
+
+    // (delta x, delta y) of every pixel of the element from its origin.
+    pub fn from_offsets(offsets: Vec<(i32, i32)>) -> Self {
+        StructuringElement {
+            shape: Shape::Custom(offsets),
+        }
+    }
+
+    // The foreground pixels of the mask, the origin is a position in it.
+    pub fn from_mask(mask: &BinaryImage, origin_x: usize, origin_y: usize) -> Self {
+        let offsets = mask
+            .pixels_iter()
+            .filter(|&(x, y)| mask.is_fg(x, y))
+            .map(|(x, y)| (x as i32 - origin_x as i32, y as i32 - origin_y as i32))
+            .collect();
+
+        StructuringElement::from_offsets(offsets)
+    }
+
+    pub fn offsets(&self) -> Vec<(i32, i32)> {
+        let square = |radius: usize| {
+            let radius = radius as i32;
+            (-radius..=radius).flat_map(move |y| (-radius..=radius).map(move |x| (x, y)))
+        };
+
+        match &self.shape {
+            Shape::Square(radius) => square(*radius).collect(),
+            Shape::Cross(radius) => square(*radius).filter(|&(x, y)| x == 0 || y == 0).collect(),
+            Shape::Disk(radius) => square(*radius)
+                .filter(|&(x, y)| {
+                    disk_half_width(*radius, y.unsigned_abs() as usize) >= x.unsigned_abs() as usize
+                })
+                .collect(),
+            Shape::Custom(offsets) => offsets.clone(),
+        }
+    }
+}
+
+pub fn erode(image: &BinaryImage, element: &StructuringElement) -> BinaryImage {
+    apply(image, element, Operation::Erosion)
+}
+
+pub fn erode_in_place(image: &mut BinaryImage, element: &StructuringElement) {
+    *image = erode(image, element);
+}
+
+pub fn dilate(image: &BinaryImage, element: &StructuringElement) -> BinaryImage {
+    apply(image, element, Operation::Dilation)
+}
+
+pub fn dilate_in_place(image: &mut BinaryImage, element: &StructuringElement) {
+    *image = dilate(image, element);
+}
+
+// Removes the parts the element does not fit into.
+pub fn open(image: &BinaryImage, element: &StructuringElement) -> BinaryImage {
+    dilate(&erode(image, element), element)
+}
+
+pub fn open_in_place(image: &mut BinaryImage, element: &StructuringElement) {
+    *image = open(image, element);
+}
+
+// Fills the gaps the element does not fit into.
+pub fn close(image: &BinaryImage, element: &StructuringElement) -> BinaryImage {
+    erode(&dilate(image, element), element)
+}
+
+pub fn close_in_place(image: &mut BinaryImage, element: &StructuringElement) {
+    *image = close(image, element);
+}
+
+// The dilation without the erosion, the pixels around the boundaries.
+pub fn gradient(image: &BinaryImage, element: &StructuringElement) -> BinaryImage {
+    let dilated = Rows::from_image(&dilate(image, element));
+    let mut eroded = Rows::from_image(&erode(image, element));
+
+    for (eroded, dilated) in eroded.words.iter_mut().zip(dilated.words.iter()) {
+        *eroded = dilated & !*eroded;
+    }
+
+    eroded.to_image(image)
+}
+
+pub fn gradient_in_place(image: &mut BinaryImage, element: &StructuringElement) {
+    *image = gradient(image, element);
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Operation {
+    Erosion,
+    Dilation,
+}
+
+impl Operation {
+    // The word that leaves the other one as it is.
+    fn identity(self) -> u64 {
+        match self {
+            Operation::Erosion => !0,
+            Operation::Dilation => 0,
+        }
+    }
+
+    fn combine(self, a: u64, b: u64) -> u64 {
+        match self {
+            Operation::Erosion => a & b,
+            Operation::Dilation => a | b,
+        }
+    }
+}
+
+fn apply(image: &BinaryImage, element: &StructuringElement, operation: Operation) -> BinaryImage {
+    let rows = Rows::from_image(image);
+
+    let result = match &element.shape {
+        // the square and the cross are made of a horizontal and a vertical line
+        Shape::Square(radius) => spread_vertically(
+            &spread_horizontally(&rows, *radius, operation),
+            *radius,
+            operation,
+        ),
+        Shape::Cross(radius) => {
+            let mut result = spread_horizontally(&rows, *radius, operation);
+            result.combine(&spread_vertically(&rows, *radius, operation), operation);
+            result
+        }
+        Shape::Disk(radius) => apply_disk(&rows, *radius, operation),
+        Shape::Custom(offsets) => {
+            let mut result = Rows::identity(&rows, operation);
+            for &(delta_x, delta_y) in offsets {
+                let shifted = match operation {
+                    Operation::Erosion => rows.shifted(-delta_x, -delta_y, operation),
+                    Operation::Dilation => rows.shifted(delta_x, delta_y, operation),
+                };
+                result.combine(&shifted, operation);
+            }
+            result
+        }
+    };
+
+    result.to_image(image)
+}
+
+// A disk is a stack of horizontal lines, every line is spread once for each
+// distinct half width and then moved to its rows.
+fn apply_disk(rows: &Rows, radius: usize, operation: Operation) -> Rows {
+    let mut result = Rows::identity(rows, operation);
+
+    let mut delta_y = radius;
+    loop {
+        let half_width = disk_half_width(radius, delta_y);
+        let spread = spread_horizontally(rows, half_width, operation);
+
+        loop {
+            result.combine(&spread.shifted(0, delta_y as i32, operation), operation);
+            result.combine(&spread.shifted(0, -(delta_y as i32), operation), operation);
+
+            if delta_y == 0 || disk_half_width(radius, delta_y - 1) != half_width {
+                break;
+            }
+            delta_y -= 1;
+        }
+
+        if delta_y == 0 {
+            break;
+        }
+        delta_y -= 1;
+    }
+
+    result
+}
+
+fn disk_half_width(radius: usize, delta_y: usize) -> usize {
+    let mut half_width = 0;
+    while (half_width + 1) * (half_width + 1) + delta_y * delta_y <= radius * radius {
+        half_width += 1;
+    }
+
+    half_width
+}
+
+// Combines every pixel with the ones up to `radius` pixels to the left and to
+// the right, the covered distance doubles with each shift.
+fn spread_horizontally(rows: &Rows, radius: usize, operation: Operation) -> Rows {
+    spread(rows, radius, operation, |rows, step| {
+        rows.shifted(step, 0, operation)
+    })
+}
+
+fn spread_vertically(rows: &Rows, radius: usize, operation: Operation) -> Rows {
+    spread(rows, radius, operation, |rows, step| {
+        rows.shifted(0, step, operation)
+    })
+}
+
+fn spread<S>(rows: &Rows, radius: usize, operation: Operation, shift: S) -> Rows
+where
+    S: Fn(&Rows, i32) -> Rows,
+{
+    let mut result = rows.clone();
+
+    // each direction on its own, so the pixels shifted out are never needed
+    for &direction in [-1, 1].iter() {
+        let mut spread = rows.clone();
+        let mut covered = 1;
+        while covered <= radius {
+            let step = min(covered, radius + 1 - covered);
+            let shifted = shift(&spread, direction * step as i32);
+            spread.combine(&shifted, operation);
+            covered += step;
+        }
+        result.combine(&spread, operation);
+    }
+
+    result
+}
+
+// The pixels of an image as plain words, padding bits are kept clear.
+#[derive(Debug, Clone)]
+struct Rows {
+    words: Vec<u64>,
+    words_per_row: usize,
+    width: usize,
+    height: usize,
+}
+
+impl Rows {
+    fn from_image(image: &BinaryImage) -> Self {
+        Rows {
+            words: (0..image.height())
+                .flat_map(|y| image.row_words(y).iter().copied())
+                .collect(),
+            words_per_row: image.words_per_row(),
+            width: image.width(),
+            height: image.height(),
+        }
+    }
+
+    // The value every pixel starts from before combining the shifted images.
+    fn identity(rows: &Rows, operation: Operation) -> Self {
+        let mut identity = Rows {
+            words: vec![operation.identity(); rows.words.len()],
+            ..*rows
+        };
+
+        let last_word_mask = identity.last_word_mask();
+        for row in identity.words.chunks_mut(identity.words_per_row) {
+            if let Some(last) = row.last_mut() {
+                *last &= last_word_mask;
+            }
+        }
+
+        identity
+    }
+
+    fn to_image(&self, like: &BinaryImage) -> BinaryImage {
+        let mut image = BinaryImage::new(self.width, self.height, like.get_bg_color());
+        for y in 0..self.height {
+            for word_x in 0..self.words_per_row {
+                image.set_word(word_x, y, self.words[y * self.words_per_row + word_x]);
+            }
+        }
+
+        image
+    }
+
+    fn combine(&mut self, other: &Rows, operation: Operation) {
+        for (word, other) in self.words.iter_mut().zip(other.words.iter()) {
+            *word = operation.combine(*word, *other);
+        }
+    }
+
+    // Moves the pixel (x, y) to (x + delta_x, y + delta_y), the pixels that
+    // come from outside of the image are the identity of the operation.
+    fn shifted(&self, delta_x: i32, delta_y: i32, operation: Operation) -> Rows {
+        let mut shifted = Rows::identity(self, operation);
+
+        let last_word_mask = self.last_word_mask();
+        let outside = operation.identity();
+        for y in 0..self.height {
+            let source_y = y as i32 - delta_y;
+            if source_y < 0 || source_y >= self.height as i32 {
+                continue;
+            }
+
+            let start = source_y as usize * self.words_per_row;
+            let source = &self.words[start..start + self.words_per_row];
+            let start = y * self.words_per_row;
+            let target = &mut shifted.words[start..start + self.words_per_row];
+
+            shift_row(source, delta_x, outside & !last_word_mask, outside, target);
+            if let Some(last) = target.last_mut() {
+                *last &= last_word_mask;
+            }
+        }
+
+        shifted
+    }
+
+    fn last_word_mask(&self) -> u64 {
+        match self.width % WORD_BITS {
+            0 => !0,
+            rest => (1 << rest) - 1,
+        }
+    }
+}
+
+// The bit of pixel x is at `x % WORD_BITS` of word `x / WORD_BITS`, so moving
+// pixels to the right shifts the bits to the left. The padding bits of the last
+// word are replaced with `padding` and the words outside of the row are
+// `outside`.
+fn shift_row(source: &[u64], delta_x: i32, padding: u64, outside: u64, target: &mut [u64]) {
+    let word_shift = delta_x.unsigned_abs() as usize / WORD_BITS;
+    let bit_shift = delta_x.unsigned_abs() as usize % WORD_BITS;
+    let last = source.len() as i64 - 1;
+    let word = |i: i64| match i {
+        i if i < 0 || i > last => outside,
+        i if i == last => source[i as usize] | padding,
+        i => source[i as usize],
+    };
+
+    for (i, target) in target.iter_mut().enumerate() {
+        let i = i as i64;
+        *target = if delta_x >= 0 {
+            let from = i - word_shift as i64;
+            match bit_shift {
+                0 => word(from),
+                _ => word(from) << bit_shift | word(from - 1) >> (WORD_BITS - bit_shift),
+            }
+        } else {
+            let from = i + word_shift as i64;
+            match bit_shift {
+                0 => word(from),
+                _ => word(from) >> bit_shift | word(from + 1) << (WORD_BITS - bit_shift),
+            }
+        };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::binary_image::PixelColor;
+    use crate::skeletonizers::test_shapes::{from_ascii, to_ascii};
+
+    fn noise_image(width: usize, height: usize, seed: u64) -> BinaryImage {
+        let mut image = BinaryImage::new(width, height, PixelColor::White);
+        let mut state = seed;
+        for (x, y) in image.pixels_iter() {
+            state = state
+                .wrapping_mul(6364136223846793005)
+                .wrapping_add(1442695040888963407);
+            if state >> 62 != 0 {
+                image.set_fg(x, y);
+            }
+        }
+
+        image
+    }
+
+    // Straight from the definitions, pixels outside of the image are foreground
+    // for the erosion and background for the dilation.
+    fn apply_naive(
+        image: &BinaryImage,
+        element: &StructuringElement,
+        operation: Operation,
+    ) -> Vec<String> {
+        let offsets = element.offsets();
+        let is_inside = |x: i32, y: i32| {
+            x >= 0 && y >= 0 && (x as usize) < image.width() && (y as usize) < image.height()
+        };
+        let mut result = BinaryImage::new(image.width(), image.height(), PixelColor::White);
+        for (x, y) in image.pixels_iter() {
+            let (x, y) = (x as i32, y as i32);
+            let is_fg = match operation {
+                Operation::Erosion => offsets.iter().all(|&(dx, dy)| {
+                    !is_inside(x + dx, y + dy) || image.is_fg_or_default(x + dx, y + dy)
+                }),
+                Operation::Dilation => offsets
+                    .iter()
+                    .any(|&(dx, dy)| image.is_fg_or_default(x - dx, y - dy)),
+            };
+            if is_fg {
+                result.set_fg(x as usize, y as usize);
+            }
+        }
+
+        to_ascii(&result)
+    }
+
+    #[test]
+    fn morphology_matches_definitions_test() {
+        // Arrange
+        let image = noise_image(150, 30, 7);
+        let elements = [
+            StructuringElement::square(1),
+            StructuringElement::square(3),
+            StructuringElement::cross(2),
+            StructuringElement::disk(1),
+            StructuringElement::disk(4),
+            StructuringElement::from_offsets(vec![(0, 0), (70, 1), (-3, -2)]),
+        ];
+
+        for element in elements.iter() {
+            // Act
+            let eroded = erode(&image, element);
+            let dilated = dilate(&image, element);
+
+            // Assert
+            assert_eq!(
+                apply_naive(&image, element, Operation::Erosion),
+                to_ascii(&eroded)
+            );
+            assert_eq!(
+                apply_naive(&image, element, Operation::Dilation),
+                to_ascii(&dilated)
+            );
+        }
+    }
+
+    #[test]
+    fn disk_offsets_test() {
+        // Arrange
+        let element = StructuringElement::disk(2);
+
+        // Act
+        let offsets = element.offsets();
+
+        // Assert
+        assert_eq!(13, offsets.len());
+        assert!(offsets.contains(&(1, 1)));
+        assert!(!offsets.contains(&(2, 1)));
+    }
+
+    #[test]
+    fn from_mask_test() {
+        // Arrange
+        let mask = from_ascii(&[
+            "#..", //
+            ".#.", //
+        ]);
+
+        // Act
+        let element = StructuringElement::from_mask(&mask, 1, 1);
+
+        // Assert
+        assert_eq!(vec![(-1, -1), (0, 0)], element.offsets());
+    }
+
+    #[test]
+    fn open_close_test() {
+        // Arrange
+        let image = from_ascii(&[
+            "#...........", //
+            "............", //
+            "..#####.##..", //
+            "..#####.##..", //
+            "..#####.##..", //
+            "............", //
+            "............", //
+        ]);
+        let element = StructuringElement::square(1);
+
+        // Act
+        let opened = open(&image, &element);
+        let closed = close(&image, &element);
+
+        // Assert
+        assert_eq!(
+            vec![
+                "............",
+                "............",
+                "..#####.....",
+                "..#####.....",
+                "..#####.....",
+                "............",
+                "............",
+            ],
+            to_ascii(&opened)
+        );
+        assert_eq!(
+            vec![
+                "#...........",
+                "............",
+                "..########..",
+                "..########..",
+                "..########..",
+                "............",
+                "............",
+            ],
+            to_ascii(&closed)
+        );
+    }
+
+    #[test]
+    fn open_close_at_border_test() {
+        // Arrange
+        let image = from_ascii(&[
+            "#.....", //
+            "#.....", //
+            "#..#..", //
+            "#.....", //
+            "#.....", //
+            "#.....", //
+        ]);
+        let elements = [StructuringElement::square(1), StructuringElement::disk(2)];
+
+        for element in elements.iter() {
+            // Act
+            let opened = open(&image, element);
+            let closed = close(&image, element);
+
+            // Assert
+            for (x, y) in image.pixels_iter() {
+                assert!(!opened.is_fg(x, y) || image.is_fg(x, y));
+                assert!(!image.is_fg(x, y) || closed.is_fg(x, y));
+            }
+            assert!(closed.is_fg(0, 0) && closed.is_fg(0, 5));
+        }
+    }
+
+    #[test]
+    fn gradient_test() {
+        // Arrange
+        let image = from_ascii(&[
+            ".....", //
+            ".###.", //
+            ".###.", //
+            ".###.", //
+            ".....", //
+        ]);
+
+        // Act
+        let result = gradient(&image, &StructuringElement::cross(1));
+
+        // Assert
+        assert_eq!(
+            vec![".###.", "#####", "##.##", "#####", ".###."],
+            to_ascii(&result)
+        );
+    }
+
+    #[test]
+    fn in_place_test() {
+        // Arrange
+        let image = noise_image(70, 10, 3);
+        let element = StructuringElement::disk(2);
+        let mut in_place = image.clone();
+
+        // Act
+        close_in_place(&mut in_place, &element);
+
+        // Assert
+        assert_eq!(to_ascii(&close(&image, &element)), to_ascii(&in_place));
+    }
+}
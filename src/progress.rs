@@ -0,0 +1,102 @@
+// progress.rs - Progress reports and cancellation of long running algorithms
+// Copyright (C) 2019 Denis Karpovskiy
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use std::error::Error;
+use std::fmt;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+// What a progress callback answers to the algorithm reporting to it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Progress {
+    Continue,
+    Cancel,
+}
+
+// An algorithm stopped by its progress callback, the image it was given is
+// left as it was.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Cancelled;
+
+impl fmt::Display for Cancelled {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "the operation was cancelled")
+    }
+}
+
+impl Error for Cancelled {}
+
+// A flag that can be raised from another thread, the clones share it.
+#[derive(Debug, Clone, Default)]
+pub struct CancellationToken {
+    is_cancelled: Arc<AtomicBool>,
+}
+
+impl CancellationToken {
+    pub fn new() -> Self {
+        CancellationToken::default()
+    }
+
+    pub fn cancel(&self) {
+        self.is_cancelled.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.is_cancelled.load(Ordering::Relaxed)
+    }
+
+    // The answer for a progress callback, so a callback can end with
+    // `token.progress()`.
+    pub fn progress(&self) -> Progress {
+        if self.is_cancelled() {
+            Progress::Cancel
+        } else {
+            Progress::Continue
+        }
+    }
+}
+
+// Reports the progress and turns a cancel into an error to return with `?`.
+pub(crate) fn report<F>(report_progress: &F, current: i32, max: i32) -> Result<(), Cancelled>
+where
+    F: Fn(i32, i32) -> Progress,
+{
+    match report_progress(current, max) {
+        Progress::Continue => Ok(()),
+        Progress::Cancel => Err(Cancelled),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cancellation_token_test() {
+        // Arrange
+        let token = CancellationToken::new();
+        let clone = token.clone();
+
+        // Act
+        let before = token.progress();
+        clone.cancel();
+
+        // Assert
+        assert_eq!(Progress::Continue, before);
+        assert!(token.is_cancelled());
+        assert_eq!(Err(Cancelled), report(&|_, _| token.progress(), 1, 2));
+    }
+}
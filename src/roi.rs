@@ -0,0 +1,123 @@
+// roi.rs - Regions of interest restricting the pixels an algorithm may change
+// Copyright (C) 2019 Denis Karpovskiy
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use crate::binary_image::{BinaryImage, PixelColor};
+use crate::labeling::BoundingBox;
+use std::cmp::min;
+
+// The pixels an algorithm is allowed to change, the rest of the image is only
+// read as the context around them.
+#[derive(Debug, Clone)]
+pub enum Roi {
+    Rect(BoundingBox),
+    // The foreground of the mask, pixels outside of it are outside.
+    Mask(BinaryImage),
+}
+
+impl Roi {
+    pub fn contains(&self, x: usize, y: usize) -> bool {
+        match self {
+            Roi::Rect(rect) => rect.contains(x, y),
+            Roi::Mask(mask) => x < mask.width() && y < mask.height() && mask.is_fg(x, y),
+        }
+    }
+
+    // A `width` by `height` mask whose foreground is the part of the region
+    // inside of it.
+    pub fn to_mask(&self, width: usize, height: usize) -> BinaryImage {
+        let mut mask = BinaryImage::new(width, height, PixelColor::White);
+
+        match self {
+            Roi::Rect(rect) => {
+                for y in rect.y()..min(rect.y().saturating_add(rect.height()), height) {
+                    for x in rect.x()..min(rect.x().saturating_add(rect.width()), width) {
+                        mask.set_fg(x, y);
+                    }
+                }
+            }
+            Roi::Mask(roi_mask) => {
+                // the tail bits of a row are always clear, so whole words
+                // can be copied and `set_word` drops whatever sticks out
+                let words_per_row = min(mask.words_per_row(), roi_mask.words_per_row());
+                for y in 0..min(height, roi_mask.height()) {
+                    for word_x in 0..words_per_row {
+                        mask.set_word(word_x, y, roi_mask.get_word(word_x, y));
+                    }
+                }
+            }
+        }
+
+        mask
+    }
+}
+
+// The mask of the pixels that may be changed, all of them without a region.
+pub(crate) fn editable_mask(roi: Option<&Roi>, width: usize, height: usize) -> BinaryImage {
+    match roi {
+        Some(roi) => roi.to_mask(width, height),
+        None => {
+            let mut mask = BinaryImage::new(width, height, PixelColor::White);
+            mask.fill(PixelColor::Black);
+            mask
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::skeletonizers::test_shapes::{from_ascii, to_ascii};
+
+    #[test]
+    fn to_mask_test() {
+        // Arrange
+        let rect = Roi::Rect(BoundingBox::new(2, 1, 5, 2));
+        let mask = Roi::Mask(from_ascii(&[
+            "#..", //
+            ".#.", //
+        ]));
+
+        // Act
+        let rect_mask = rect.to_mask(5, 4);
+        let mask_mask = mask.to_mask(5, 3);
+
+        // Assert
+        assert_eq!(
+            vec![".....", "..###", "..###", "....."],
+            to_ascii(&rect_mask)
+        );
+        assert_eq!(vec!["#....", ".#...", "....."], to_ascii(&mask_mask));
+        assert!(rect.contains(6, 2));
+        assert!(!mask.contains(3, 0));
+    }
+
+    #[test]
+    fn to_mask_far_rect_test() {
+        // Arrange
+        let far = Roi::Rect(BoundingBox::new(usize::MAX, usize::MAX, 2, 2));
+        let huge = Roi::Rect(BoundingBox::new(1, 0, usize::MAX, usize::MAX));
+
+        // Act
+        let far_mask = far.to_mask(3, 2);
+        let huge_mask = huge.to_mask(3, 2);
+
+        // Assert
+        assert_eq!(vec!["...", "..."], to_ascii(&far_mask));
+        assert_eq!(vec![".##", ".##"], to_ascii(&huge_mask));
+        assert!(!far.contains(0, 0));
+        assert!(huge.contains(usize::MAX, 5));
+    }
+}
@@ -0,0 +1,604 @@
+// skeleton_graph.rs - Topology of a skeleton as a graph of nodes and edges
+// Copyright (C) 2019 Denis Karpovskiy
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use crate::binary_image::BinaryImage;
+use crate::bool_matrix::BoolMatrix;
+use crate::grid::Grid;
+use crate::skeletonizers::AdjacencyMode;
+use std::fmt::Write;
+
+// (delta x, delta y) of the neighbours clockwise starting from the north one
+const NEIGHBOURS: [(i32, i32); 8] = [
+    (0, -1),
+    (1, -1),
+    (1, 0),
+    (1, 1),
+    (0, 1),
+    (-1, 1),
+    (-1, 0),
+    (-1, -1),
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NodeKind {
+    Isolated,
+    Endpoint,
+    Junction,
+    // A pixel picked on a closed loop that has no other nodes
+    Cycle,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Node {
+    x: usize,
+    y: usize,
+    kind: NodeKind,
+    degree: usize,
+    pixels: Vec<(usize, usize)>,
+}
+
+impl Node {
+    pub fn x(&self) -> usize {
+        self.x
+    }
+
+    pub fn y(&self) -> usize {
+        self.y
+    }
+
+    pub fn position(&self) -> (usize, usize) {
+        (self.x, self.y)
+    }
+
+    pub fn kind(&self) -> NodeKind {
+        self.kind
+    }
+
+    // The number of edge ends at the node, a loop counts twice.
+    pub fn degree(&self) -> usize {
+        self.degree
+    }
+
+    // A junction can span several adjacent pixels, the position is the one
+    // closest to their centroid.
+    pub fn pixels(&self) -> &[(usize, usize)] {
+        &self.pixels
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Edge {
+    start: usize,
+    end: usize,
+    pixels: Vec<(usize, usize)>,
+    length: f64,
+}
+
+impl Edge {
+    pub fn start(&self) -> usize {
+        self.start
+    }
+
+    pub fn end(&self) -> usize {
+        self.end
+    }
+
+    pub fn is_loop(&self) -> bool {
+        self.start == self.end
+    }
+
+    // The pixels going from the start node to the end node, both node pixels
+    // included.
+    pub fn pixels(&self) -> &[(usize, usize)] {
+        &self.pixels
+    }
+
+    // The length of the polyline through the pixels.
+    pub fn length(&self) -> f64 {
+        self.length
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct SkeletonGraph {
+    nodes: Vec<Node>,
+    edges: Vec<Edge>,
+}
+
+impl SkeletonGraph {
+    pub fn new(image: &BinaryImage, mode: AdjacencyMode) -> Self {
+        let mut builder = GraphBuilder::new(image, mode);
+
+        builder.add_nodes();
+        builder.add_edges();
+        builder.add_cycles();
+
+        let mut graph = SkeletonGraph {
+            nodes: builder.nodes,
+            edges: builder.edges,
+        };
+        for i in 0..graph.edges.len() {
+            graph.nodes[graph.edges[i].start].degree += 1;
+            graph.nodes[graph.edges[i].end].degree += 1;
+        }
+
+        graph
+    }
+
+    pub fn nodes(&self) -> std::slice::Iter<'_, Node> {
+        self.nodes.iter()
+    }
+
+    pub fn edges(&self) -> std::slice::Iter<'_, Edge> {
+        self.edges.iter()
+    }
+
+    pub fn node(&self, index: usize) -> &Node {
+        &self.nodes[index]
+    }
+
+    pub fn edge(&self, index: usize) -> &Edge {
+        &self.edges[index]
+    }
+
+    pub fn node_count(&self) -> usize {
+        self.nodes.len()
+    }
+
+    pub fn edge_count(&self) -> usize {
+        self.edges.len()
+    }
+
+    pub fn endpoints(&self) -> impl Iterator<Item = &Node> {
+        self.nodes_of_kind(NodeKind::Endpoint)
+    }
+
+    pub fn junctions(&self) -> impl Iterator<Item = &Node> {
+        self.nodes_of_kind(NodeKind::Junction)
+    }
+
+    // The indices of the edges that start or end at the node.
+    pub fn node_edges(&self, node: usize) -> impl Iterator<Item = usize> + '_ {
+        self.edges
+            .iter()
+            .enumerate()
+            .filter(move |(_, edge)| edge.start == node || edge.end == node)
+            .map(|(i, _)| i)
+    }
+
+    pub fn to_json(&self) -> String {
+        let mut json = String::from("{\"nodes\":[");
+        for (i, node) in self.nodes.iter().enumerate() {
+            if i > 0 {
+                json.push(',');
+            }
+            let kind = match node.kind {
+                NodeKind::Isolated => "isolated",
+                NodeKind::Endpoint => "endpoint",
+                NodeKind::Junction => "junction",
+                NodeKind::Cycle => "cycle",
+            };
+            write!(
+                json,
+                "{{\"x\":{},\"y\":{},\"kind\":\"{}\",\"degree\":{}}}",
+                node.x, node.y, kind, node.degree
+            )
+            .unwrap();
+        }
+
+        json.push_str("],\"edges\":[");
+        for (i, edge) in self.edges.iter().enumerate() {
+            if i > 0 {
+                json.push(',');
+            }
+            write!(
+                json,
+                "{{\"start\":{},\"end\":{},\"length\":{},\"pixels\":[",
+                edge.start, edge.end, edge.length
+            )
+            .unwrap();
+            for (j, (x, y)) in edge.pixels.iter().enumerate() {
+                if j > 0 {
+                    json.push(',');
+                }
+                write!(json, "[{},{}]", x, y).unwrap();
+            }
+            json.push_str("]}");
+        }
+        json.push_str("]}");
+
+        json
+    }
+
+    fn nodes_of_kind(&self, kind: NodeKind) -> impl Iterator<Item = &Node> {
+        self.nodes.iter().filter(move |node| node.kind == kind)
+    }
+}
+
+struct GraphBuilder<'a> {
+    image: &'a BinaryImage,
+    mode: AdjacencyMode,
+    node_ids: Grid<Option<usize>>,
+    visited: BoolMatrix,
+    nodes: Vec<Node>,
+    edges: Vec<Edge>,
+}
+
+impl<'a> GraphBuilder<'a> {
+    fn new(image: &'a BinaryImage, mode: AdjacencyMode) -> Self {
+        GraphBuilder {
+            image,
+            mode,
+            node_ids: Grid::new(image.width(), image.height(), None),
+            visited: BoolMatrix::new(image.width(), image.height(), false),
+            nodes: Vec::new(),
+            edges: Vec::new(),
+        }
+    }
+
+    // Every pixel that does not have exactly two neighbours is a node, the
+    // adjacent junction pixels are merged into one node.
+    fn add_nodes(&mut self) {
+        for (x, y) in self.image.pixels_iter() {
+            if !self.image.is_fg(x, y) || self.node_ids.get(x, y).is_some() {
+                continue;
+            }
+
+            let kind = match self.neighbours(x, y).len() {
+                0 => NodeKind::Isolated,
+                1 => NodeKind::Endpoint,
+                2 => continue,
+                _ => NodeKind::Junction,
+            };
+
+            let pixels = if kind == NodeKind::Junction {
+                self.junction_pixels(x, y)
+            } else {
+                vec![(x, y)]
+            };
+
+            self.add_node(kind, pixels);
+        }
+    }
+
+    fn junction_pixels(&self, x: usize, y: usize) -> Vec<(usize, usize)> {
+        let mut pixels = vec![(x, y)];
+        let mut is_added = BoolMatrix::new(self.image.width(), self.image.height(), false);
+        is_added.set(x, y);
+
+        let mut i = 0;
+        while i < pixels.len() {
+            let (x, y) = pixels[i];
+            for (next_x, next_y) in self.neighbours(x, y) {
+                if !is_added.check(next_x, next_y) && self.neighbours(next_x, next_y).len() > 2 {
+                    is_added.set(next_x, next_y);
+                    pixels.push((next_x, next_y));
+                }
+            }
+            i += 1;
+        }
+
+        pixels
+    }
+
+    fn add_node(&mut self, kind: NodeKind, pixels: Vec<(usize, usize)>) -> usize {
+        let index = self.nodes.len();
+        for &(x, y) in pixels.iter() {
+            self.node_ids.set(x, y, Some(index));
+        }
+
+        let count = pixels.len() as f64;
+        let center_x = pixels.iter().map(|&(x, _)| x as f64).sum::<f64>() / count;
+        let center_y = pixels.iter().map(|&(_, y)| y as f64).sum::<f64>() / count;
+        let distance = |&(x, y): &(usize, usize)| {
+            (x as f64 - center_x).powi(2) + (y as f64 - center_y).powi(2)
+        };
+        let mut position = pixels[0];
+        for pixel in pixels.iter() {
+            if distance(pixel) < distance(&position) {
+                position = *pixel;
+            }
+        }
+
+        self.nodes.push(Node {
+            x: position.0,
+            y: position.1,
+            kind,
+            degree: 0,
+            pixels,
+        });
+
+        index
+    }
+
+    fn add_edges(&mut self) {
+        for node in 0..self.nodes.len() {
+            for i in 0..self.nodes[node].pixels.len() {
+                let pixel = self.nodes[node].pixels[i];
+                for next in self.neighbours(pixel.0, pixel.1) {
+                    match self.node_ids.get(next.0, next.1) {
+                        Some(other) if other == node => {}
+                        // adjacent nodes are seen from both sides
+                        Some(other) => {
+                            if (pixel.1, pixel.0) < (next.1, next.0) {
+                                self.push_edge(node, other, vec![pixel, next]);
+                            }
+                        }
+                        None => {
+                            if !self.visited.check(next.0, next.1) {
+                                self.trace(node, pixel, next);
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    // The pixels left are closed loops, each gets a node of its own.
+    fn add_cycles(&mut self) {
+        for (x, y) in self.image.pixels_iter() {
+            if self.image.is_fg(x, y)
+                && self.node_ids.get(x, y).is_none()
+                && !self.visited.check(x, y)
+            {
+                let node = self.add_node(NodeKind::Cycle, vec![(x, y)]);
+                let next = self.neighbours(x, y)[0];
+                self.trace(node, (x, y), next);
+            }
+        }
+    }
+
+    // Follows the pixels with two neighbours from `first` until a node.
+    fn trace(&mut self, node: usize, start: (usize, usize), first: (usize, usize)) {
+        let mut pixels = vec![start, first];
+        self.visited.set(first.0, first.1);
+
+        let mut previous = start;
+        let mut current = first;
+        loop {
+            let next = self
+                .neighbours(current.0, current.1)
+                .into_iter()
+                .find(|&pixel| pixel != previous);
+
+            let next = match next {
+                Some(next) => next,
+                None => break,
+            };
+            pixels.push(next);
+
+            if let Some(end) = self.node_ids.get(next.0, next.1) {
+                self.push_edge(node, end, pixels);
+                return;
+            }
+
+            self.visited.set(next.0, next.1);
+            previous = current;
+            current = next;
+        }
+    }
+
+    fn push_edge(&mut self, start: usize, end: usize, pixels: Vec<(usize, usize)>) {
+        let length = pixels
+            .windows(2)
+            .map(|pair| {
+                let delta_x = pair[0].0 as f64 - pair[1].0 as f64;
+                let delta_y = pair[0].1 as f64 - pair[1].1 as f64;
+                delta_x.hypot(delta_y)
+            })
+            .sum();
+
+        self.edges.push(Edge {
+            start,
+            end,
+            pixels,
+            length,
+        });
+    }
+
+    // In the 8-adjacency a diagonal neighbour is skipped when a side
+    // neighbour already links to it, otherwise every corner of a line would
+    // look like a junction.
+    fn neighbours(&self, x: usize, y: usize) -> Vec<(usize, usize)> {
+        let is_fg = |(delta_x, delta_y): (i32, i32)| {
+            self.image
+                .is_fg_or_default(x as i32 + delta_x, y as i32 + delta_y)
+        };
+
+        NEIGHBOURS
+            .iter()
+            .filter(|&&(delta_x, delta_y)| {
+                let is_diagonal = delta_x != 0 && delta_y != 0;
+                if is_diagonal {
+                    self.mode == AdjacencyMode::Eight
+                        && is_fg((delta_x, delta_y))
+                        && !is_fg((delta_x, 0))
+                        && !is_fg((0, delta_y))
+                } else {
+                    is_fg((delta_x, delta_y))
+                }
+            })
+            .map(|&(delta_x, delta_y)| {
+                ((x as i32 + delta_x) as usize, (y as i32 + delta_y) as usize)
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::skeletonizers::test_shapes::from_ascii;
+
+    #[test]
+    fn skeleton_graph_plus_test() {
+        // Arrange
+        let image = from_ascii(&[
+            "...#...", //
+            "...#...", //
+            "...#...", //
+            "#######", //
+            "...#...", //
+            "...#...", //
+        ]);
+
+        // Act
+        let graph = SkeletonGraph::new(&image, AdjacencyMode::Eight);
+
+        // Assert
+        assert_eq!(5, graph.node_count());
+        assert_eq!(4, graph.edge_count());
+
+        let junctions: Vec<&Node> = graph.junctions().collect();
+        assert_eq!(1, junctions.len());
+        assert_eq!((3, 3), junctions[0].position());
+        assert_eq!(4, junctions[0].degree());
+
+        let mut endpoints: Vec<(usize, usize)> = graph.endpoints().map(|n| n.position()).collect();
+        endpoints.sort();
+        assert_eq!(vec![(0, 3), (3, 0), (3, 5), (6, 3)], endpoints);
+
+        let mut lengths: Vec<f64> = graph.edges().map(|edge| edge.length()).collect();
+        lengths.sort_by(|a, b| a.partial_cmp(b).unwrap());
+        assert_eq!(vec![2.0, 3.0, 3.0, 3.0], lengths);
+    }
+
+    #[test]
+    fn skeleton_graph_edge_pixels_test() {
+        // Arrange
+        let image = from_ascii(&[
+            "#.....", //
+            ".#....", //
+            ".#####", //
+        ]);
+
+        // Act
+        let graph = SkeletonGraph::new(&image, AdjacencyMode::Eight);
+
+        // Assert
+        assert_eq!(2, graph.node_count());
+        assert_eq!(1, graph.edge_count());
+
+        let edge = graph.edge(0);
+        let start = graph.node(edge.start()).position();
+        let end = graph.node(edge.end()).position();
+        assert_eq!(start, edge.pixels()[0]);
+        assert_eq!(end, *edge.pixels().last().unwrap());
+        assert_eq!(
+            vec![(0, 0), (1, 1), (1, 2), (2, 2), (3, 2), (4, 2), (5, 2)],
+            edge.pixels().to_vec()
+        );
+        assert!((edge.length() - (5.0 + 2f64.sqrt())).abs() < 1e-9);
+    }
+
+    #[test]
+    fn skeleton_graph_cycle_test() {
+        // Arrange
+        let image = from_ascii(&[
+            ".###.", //
+            "#...#", //
+            "#...#", //
+            ".###.", //
+            ".....", //
+            "....#", //
+        ]);
+
+        // Act
+        let graph = SkeletonGraph::new(&image, AdjacencyMode::Eight);
+
+        // Assert
+        assert_eq!(2, graph.node_count());
+        assert_eq!(1, graph.edge_count());
+
+        let cycle = graph.nodes().find(|n| n.kind() == NodeKind::Cycle).unwrap();
+        assert_eq!(2, cycle.degree());
+        assert!(graph.edge(0).is_loop());
+        assert_eq!(11, graph.edge(0).pixels().len());
+
+        let isolated = graph
+            .nodes()
+            .find(|n| n.kind() == NodeKind::Isolated)
+            .unwrap();
+        assert_eq!((4, 5), isolated.position());
+        assert_eq!(0, isolated.degree());
+    }
+
+    #[test]
+    fn skeleton_graph_four_adjacency_test() {
+        // Arrange
+        let image = from_ascii(&[
+            "##..", //
+            ".#..", //
+            "..##", //
+        ]);
+
+        // Act
+        let graph = SkeletonGraph::new(&image, AdjacencyMode::Four);
+
+        // Assert
+        assert_eq!(4, graph.node_count());
+        assert_eq!(2, graph.edge_count());
+        assert_eq!(4, graph.endpoints().count());
+    }
+
+    #[test]
+    fn skeleton_graph_junction_cluster_test() {
+        // Arrange
+        let image = from_ascii(&[
+            "#....#", //
+            ".#..#.", //
+            "..##..", //
+            "..##..", //
+            ".#..#.", //
+            "#....#", //
+        ]);
+
+        // Act
+        let graph = SkeletonGraph::new(&image, AdjacencyMode::Eight);
+
+        // Assert
+        assert_eq!(5, graph.node_count());
+        assert_eq!(4, graph.edge_count());
+
+        let junction = graph
+            .nodes()
+            .position(|n| n.kind() == NodeKind::Junction)
+            .unwrap();
+        assert_eq!(4, graph.node(junction).pixels().len());
+        assert_eq!((2, 2), graph.node(junction).position());
+        assert_eq!(4, graph.node(junction).degree());
+        assert_eq!(4, graph.node_edges(junction).count());
+    }
+
+    #[test]
+    fn skeleton_graph_to_json_test() {
+        // Arrange
+        let image = from_ascii(&["##"]);
+        let graph = SkeletonGraph::new(&image, AdjacencyMode::Eight);
+
+        // Act
+        let json = graph.to_json();
+
+        // Assert
+        assert_eq!(
+            "{\"nodes\":[{\"x\":0,\"y\":0,\"kind\":\"endpoint\",\"degree\":1},\
+             {\"x\":1,\"y\":0,\"kind\":\"endpoint\",\"degree\":1}],\
+             \"edges\":[{\"start\":0,\"end\":1,\"length\":1,\"pixels\":[[0,0],[1,0]]}]}",
+            json
+        );
+    }
+}
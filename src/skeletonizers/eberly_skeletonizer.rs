@@ -16,6 +16,8 @@
 
 use crate::binary_image::BinaryImage;
 use crate::bool_matrix::BoolMatrix;
+use crate::progress::{report, Cancelled, Progress};
+use crate::roi::{editable_mask, Roi};
 use crate::skeletonizers::{is_local_articulation_point, AdjacencyMode, Skeletonizer};
 use std::cmp::max;
 
@@ -26,9 +28,10 @@ struct TwoInteriorAlgorithm;
 
 trait EberlyInteriorAlgorithm {
     fn is_interior(image: &BinaryImage, x: usize, y: usize) -> bool;
-    fn remove_interiors(image: &mut BinaryImage, is_interior: &BoolMatrix) {
+    fn remove_interiors(image: &mut BinaryImage, editable: &BinaryImage, is_interior: &BoolMatrix) {
         for (x, y) in image.pixels_iter() {
             if is_interior.check(x, y)
+                && editable.is_fg(x, y)
                 && !is_local_articulation_point(image, x, y, AdjacencyMode::Eight)
             {
                 image.set_bg(x, y);
@@ -45,28 +48,48 @@ enum ReturnStatus {
 }
 
 impl Skeletonizer for EberlySkeletonizer {
-    fn process_with_progress<F>(&self, image: &mut BinaryImage, report_progress: F)
+    fn process_with_progress<F>(
+        &self,
+        image: &mut BinaryImage,
+        roi: Option<&Roi>,
+        report_progress: F,
+    ) -> Result<(), Cancelled>
     where
-        F: Fn(i32, i32),
+        F: Fn(i32, i32) -> Progress,
     {
         let max_progress = Self::compute_max_progress(image.width(), image.height());
         let mut current_progress = 0;
-        while Self::thinning::<FourInteriorAlgorithm>(image) == ReturnStatus::ExitCriteriaNotMet {
+        let editable = editable_mask(roi, image.width(), image.height());
+
+        // thinned on a copy, so a cancelled run leaves the image as it was
+        let mut thinned = image.clone();
+
+        while Self::thinning::<FourInteriorAlgorithm>(&mut thinned, &editable)
+            == ReturnStatus::ExitCriteriaNotMet
+        {
             current_progress += 1;
-            report_progress(current_progress as i32, max_progress as i32);
+            report(&report_progress, current_progress, max_progress as i32)?;
         }
 
-        while Self::thinning::<ThreeInteriorAlgorithm>(image) == ReturnStatus::ExitCriteriaNotMet {
+        while Self::thinning::<ThreeInteriorAlgorithm>(&mut thinned, &editable)
+            == ReturnStatus::ExitCriteriaNotMet
+        {
             current_progress += 1;
-            report_progress(current_progress as i32, max_progress as i32);
+            report(&report_progress, current_progress, max_progress as i32)?;
         }
 
-        while Self::thinning::<TwoInteriorAlgorithm>(image) == ReturnStatus::ExitCriteriaNotMet {
+        while Self::thinning::<TwoInteriorAlgorithm>(&mut thinned, &editable)
+            == ReturnStatus::ExitCriteriaNotMet
+        {
             current_progress += 1;
-            report_progress(current_progress as i32, max_progress as i32);
+            report(&report_progress, current_progress, max_progress as i32)?;
         }
 
         report_progress(max_progress as i32, max_progress as i32);
+
+        *image = thinned;
+
+        Ok(())
     }
 }
 
@@ -79,11 +102,14 @@ impl EberlySkeletonizer {
         max(width, height) / 2
     }
 
-    fn thinning<T: EberlyInteriorAlgorithm>(image: &mut BinaryImage) -> ReturnStatus {
+    fn thinning<T: EberlyInteriorAlgorithm>(
+        image: &mut BinaryImage,
+        editable: &BinaryImage,
+    ) -> ReturnStatus {
         if let Some(is_interior) = Self::get_interior_matrix::<T>(image) {
-            let amount_removed = Self::remove_boundaries(image, &is_interior);
+            let amount_removed = Self::remove_boundaries(image, editable, &is_interior);
             if amount_removed == 0 {
-                T::remove_interiors(image, &is_interior);
+                T::remove_interiors(image, editable, &is_interior);
                 ReturnStatus::CantRemoveMoreBoundaryPixels
             } else {
                 ReturnStatus::ExitCriteriaNotMet
@@ -111,10 +137,14 @@ impl EberlySkeletonizer {
         }
     }
 
-    fn remove_boundaries(image: &mut BinaryImage, is_interior: &BoolMatrix) -> usize {
+    fn remove_boundaries(
+        image: &mut BinaryImage,
+        editable: &BinaryImage,
+        is_interior: &BoolMatrix,
+    ) -> usize {
         let mut amount = 0;
         for (x, y) in image.pixels_iter() {
-            if Self::is_boundary(image, x, y, is_interior) {
+            if editable.is_fg(x, y) && Self::is_boundary(image, x, y, is_interior) {
                 image.set_bg(x, y);
                 amount += 1;
             }
@@ -245,7 +275,11 @@ impl EberlyInteriorAlgorithm for FourInteriorAlgorithm {
         }
     }
 
-    fn remove_interiors(_image: &mut BinaryImage, _is_interior: &BoolMatrix) {
+    fn remove_interiors(
+        _image: &mut BinaryImage,
+        _editable: &BinaryImage,
+        _is_interior: &BoolMatrix,
+    ) {
         // nothing to do
     }
 }
@@ -257,6 +291,7 @@ impl Default for EberlySkeletonizer {
 }
 
 #[cfg(test)]
+#[allow(clippy::bool_assert_comparison)]
 mod tests {
     use super::*;
     use crate::PixelColor;
@@ -0,0 +1,303 @@
+// guo_hall_skeletonizer.rs - Skeletonization using the Guo Hall algorithm
+// Copyright (C) 2019 Denis Karpovskiy
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use crate::binary_image::BinaryImage;
+use crate::progress::{Cancelled, Progress};
+use crate::roi::Roi;
+use crate::skeletonizers::{delete_in_parallel, neighbours, thin_with_frame, Skeletonizer};
+
+pub struct GuoHallSkeletonizer;
+
+impl Skeletonizer for GuoHallSkeletonizer {
+    fn process_with_progress<F>(
+        &self,
+        image: &mut BinaryImage,
+        roi: Option<&Roi>,
+        report_progress: F,
+    ) -> Result<(), Cancelled>
+    where
+        F: Fn(i32, i32) -> Progress,
+    {
+        thin_with_frame(image, roi, report_progress, |outer_image, editable| {
+            delete_in_parallel(outer_image, editable, |image, x, y| {
+                Self::is_deletable(image, x, y, true)
+            }) + delete_in_parallel(outer_image, editable, |image, x, y| {
+                Self::is_deletable(image, x, y, false)
+            })
+        })
+    }
+}
+
+impl GuoHallSkeletonizer {
+    pub fn new() -> Self {
+        GuoHallSkeletonizer {}
+    }
+
+    fn is_deletable(image: &BinaryImage, x: usize, y: usize, is_first: bool) -> bool {
+        // p2 is the north neighbour, the rest go clockwise
+        let [p2, p3, p4, p5, p6, p7, p8, p9] = neighbours(image, x, y);
+
+        let transitions = (!p2 && (p3 || p4)) as u32
+            + (!p4 && (p5 || p6)) as u32
+            + (!p6 && (p7 || p8)) as u32
+            + (!p8 && (p9 || p2)) as u32;
+        if transitions != 1 {
+            return false;
+        }
+
+        let n1 = (p9 || p2) as u32 + (p3 || p4) as u32 + (p5 || p6) as u32 + (p7 || p8) as u32;
+        let n2 = (p2 || p3) as u32 + (p4 || p5) as u32 + (p6 || p7) as u32 + (p8 || p9) as u32;
+        if !(2..=3).contains(&n1.min(n2)) {
+            return false;
+        }
+
+        let m = if is_first {
+            (p6 || p7 || !p9) && p8
+        } else {
+            (p2 || p3 || !p5) && p4
+        };
+
+        !m
+    }
+}
+
+impl Default for GuoHallSkeletonizer {
+    fn default() -> Self {
+        GuoHallSkeletonizer::new()
+    }
+}
+
+// The expected skeletons are the output of `thinning` with
+// `THINNING_GUOHALL` from the OpenCV ximgproc module, reproduced by
+// `opencv_thinning` below, a line by line port of it. The two sub-iterations
+// peel different sides, so symmetric shapes such as the ring get asymmetric
+// skeletons there too.
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::skeletonizers::test_shapes::*;
+
+    // `thinningIteration` and `thinning` of opencv_contrib
+    // modules/ximgproc/src/thinning.cpp. Border pixels are never removed, so
+    // the shapes need a background frame.
+    fn opencv_thinning(rows: &[&str]) -> Vec<String> {
+        let mut img: Vec<Vec<u8>> = rows
+            .iter()
+            .map(|row| row.chars().map(|c| (c == '#') as u8).collect())
+            .collect();
+        let (height, width) = (img.len(), img[0].len());
+
+        let thinning_iteration = |img: &mut Vec<Vec<u8>>, iter: u8| {
+            let mut marker = vec![vec![0u8; width]; height];
+            for i in 1..height - 1 {
+                for j in 1..width - 1 {
+                    let p2 = img[i - 1][j];
+                    let p3 = img[i - 1][j + 1];
+                    let p4 = img[i][j + 1];
+                    let p5 = img[i + 1][j + 1];
+                    let p6 = img[i + 1][j];
+                    let p7 = img[i + 1][j - 1];
+                    let p8 = img[i][j - 1];
+                    let p9 = img[i - 1][j - 1];
+
+                    let c = ((p2 ^ 1) & (p3 | p4))
+                        + ((p4 ^ 1) & (p5 | p6))
+                        + ((p6 ^ 1) & (p7 | p8))
+                        + ((p8 ^ 1) & (p9 | p2));
+                    let n1 = (p9 | p2) + (p3 | p4) + (p5 | p6) + (p7 | p8);
+                    let n2 = (p2 | p3) + (p4 | p5) + (p6 | p7) + (p8 | p9);
+                    let n = n1.min(n2);
+                    let m = if iter == 0 {
+                        (p6 | p7 | (p9 ^ 1)) & p8
+                    } else {
+                        (p2 | p3 | (p5 ^ 1)) & p4
+                    };
+
+                    if c == 1 && (2..=3).contains(&n) && m == 0 {
+                        marker[i][j] = 1;
+                    }
+                }
+            }
+
+            for (row, marker_row) in img.iter_mut().zip(marker.iter()) {
+                for (pixel, &marker) in row.iter_mut().zip(marker_row.iter()) {
+                    *pixel &= marker ^ 1;
+                }
+            }
+        };
+
+        let mut prev = vec![vec![0u8; width]; height];
+        loop {
+            thinning_iteration(&mut img, 0);
+            thinning_iteration(&mut img, 1);
+            if img == prev {
+                break;
+            }
+            prev = img.clone();
+        }
+
+        img.iter()
+            .map(|row| {
+                row.iter()
+                    .map(|&p| if p == 1 { '#' } else { '.' })
+                    .collect()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn guo_hall_matches_opencv_test() {
+        // Arrange
+        let skeletonizer = GuoHallSkeletonizer::new();
+        // framed blobs of pseudo-random pixels
+        let mut seed = 12345u32;
+        let mut shapes: Vec<Vec<String>> = Vec::new();
+        for _ in 0..20 {
+            let rows = (0..16)
+                .map(|y| {
+                    (0..16)
+                        .map(|x| {
+                            seed = seed.wrapping_mul(1_103_515_245).wrapping_add(12345);
+                            let is_inside = (1..15).contains(&x) && (1..15).contains(&y);
+                            if is_inside && (seed >> 16) & 3 != 0 {
+                                '#'
+                            } else {
+                                '.'
+                            }
+                        })
+                        .collect()
+                })
+                .collect();
+            shapes.push(rows);
+        }
+
+        for shape in &shapes {
+            let shape: Vec<&str> = shape.iter().map(|row| row.as_str()).collect();
+            let mut image = from_ascii(&shape);
+
+            // Act
+            skeletonizer.process(&mut image);
+
+            // Assert
+            assert_eq!(opencv_thinning(&shape), to_ascii(&image));
+        }
+    }
+
+    #[test]
+    fn guo_hall_rectangle_test() {
+        // Arrange
+        let skeletonizer = GuoHallSkeletonizer::new();
+        let expected = [
+            "............",
+            "............",
+            "............",
+            "...######...",
+            "............",
+            "............",
+            "............",
+        ];
+
+        // Act & Assert
+        assert_skeleton(&skeletonizer, &RECTANGLE, &expected);
+    }
+
+    #[test]
+    fn guo_hall_plus_test() {
+        // Arrange
+        let skeletonizer = GuoHallSkeletonizer::new();
+        let expected = [
+            "...........",
+            "...........",
+            ".....#.....",
+            ".....#.....",
+            ".....#.....",
+            "..#######..",
+            ".....#.....",
+            ".....#.....",
+            ".....#.....",
+            "...........",
+            "...........",
+        ];
+
+        // Act & Assert
+        assert_skeleton(&skeletonizer, &PLUS, &expected);
+    }
+
+    #[test]
+    fn guo_hall_ring_test() {
+        // Arrange
+        let skeletonizer = GuoHallSkeletonizer::new();
+        let expected = [
+            "..........",
+            "...####...",
+            "..#....#..",
+            "..#.....#.",
+            "..#.....#.",
+            "..#.....#.",
+            "..#.....#.",
+            "...#####..",
+            "..........",
+            "..........",
+        ];
+
+        // Act & Assert
+        assert_skeleton(&skeletonizer, &RING, &expected);
+    }
+
+    #[test]
+    fn guo_hall_diagonal_test() {
+        // Arrange
+        let skeletonizer = GuoHallSkeletonizer::new();
+        let expected = [
+            "............",
+            "..#.........",
+            "..#.........",
+            "...#........",
+            "....#.......",
+            ".....#......",
+            "......#.....",
+            ".......#....",
+            "........#...",
+            ".........##.",
+            "............",
+            "............",
+        ];
+
+        // Act & Assert
+        assert_skeleton(&skeletonizer, &DIAGONAL, &expected);
+    }
+
+    #[test]
+    fn guo_hall_l_shape_test() {
+        // Arrange
+        let skeletonizer = GuoHallSkeletonizer::new();
+        let expected = [
+            "..........",
+            "..........",
+            "..#.......",
+            "..#.......",
+            "..#.......",
+            "..#.......",
+            "...#......",
+            "....####..",
+            "..........",
+            "..........",
+        ];
+
+        // Act & Assert
+        assert_skeleton(&skeletonizer, &L_SHAPE, &expected);
+    }
+}
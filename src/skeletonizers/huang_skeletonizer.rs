@@ -0,0 +1,205 @@
+// huang_skeletonizer.rs - Skeletonization using the Huang-Wan-Liu algorithm
+// Copyright (C) 2019 Denis Karpovskiy
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use crate::binary_image::BinaryImage;
+use crate::progress::{Cancelled, Progress};
+use crate::roi::Roi;
+use crate::skeletonizers::{
+    count_fg_neighbours, count_transitions, delete_in_parallel, neighbours, thin_with_frame,
+    Skeletonizer,
+};
+
+pub struct HuangSkeletonizer;
+
+impl Skeletonizer for HuangSkeletonizer {
+    fn process_with_progress<F>(
+        &self,
+        image: &mut BinaryImage,
+        roi: Option<&Roi>,
+        report_progress: F,
+    ) -> Result<(), Cancelled>
+    where
+        F: Fn(i32, i32) -> Progress,
+    {
+        thin_with_frame(image, roi, report_progress, |outer_image, editable| {
+            delete_in_parallel(outer_image, editable, Self::is_deletable)
+        })
+    }
+}
+
+impl HuangSkeletonizer {
+    pub fn new() -> Self {
+        HuangSkeletonizer {}
+    }
+
+    fn is_deletable(image: &BinaryImage, x: usize, y: usize) -> bool {
+        let around = neighbours(image, x, y);
+
+        let fg_count = count_fg_neighbours(&around);
+        if !(2..=6).contains(&fg_count) || count_transitions(&around) != 1 {
+            return false;
+        }
+
+        !Self::is_preserved(image, x, y, &around)
+    }
+
+    // A single pass removes both sides of a two pixel wide line at once, so
+    // one side of it is kept.
+    fn is_preserved(image: &BinaryImage, x: usize, y: usize, around: &[bool; 8]) -> bool {
+        let [n, ne, e, se, s, sw, w, nw] = *around;
+        let (x, y) = (x as i32, y as i32);
+
+        // the west side of a vertical line
+        if e && !w && !image.is_fg_or_default(x + 2, y) {
+            return true;
+        }
+
+        // the north side of a horizontal line
+        if s && !n && !image.is_fg_or_default(x, y + 2) {
+            return true;
+        }
+
+        // the north-west pixel of a two by two square
+        e && se
+            && s
+            && !n
+            && !ne
+            && !sw
+            && !w
+            && !nw
+            && !image.is_fg_or_default(x + 2, y)
+            && !image.is_fg_or_default(x + 2, y + 1)
+            && !image.is_fg_or_default(x, y + 2)
+            && !image.is_fg_or_default(x + 1, y + 2)
+    }
+}
+
+impl Default for HuangSkeletonizer {
+    fn default() -> Self {
+        HuangSkeletonizer::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::skeletonizers::test_shapes::*;
+
+    #[test]
+    fn huang_rectangle_test() {
+        // Arrange
+        let skeletonizer = HuangSkeletonizer::new();
+        let expected = [
+            "............",
+            "............",
+            "............",
+            "...######...",
+            "............",
+            "............",
+            "............",
+        ];
+
+        // Act & Assert
+        assert_skeleton(&skeletonizer, &RECTANGLE, &expected);
+    }
+
+    #[test]
+    fn huang_plus_test() {
+        // Arrange
+        let skeletonizer = HuangSkeletonizer::new();
+        let expected = [
+            "...........",
+            "...........",
+            ".....#.....",
+            ".....#.....",
+            ".....#.....",
+            "..#######..",
+            ".....#.....",
+            ".....#.....",
+            ".....#.....",
+            "...........",
+            "...........",
+        ];
+
+        // Act & Assert
+        assert_skeleton(&skeletonizer, &PLUS, &expected);
+    }
+
+    #[test]
+    fn huang_ring_test() {
+        // Arrange
+        let skeletonizer = HuangSkeletonizer::new();
+        let expected = [
+            "..........",
+            "...####...",
+            "..#....#..",
+            ".#.....#..",
+            ".#.....#..",
+            ".#.....#..",
+            ".#.....#..",
+            "..######..",
+            "..........",
+            "..........",
+        ];
+
+        // Act & Assert
+        assert_skeleton(&skeletonizer, &RING, &expected);
+    }
+
+    #[test]
+    fn huang_diagonal_test() {
+        // Arrange
+        let skeletonizer = HuangSkeletonizer::new();
+        let expected = [
+            "............",
+            ".#..........",
+            "..#.........",
+            "...#........",
+            "....#.......",
+            ".....#......",
+            "......#.....",
+            ".......#....",
+            "........#...",
+            ".........#..",
+            "............",
+            "............",
+        ];
+
+        // Act & Assert
+        assert_skeleton(&skeletonizer, &DIAGONAL, &expected);
+    }
+
+    #[test]
+    fn huang_l_shape_test() {
+        // Arrange
+        let skeletonizer = HuangSkeletonizer::new();
+        let expected = [
+            "..........",
+            "..........",
+            "..#.......",
+            "..#.......",
+            "..#.......",
+            "..#.......",
+            "..##......",
+            "...#####..",
+            "..........",
+            "..........",
+        ];
+
+        // Act & Assert
+        assert_skeleton(&skeletonizer, &L_SHAPE, &expected);
+    }
+}
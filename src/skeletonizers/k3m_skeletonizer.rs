@@ -0,0 +1,267 @@
+// k3m_skeletonizer.rs - Skeletonization using the K3M algorithm
+// Copyright (C) 2019 Denis Karpovskiy
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use crate::binary_image::BinaryImage;
+use crate::progress::{Cancelled, Progress};
+use crate::roi::{editable_mask, Roi};
+use crate::skeletonizers::{neighbourhood_weight, neighbours, thin_with_frame, Skeletonizer};
+
+// Neighbourhood weights from the paper by Saeed, Tabedzki, Rybnik and Adamski,
+// see `neighbourhood_weight` for how they are computed. The paper marks the
+// borders and thins to one pixel width with this same array, A0 and A1pix.
+const BORDER_WEIGHTS: [u8; 48] = [
+    3, 6, 7, 12, 14, 15, 24, 28, 30, 31, 48, 56, 60, 62, 63, 96, 112, 120, 124, 126, 127, 129, 131,
+    135, 143, 159, 191, 192, 193, 195, 199, 207, 223, 224, 225, 227, 231, 239, 240, 241, 243, 247,
+    248, 249, 251, 252, 253, 254,
+];
+
+const PHASE_ONE_WEIGHTS: [u8; 8] = [7, 14, 28, 56, 112, 131, 193, 224];
+
+const PHASE_TWO_WEIGHTS: [u8; 16] = [
+    7, 14, 15, 28, 30, 56, 60, 112, 120, 131, 135, 193, 195, 224, 225, 240,
+];
+
+const PHASE_THREE_WEIGHTS: [u8; 24] = [
+    7, 14, 15, 28, 30, 31, 56, 60, 62, 112, 120, 124, 131, 135, 143, 193, 195, 199, 224, 225, 227,
+    240, 241, 248,
+];
+
+const PHASE_FOUR_WEIGHTS: [u8; 32] = [
+    7, 14, 15, 28, 30, 31, 56, 60, 62, 63, 112, 120, 124, 126, 131, 135, 143, 159, 193, 195, 199,
+    207, 224, 225, 227, 231, 240, 241, 243, 248, 249, 252,
+];
+
+const PHASE_FIVE_WEIGHTS: [u8; 36] = [
+    7, 14, 15, 28, 30, 31, 56, 60, 62, 63, 112, 120, 124, 126, 131, 135, 143, 159, 191, 193, 195,
+    199, 207, 224, 225, 227, 231, 239, 240, 241, 243, 248, 249, 251, 252, 254,
+];
+
+pub struct K3MSkeletonizer;
+
+impl Skeletonizer for K3MSkeletonizer {
+    fn process_with_progress<F>(
+        &self,
+        image: &mut BinaryImage,
+        roi: Option<&Roi>,
+        report_progress: F,
+    ) -> Result<(), Cancelled>
+    where
+        F: Fn(i32, i32) -> Progress,
+    {
+        thin_with_frame(image, roi, report_progress, Self::iteration)?;
+
+        let editable = editable_mask(roi, image.width(), image.height());
+        Self::thin_to_one_pixel(image, &editable);
+
+        Ok(())
+    }
+}
+
+impl K3MSkeletonizer {
+    pub fn new() -> Self {
+        K3MSkeletonizer {}
+    }
+
+    fn iteration(image: &mut BinaryImage, editable: &BinaryImage) -> u32 {
+        let mut borders: Vec<(usize, usize)> = Self::pixels(image, editable)
+            .into_iter()
+            .filter(|&(x, y)| Self::has_weight(image, x, y, &BORDER_WEIGHTS))
+            .collect();
+
+        let phases: [&[u8]; 5] = [
+            &PHASE_ONE_WEIGHTS,
+            &PHASE_TWO_WEIGHTS,
+            &PHASE_THREE_WEIGHTS,
+            &PHASE_FOUR_WEIGHTS,
+            &PHASE_FIVE_WEIGHTS,
+        ];
+
+        let mut count = 0;
+        for weights in phases.iter() {
+            count += Self::delete_sequentially(image, &borders, weights);
+            borders.retain(|&(x, y)| image.is_fg(x, y));
+        }
+
+        count
+    }
+
+    // Unlike the parallel algorithms every deletion is seen by the pixels
+    // checked after it.
+    fn delete_sequentially(
+        image: &mut BinaryImage,
+        pixels: &[(usize, usize)],
+        weights: &[u8],
+    ) -> u32 {
+        let mut count = 0;
+        for &(x, y) in pixels {
+            if image.is_fg(x, y) && Self::has_weight(image, x, y, weights) {
+                image.set_bg(x, y);
+                count += 1;
+            }
+        }
+
+        count
+    }
+
+    // The phases leave some two pixel wide lines, they are thinned once. The
+    // paper removes these pixels one after another, but then every removal
+    // makes the next pixel of a two pixel wide diagonal line look like its end
+    // and the whole line is gone. So unlike the paper all of them are picked
+    // on the image as it was before this pass.
+    fn thin_to_one_pixel(image: &mut BinaryImage, editable: &BinaryImage) {
+        let deletable: Vec<(usize, usize)> = Self::pixels(image, editable)
+            .into_iter()
+            .filter(|&(x, y)| Self::has_weight(image, x, y, &BORDER_WEIGHTS))
+            .collect();
+
+        for (x, y) in deletable {
+            image.set_bg(x, y);
+        }
+    }
+
+    fn has_weight(image: &BinaryImage, x: usize, y: usize, weights: &[u8]) -> bool {
+        weights.contains(&neighbourhood_weight(&neighbours(image, x, y)))
+    }
+
+    // The foreground pixels that may be removed.
+    fn pixels(image: &BinaryImage, editable: &BinaryImage) -> Vec<(usize, usize)> {
+        image
+            .pixels_iter()
+            .filter(|&(x, y)| image.is_fg(x, y) && editable.is_fg(x, y))
+            .collect()
+    }
+}
+
+impl Default for K3MSkeletonizer {
+    fn default() -> Self {
+        K3MSkeletonizer::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::skeletonizers::test_shapes::*;
+
+    #[test]
+    fn k3m_rectangle_test() {
+        // Arrange
+        let skeletonizer = K3MSkeletonizer::new();
+        // The phases go through the pixels in raster order, so the right end
+        // is peeled from below and the line steps up one pixel there.
+        let expected = [
+            "............",
+            "............",
+            "............",
+            "..#######...",
+            "........##..",
+            "............",
+            "............",
+        ];
+
+        // Act & Assert
+        assert_skeleton(&skeletonizer, &RECTANGLE, &expected);
+    }
+
+    #[test]
+    fn k3m_plus_test() {
+        // Arrange
+        let skeletonizer = K3MSkeletonizer::new();
+        let expected = [
+            "...........",
+            "...........",
+            ".....#.....",
+            ".....#.....",
+            ".....#.....",
+            ".#########.",
+            ".....#.....",
+            ".....#.....",
+            ".....#.....",
+            ".....#.....",
+            "...........",
+        ];
+
+        // Act & Assert
+        assert_skeleton(&skeletonizer, &PLUS, &expected);
+    }
+
+    #[test]
+    fn k3m_ring_test() {
+        // Arrange
+        let skeletonizer = K3MSkeletonizer::new();
+        let expected = [
+            "..........",
+            "..........",
+            "..######..",
+            "..#....#..",
+            "..#....#..",
+            "..#....#..",
+            "..#....#..",
+            "..######..",
+            "..........",
+            "..........",
+        ];
+
+        // Act & Assert
+        assert_skeleton(&skeletonizer, &RING, &expected);
+    }
+
+    #[test]
+    fn k3m_diagonal_test() {
+        // Arrange
+        let skeletonizer = K3MSkeletonizer::new();
+        // None of the paper's weights removes a corner of a staircase, so the
+        // line stays four connected.
+        let expected = [
+            "............",
+            "............",
+            "..#.........",
+            "..##........",
+            "...##.......",
+            "....##......",
+            ".....##.....",
+            "......##....",
+            ".......##...",
+            "........###.",
+            "............",
+            "............",
+        ];
+
+        // Act & Assert
+        assert_skeleton(&skeletonizer, &DIAGONAL, &expected);
+    }
+
+    #[test]
+    fn k3m_l_shape_test() {
+        // Arrange
+        let skeletonizer = K3MSkeletonizer::new();
+        let expected = [
+            "..........",
+            "..........",
+            "..#.......",
+            "..#.......",
+            "..#.......",
+            "..#.......",
+            "..#.......",
+            "..#######.",
+            "..........",
+            "..........",
+        ];
+
+        // Act & Assert
+        assert_skeleton(&skeletonizer, &L_SHAPE, &expected);
+    }
+}
@@ -0,0 +1,338 @@
+// medial_axis_skeletonizer.rs - Medial axis transform keeping the distance to the boundary
+// Copyright (C) 2019 Denis Karpovskiy
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use crate::binary_image::{BinaryImage, PixelColor};
+use crate::bool_matrix::BoolMatrix;
+use crate::distance_transform;
+use crate::grid::Grid;
+use crate::progress::{report, Cancelled, Progress};
+use crate::roi::{editable_mask, Roi};
+use crate::skeletonizers::{is_local_articulation_point, AdjacencyMode, Skeletonizer};
+use std::cmp::max;
+
+pub struct MedialAxisSkeletonizer;
+
+impl Skeletonizer for MedialAxisSkeletonizer {
+    fn process_with_progress<F>(
+        &self,
+        image: &mut BinaryImage,
+        roi: Option<&Roi>,
+        report_progress: F,
+    ) -> Result<(), Cancelled>
+    where
+        F: Fn(i32, i32) -> Progress,
+    {
+        self.process_with_distances_with_progress(image, roi, report_progress)?;
+
+        Ok(())
+    }
+}
+
+impl MedialAxisSkeletonizer {
+    pub fn new() -> Self {
+        MedialAxisSkeletonizer {}
+    }
+
+    // Thins the image and returns the Euclidean distance from every remaining
+    // pixel to the nearest background pixel, the rest of the grid is zero.
+    // Pixels outside of the image count as background.
+    pub fn process_with_distances(&self, image: &mut BinaryImage) -> Grid<f32> {
+        match self.process_with_distances_with_progress(image, None, |_, _| Progress::Continue) {
+            Ok(distances) => distances,
+            // without a callback that asks for it the run is never cancelled
+            Err(Cancelled) => Grid::new(image.width(), image.height(), 0.0),
+        }
+    }
+
+    pub fn process_with_distances_with_progress<F>(
+        &self,
+        image: &mut BinaryImage,
+        roi: Option<&Roi>,
+        report_progress: F,
+    ) -> Result<Grid<f32>, Cancelled>
+    where
+        F: Fn(i32, i32) -> Progress,
+    {
+        let distances = distance_transform::euclidean(image);
+        let editable = editable_mask(roi, image.width(), image.height());
+
+        // pixels closer to the boundary are peeled off first, so whatever is
+        // left lies on the ridge of the distance map
+        let mut pixels: Vec<(usize, usize)> = image
+            .pixels_iter()
+            .filter(|&(x, y)| image.is_fg(x, y) && editable.is_fg(x, y))
+            .collect();
+        pixels.sort_by(|&(x1, y1), &(x2, y2)| {
+            distances
+                .get(x1, y1)
+                .partial_cmp(&distances.get(x2, y2))
+                .unwrap()
+        });
+
+        let mut is_center = BoolMatrix::new(image.width(), image.height(), false);
+        for &(x, y) in &pixels {
+            if Self::is_disc_center(&distances, x, y) {
+                is_center.set(x, y);
+            }
+        }
+
+        let max_progress = Self::compute_max_progress(image.width(), image.height());
+        let mut current_progress = 0;
+
+        // thinned on a copy, so a cancelled run leaves the image as it was
+        let mut thinned = image.clone();
+
+        loop {
+            let mut pixels_changed = 0;
+            for &(x, y) in &pixels {
+                if thinned.is_fg(x, y)
+                    && (!is_center.check(x, y)
+                        || Self::has_center_twin(&thinned, &is_center, &distances, x, y))
+                    && Self::is_removable(&thinned, x, y)
+                {
+                    thinned.set_bg(x, y);
+                    pixels_changed += 1;
+                }
+            }
+
+            current_progress += 1;
+            report(&report_progress, current_progress, max_progress as i32)?;
+
+            if pixels_changed == 0 {
+                break;
+            }
+        }
+
+        report_progress(max_progress as i32, max_progress as i32);
+
+        *image = thinned;
+
+        let mut radii = Grid::new(image.width(), image.height(), 0.0);
+        for (x, y) in image.pixels_iter() {
+            if image.is_fg(x, y) {
+                radii.set(x, y, distances.get(x, y));
+            }
+        }
+
+        Ok(radii)
+    }
+
+    // Draws a disc around every pixel with a non-zero distance. For a grid
+    // returned by `process_with_distances` this gives back the original shape
+    // up to the pixels on spurs removed by the thinning.
+    pub fn reconstruct(radii: &Grid<f32>, bg_color: PixelColor) -> BinaryImage {
+        let mut image = BinaryImage::new(radii.width(), radii.height(), bg_color);
+
+        for (x, y) in radii.pixels_iter() {
+            let radius = radii.get(x, y);
+            if radius <= 0.0 {
+                continue;
+            }
+
+            let reach = radius.ceil() as i32;
+            for delta_y in -reach..=reach {
+                for delta_x in -reach..=reach {
+                    let new_x = x as i32 + delta_x;
+                    let new_y = y as i32 + delta_y;
+
+                    if ((delta_x * delta_x + delta_y * delta_y) as f32) < radius * radius
+                        && new_x >= 0
+                        && new_y >= 0
+                        && (new_x as usize) < image.width()
+                        && (new_y as usize) < image.height()
+                    {
+                        image.set_fg(new_x as usize, new_y as usize);
+                    }
+                }
+            }
+        }
+
+        image
+    }
+
+    fn compute_max_progress(width: usize, height: usize) -> usize {
+        max(width, height) / 2
+    }
+
+    // The centers of maximal discs make up the medial axis itself, a disc is
+    // not maximal if it lies inside the disc of one of the neighbours.
+    fn is_disc_center(distances: &Grid<f32>, x: usize, y: usize) -> bool {
+        let distance = distances.get(x, y);
+
+        for i in 0..9 {
+            let new_x = x + i % 3;
+            let new_y = y + i / 3;
+
+            if i == 4
+                || new_x == 0
+                || new_x > distances.width()
+                || new_y == 0
+                || new_y > distances.height()
+            {
+                continue;
+            }
+
+            let step = if i % 2 == 0 { 2.0f32.sqrt() } else { 1.0 };
+            if distances.get(new_x - 1, new_y - 1) >= distance + step - 1e-4 {
+                return false;
+            }
+        }
+
+        true
+    }
+
+    // Strokes of an even width have two rows of centers, one of them may go
+    // as long as its twin at least as far from the boundary stays.
+    fn has_center_twin(
+        image: &BinaryImage,
+        is_center: &BoolMatrix,
+        distances: &Grid<f32>,
+        x: usize,
+        y: usize,
+    ) -> bool {
+        let x_offsets = [1, 2, 1, 0];
+        let y_offsets = [0, 1, 2, 1];
+
+        for i in 0..x_offsets.len() {
+            let new_x = x + x_offsets[i];
+            let new_y = y + y_offsets[i];
+
+            if new_x != 0
+                && new_x - 1 < image.width()
+                && new_y != 0
+                && new_y - 1 < image.height()
+                && image.is_fg(new_x - 1, new_y - 1)
+                && is_center.check(new_x - 1, new_y - 1)
+                && distances.get(new_x - 1, new_y - 1) >= distances.get(x, y)
+            {
+                return true;
+            }
+        }
+
+        false
+    }
+
+    fn is_removable(image: &BinaryImage, x: usize, y: usize) -> bool {
+        let (x, y) = (x as i32, y as i32);
+
+        // end points are kept so the branches do not shrink
+        let mut neighbours = 0;
+        for i in 0..9 {
+            if i != 4 && image.is_fg_or_default(x + i % 3 - 1, y + i / 3 - 1) {
+                neighbours += 1;
+            }
+        }
+
+        if neighbours < 2 {
+            return false;
+        }
+
+        // interior pixels are kept so no holes appear
+        let is_boundary = image.is_bg_or_default(x, y - 1)
+            || image.is_bg_or_default(x + 1, y)
+            || image.is_bg_or_default(x, y + 1)
+            || image.is_bg_or_default(x - 1, y);
+
+        is_boundary
+            && !is_local_articulation_point(image, x as usize, y as usize, AdjacencyMode::Eight)
+    }
+}
+
+impl Default for MedialAxisSkeletonizer {
+    fn default() -> Self {
+        MedialAxisSkeletonizer::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::skeletonizers::count_components;
+
+    fn bar_image() -> BinaryImage {
+        let mut image = BinaryImage::new(20, 7, PixelColor::White);
+        for y in 1..6 {
+            for x in 1..19 {
+                image.set_fg(x, y);
+            }
+        }
+
+        image
+    }
+
+    #[test]
+    fn medial_axis_bar_test() {
+        // Arrange
+        let mut image = bar_image();
+        let skeletonizer = MedialAxisSkeletonizer::new();
+
+        // Act
+        let radii = skeletonizer.process_with_distances(&mut image);
+
+        // Assert
+        for x in 4..16 {
+            assert!(image.is_fg(x, 3));
+            assert_eq!(3.0, radii.get(x, 3));
+        }
+
+        for (x, y) in image.pixels_iter() {
+            if image.is_bg(x, y) {
+                assert_eq!(0.0, radii.get(x, y));
+            }
+        }
+    }
+
+    #[test]
+    fn medial_axis_l_shape_test() {
+        // Arrange
+        let mut image = BinaryImage::new(20, 12, PixelColor::White);
+        for (x, y) in image.pixels_iter() {
+            if (1..19).contains(&x) && (1..11).contains(&y) && !(x > 10 && y < 5) {
+                image.set_fg(x, y);
+            }
+        }
+        let skeletonizer = MedialAxisSkeletonizer::new();
+
+        // Act
+        let radii = skeletonizer.process_with_distances(&mut image);
+
+        // Assert
+        assert_eq!(1, count_components(&image, AdjacencyMode::Eight));
+        assert!(image.is_fg(1, 1) && image.is_fg(10, 1));
+        assert!(image.is_fg(1, 10) && image.is_fg(18, 10));
+        assert_eq!(4.0, radii.get(4, 4));
+    }
+
+    #[test]
+    fn reconstruct_test() {
+        // Arrange
+        let original = bar_image();
+        let mut image = original.clone();
+        let skeletonizer = MedialAxisSkeletonizer::new();
+        let radii = skeletonizer.process_with_distances(&mut image);
+
+        // Act
+        let reconstructed = MedialAxisSkeletonizer::reconstruct(&radii, PixelColor::White);
+
+        // Assert
+        for (x, y) in reconstructed.pixels_iter() {
+            if reconstructed.is_fg(x, y) {
+                assert!(original.is_fg(x, y));
+            }
+        }
+        assert!(reconstructed.count_fg() * 10 >= original.count_fg() * 9);
+    }
+}
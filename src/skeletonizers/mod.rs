@@ -15,26 +15,213 @@
 // along with this program.  If not, see <https://www.gnu.org/licenses/>.
 
 pub mod eberly_skeletonizer;
+pub mod guo_hall_skeletonizer;
+pub mod huang_skeletonizer;
+pub mod k3m_skeletonizer;
+pub mod medial_axis_skeletonizer;
 pub mod rosenfeld_skeletonizer;
+pub mod stentiford_skeletonizer;
 pub mod zhangsuen_skeletonizer;
 
-use crate::binary_image::BinaryImage;
-use crate::bool_matrix::BoolMatrix;
+use crate::binary_image::{BinaryImage, WORD_BITS};
+use crate::labeling::label_components;
+use crate::progress::{report, Cancelled, Progress};
+use crate::roi::{editable_mask, Roi};
+use std::cmp::max;
 
-#[derive(Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum AdjacencyMode {
     Four,
     Eight,
 }
 
+impl AdjacencyMode {
+    // The adjacency of the background that goes with this adjacency of the
+    // foreground, so that the borders between them are closed curves.
+    pub fn opposite(self) -> AdjacencyMode {
+        match self {
+            AdjacencyMode::Four => AdjacencyMode::Eight,
+            AdjacencyMode::Eight => AdjacencyMode::Four,
+        }
+    }
+}
+
 pub trait Skeletonizer {
+    // Nothing can cancel these two, so they cannot fail.
     fn process(&self, binary_image: &mut BinaryImage) {
-        self.process_with_progress(binary_image, |_, _| {});
+        let _ = self.process_with_progress(binary_image, None, |_, _| Progress::Continue);
+    }
+
+    fn process_in_roi(&self, binary_image: &mut BinaryImage, roi: &Roi) {
+        let _ = self.process_with_progress(binary_image, Some(roi), |_, _| Progress::Continue);
     }
 
-    fn process_with_progress<F>(&self, binary_image: &mut BinaryImage, report_progress: F)
+    // Only the pixels inside of `roi` are changed, the ones outside of it are
+    // kept as they are and still seen by the neighbourhood tests. The progress
+    // is reported between the iterations, answering `Progress::Cancel` stops
+    // at once and leaves the image as it was.
+    fn process_with_progress<F>(
+        &self,
+        binary_image: &mut BinaryImage,
+        roi: Option<&Roi>,
+        report_progress: F,
+    ) -> Result<(), Cancelled>
     where
-        F: Fn(i32, i32);
+        F: Fn(i32, i32) -> Progress;
+}
+
+// Runs `iteration` on a copy of the image framed by one pixel of background
+// until it stops removing pixels, so the neighbourhood of every image pixel
+// can be read without bounds checks. The iteration gets the likewise framed
+// mask of the pixels it may remove.
+fn thin_with_frame<F, I>(
+    image: &mut BinaryImage,
+    roi: Option<&Roi>,
+    report_progress: F,
+    mut iteration: I,
+) -> Result<(), Cancelled>
+where
+    F: Fn(i32, i32) -> Progress,
+    I: FnMut(&mut BinaryImage, &BinaryImage) -> u32,
+{
+    let editable = editable_mask(roi, image.width(), image.height());
+
+    let mut outer_image =
+        BinaryImage::new(image.width() + 2, image.height() + 2, image.get_bg_color());
+    let mut outer_editable =
+        BinaryImage::new(image.width() + 2, image.height() + 2, image.get_bg_color());
+    for (x, y) in image.pixels_iter() {
+        if image.is_fg(x, y) {
+            outer_image.set_fg(x + 1, y + 1);
+        }
+
+        if editable.is_fg(x, y) {
+            outer_editable.set_fg(x + 1, y + 1);
+        }
+    }
+
+    let max_progress = max(image.width(), image.height()) / 2;
+    let mut current_progress = 0;
+
+    loop {
+        let pixels_changed = iteration(&mut outer_image, &outer_editable);
+
+        current_progress += 1;
+
+        // the image itself is not touched until the end
+        report(&report_progress, current_progress, max_progress as i32)?;
+
+        if pixels_changed == 0 {
+            break;
+        }
+    }
+
+    report_progress(max_progress as i32, max_progress as i32);
+
+    for (x, y) in image.pixels_iter() {
+        if outer_image.is_fg(x + 1, y + 1) {
+            image.set_fg(x, y);
+        } else {
+            image.set_bg(x, y);
+        }
+    }
+
+    Ok(())
+}
+
+// Removes at once every editable foreground pixel of a framed image that is
+// deletable judging by the image before the removal.
+fn delete_in_parallel<D>(image: &mut BinaryImage, editable: &BinaryImage, is_deletable: D) -> u32
+where
+    D: Fn(&BinaryImage, usize, usize) -> bool,
+{
+    let mut count = 0;
+    let mut marked_pixels = Vec::new();
+
+    for y in 1..image.height() - 1 {
+        for word_x in 0..image.words_per_row() {
+            // only editable foreground pixels can be removed, so visit just
+            // the bits set in both
+            let mut word = image.get_word(word_x, y) & editable.get_word(word_x, y);
+            while word != 0 {
+                let x = word_x * WORD_BITS + word.trailing_zeros() as usize;
+                word &= word - 1;
+
+                if x == 0 || x == image.width() - 1 {
+                    continue;
+                }
+
+                if is_deletable(image, x, y) {
+                    marked_pixels.push((x, y));
+                    count += 1;
+                }
+            }
+        }
+    }
+
+    for (x, y) in marked_pixels {
+        image.set_bg(x, y);
+    }
+
+    count
+}
+
+// The eight neighbours clockwise starting from the north one, pixels outside
+// of the image count as background.
+fn neighbours(image: &BinaryImage, x: usize, y: usize) -> [bool; 8] {
+    let (x, y) = (x as i32, y as i32);
+
+    [
+        image.is_fg_or_default(x, y - 1),
+        image.is_fg_or_default(x + 1, y - 1),
+        image.is_fg_or_default(x + 1, y),
+        image.is_fg_or_default(x + 1, y + 1),
+        image.is_fg_or_default(x, y + 1),
+        image.is_fg_or_default(x - 1, y + 1),
+        image.is_fg_or_default(x - 1, y),
+        image.is_fg_or_default(x - 1, y - 1),
+    ]
+}
+
+fn count_fg_neighbours(neighbours: &[bool; 8]) -> u32 {
+    neighbours.iter().filter(|&&is_fg| is_fg).count() as u32
+}
+
+// The number of background to foreground changes going once around the pixel.
+fn count_transitions(neighbours: &[bool; 8]) -> u32 {
+    let mut count = 0;
+    for i in 0..8 {
+        if !neighbours[i] && neighbours[(i + 1) % 8] {
+            count += 1;
+        }
+    }
+
+    count
+}
+
+// The Yokoi connectivity number for the 8-adjacency, the number of foreground
+// components a pixel touches that its removal would keep apart.
+fn connectivity_number(neighbours: &[bool; 8]) -> u32 {
+    let is_bg = |i: usize| !neighbours[i % 8];
+
+    let mut count = 0;
+    for i in [0, 2, 4, 6].iter() {
+        if is_bg(*i) && !(is_bg(*i + 1) && is_bg(*i + 2)) {
+            count += 1;
+        }
+    }
+
+    count
+}
+
+// Neighbour `i` contributes the bit `1 << i`, so the north neighbour weighs 1,
+// the north-east one 2 and so on clockwise up to 128 for the north-west one.
+fn neighbourhood_weight(neighbours: &[bool; 8]) -> u8 {
+    neighbours
+        .iter()
+        .enumerate()
+        .filter(|(_, &is_fg)| is_fg)
+        .fold(0, |weight, (i, _)| weight | 1 << i)
 }
 
 fn is_local_articulation_point(
@@ -74,95 +261,243 @@ fn get_around(image: &BinaryImage, x: usize, y: usize) -> BinaryImage {
     around
 }
 
-fn count_components(image: &BinaryImage, mode: AdjacencyMode) -> u32 {
-    let mut amount = 0;
-    let mut is_checked = BoolMatrix::new(image.width(), image.height(), false);
-    let mut pixels_stack = Vec::new();
+fn count_components(image: &BinaryImage, mode: AdjacencyMode) -> usize {
+    label_components(image, mode).component_count()
+}
 
-    for (x, y) in image.pixels_iter() {
-        if !image.is_fg(x, y) || is_checked.check(x, y) {
-            continue;
+// Small shapes every skeletonizer is checked on, '#' marks the foreground.
+#[cfg(test)]
+pub mod test_shapes {
+    use super::Skeletonizer;
+    use crate::binary_image::{BinaryImage, PixelColor};
+
+    pub const RECTANGLE: [&str; 7] = [
+        "............",
+        ".##########.",
+        ".##########.",
+        ".##########.",
+        ".##########.",
+        ".##########.",
+        "............",
+    ];
+
+    pub const PLUS: [&str; 11] = [
+        "...........",
+        "....###....",
+        "....###....",
+        "....###....",
+        ".#########.",
+        ".#########.",
+        ".#########.",
+        "....###....",
+        "....###....",
+        "....###....",
+        "...........",
+    ];
+
+    pub const RING: [&str; 10] = [
+        "..........",
+        ".########.",
+        ".########.",
+        ".##....##.",
+        ".##....##.",
+        ".##....##.",
+        ".##....##.",
+        ".########.",
+        ".########.",
+        "..........",
+    ];
+
+    pub const DIAGONAL: [&str; 12] = [
+        "............",
+        ".##.........",
+        ".###........",
+        "..###.......",
+        "...###......",
+        "....###.....",
+        ".....###....",
+        "......###...",
+        ".......###..",
+        "........###.",
+        ".........##.",
+        "............",
+    ];
+
+    pub const L_SHAPE: [&str; 10] = [
+        "..........",
+        ".###......",
+        ".###......",
+        ".###......",
+        ".###......",
+        ".###......",
+        ".########.",
+        ".########.",
+        ".########.",
+        "..........",
+    ];
+
+    pub fn from_ascii(rows: &[&str]) -> BinaryImage {
+        let mut image = BinaryImage::new(rows[0].len(), rows.len(), PixelColor::White);
+        for (y, row) in rows.iter().enumerate() {
+            for (x, c) in row.chars().enumerate() {
+                if c == '#' {
+                    image.set_fg(x, y);
+                }
+            }
         }
 
-        amount += 1;
-        pixels_stack.push((x, y));
-        while !pixels_stack.is_empty() {
-            let (next_x, next_y) = pixels_stack.pop().unwrap();
+        image
+    }
 
-            is_checked.set(next_x, next_y);
+    pub fn to_ascii(image: &BinaryImage) -> Vec<String> {
+        (0..image.height())
+            .map(|y| {
+                (0..image.width())
+                    .map(|x| if image.is_fg(x, y) { '#' } else { '.' })
+                    .collect()
+            })
+            .collect()
+    }
 
-            // TODO: make this look not like shit and optimize
-            if next_x != 0
-                && image.is_fg(next_x - 1, next_y)
-                && !is_checked.check(next_x - 1, next_y)
-            {
-                pixels_stack.push((next_x - 1, next_y));
-            }
+    pub fn assert_skeleton<T: Skeletonizer>(skeletonizer: &T, shape: &[&str], expected: &[&str]) {
+        let mut image = from_ascii(shape);
 
-            if next_x != image.width() - 1
-                && image.is_fg(next_x + 1, next_y)
-                && !is_checked.check(next_x + 1, next_y)
-            {
-                pixels_stack.push((next_x + 1, next_y));
-            }
+        skeletonizer.process(&mut image);
 
-            if next_y != 0
-                && image.is_fg(next_x, next_y - 1)
-                && !is_checked.check(next_x, next_y - 1)
-            {
-                pixels_stack.push((next_x, next_y - 1));
-            }
+        assert_eq!(expected.to_vec(), to_ascii(&image));
+    }
+}
 
-            if next_y != image.height() - 1
-                && image.is_fg(next_x, next_y + 1)
-                && !is_checked.check(next_x, next_y + 1)
-            {
-                pixels_stack.push((next_x, next_y + 1));
-            }
+#[cfg(test)]
+#[allow(clippy::bool_assert_comparison, clippy::nonminimal_bool)]
+mod tests {
+    use super::test_shapes::{self, from_ascii, to_ascii};
+    use super::*;
+    use crate::{
+        label_components, BoundingBox, EberlySkeletonizer, GuoHallSkeletonizer, HuangSkeletonizer,
+        K3MSkeletonizer, MedialAxisSkeletonizer, PixelColor, RosenfeldSkeletonizer,
+        StentifordSkeletonizer, ZhangSuenSkeletonizer,
+    };
+    use std::cell::Cell;
 
-            if mode == AdjacencyMode::Eight {
-                if next_x != 0
-                    && next_y != 0
-                    && image.is_fg(next_x - 1, next_y - 1)
-                    && !is_checked.check(next_x - 1, next_y - 1)
-                {
-                    pixels_stack.push((next_x - 1, next_y - 1));
-                }
+    #[test]
+    fn count_transitions_borders_test() {
+        // Arrange
+        let mut image = BinaryImage::new(1, 1, PixelColor::White);
+        image.set_fg(0, 0);
 
-                if next_x != image.width() - 1
-                    && next_y != 0
-                    && image.is_fg(next_x + 1, next_y - 1)
-                    && !is_checked.check(next_x + 1, next_y - 1)
-                {
-                    pixels_stack.push((next_x + 1, next_y - 1));
-                }
+        // Act
+        let count = count_transitions(&neighbours(&image, 0, 0));
 
-                if next_x != 0
-                    && next_y != image.height() - 1
-                    && image.is_fg(next_x - 1, next_y + 1)
-                    && !is_checked.check(next_x - 1, next_y + 1)
-                {
-                    pixels_stack.push((next_x - 1, next_y + 1));
-                }
+        // Assert
+        assert_eq!(0, count);
+    }
 
-                if next_x != image.width() - 1
-                    && next_y != image.height() - 1
-                    && image.is_fg(next_x + 1, next_y + 1)
-                    && !is_checked.check(next_x + 1, next_y + 1)
-                {
-                    pixels_stack.push((next_x + 1, next_y + 1));
-                }
-            }
-        }
+    #[test]
+    fn count_transitions_zero_black_test() {
+        // Arrange
+        let mut image = BinaryImage::new(3, 3, PixelColor::White);
+        image.fill(PixelColor::Black);
+
+        // Act
+        let count = count_transitions(&neighbours(&image, 1, 1));
+
+        // Assert
+        assert_eq!(0, count);
     }
 
-    amount
-}
+    #[test]
+    fn count_transitions_zero_white_test() {
+        // Arrange
+        let mut image = BinaryImage::new(3, 3, PixelColor::White);
+        image.set_fg(1, 1);
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use crate::PixelColor;
+        // Act
+        let count = count_transitions(&neighbours(&image, 1, 1));
+
+        // Assert
+        assert_eq!(0, count);
+    }
+
+    #[test]
+    fn count_transitions_one_test() {
+        // Arrange
+        let mut image = BinaryImage::new(3, 3, PixelColor::White);
+        image.fill(PixelColor::Black);
+        image.set_bg(0, 0);
+
+        // Act
+        let count = count_transitions(&neighbours(&image, 1, 1));
+
+        // Assert
+        assert_eq!(1, count);
+    }
+
+    #[test]
+    fn count_transitions_four_test() {
+        // Arrange
+        let mut image = BinaryImage::new(3, 3, PixelColor::White);
+        image.fill(PixelColor::Black);
+        image.set_bg(0, 0);
+        image.set_bg(2, 0);
+        image.set_bg(0, 2);
+        image.set_bg(2, 2);
+
+        // Act
+        let count = count_transitions(&neighbours(&image, 1, 1));
+
+        // Assert
+        assert_eq!(4, count);
+    }
+
+    #[test]
+    fn count_fg_neighbours_test() {
+        // Arrange
+        let mut image = BinaryImage::new(3, 3, PixelColor::White);
+        image.set_fg(0, 0);
+        image.set_fg(2, 0);
+        image.set_fg(0, 2);
+        image.set_fg(2, 1);
+
+        // Act
+        let count = count_fg_neighbours(&neighbours(&image, 1, 1));
+
+        // Assert
+        assert_eq!(4, count);
+    }
+
+    #[test]
+    fn connectivity_number_test() {
+        // Arrange
+        let mut end_point = BinaryImage::new(3, 3, PixelColor::White);
+        end_point.set_fg(1, 1);
+        end_point.set_fg(1, 0);
+        let mut bridge = end_point.clone();
+        bridge.set_fg(1, 2);
+        let mut branch = end_point.clone();
+        branch.set_fg(0, 2);
+        branch.set_fg(2, 2);
+
+        // Act & Assert
+        assert_eq!(1, connectivity_number(&neighbours(&end_point, 1, 1)));
+        assert_eq!(2, connectivity_number(&neighbours(&bridge, 1, 1)));
+        assert_eq!(3, connectivity_number(&neighbours(&branch, 1, 1)));
+    }
+
+    #[test]
+    fn neighbourhood_weight_test() {
+        // Arrange
+        let mut image = BinaryImage::new(3, 3, PixelColor::White);
+        image.set_fg(1, 0);
+        image.set_fg(2, 1);
+        image.set_fg(0, 0);
+
+        // Act
+        let weight = neighbourhood_weight(&neighbours(&image, 1, 1));
+
+        // Assert
+        assert_eq!(1 + 4 + 128, weight);
+    }
 
     #[test]
     fn count_components_three_modefour_test() {
@@ -313,4 +648,129 @@ mod tests {
             is_local_articulation_point(&image, 1, 1, AdjacencyMode::Eight)
         );
     }
+
+    fn assert_roi_is_kept<T: Skeletonizer>(skeletonizer: &T) {
+        let shape = [
+            "..............",
+            ".############.",
+            ".############.",
+            ".############.",
+            ".############.",
+            ".############.",
+            "..............",
+        ];
+        let roi = Roi::Rect(BoundingBox::new(6, 0, 8, 7));
+        let mut image = from_ascii(&shape);
+
+        skeletonizer.process_in_roi(&mut image, &roi);
+
+        let original = from_ascii(&shape);
+        for (x, y) in image.pixels_iter() {
+            if !roi.contains(x, y) {
+                assert_eq!(original.is_fg(x, y), image.is_fg(x, y));
+            }
+        }
+        assert!(image.count_fg() < original.count_fg());
+        assert_eq!(
+            1,
+            label_components(&image, AdjacencyMode::Eight).component_count()
+        );
+    }
+
+    #[test]
+    fn process_in_roi_keeps_outside_test() {
+        assert_roi_is_kept(&EberlySkeletonizer::new());
+        assert_roi_is_kept(&GuoHallSkeletonizer::new());
+        assert_roi_is_kept(&HuangSkeletonizer::new());
+        assert_roi_is_kept(&K3MSkeletonizer::new());
+        assert_roi_is_kept(&MedialAxisSkeletonizer::new());
+        assert_roi_is_kept(&RosenfeldSkeletonizer::new(AdjacencyMode::Eight));
+        assert_roi_is_kept(&StentifordSkeletonizer::new());
+        assert_roi_is_kept(&ZhangSuenSkeletonizer::new());
+    }
+
+    fn assert_cancel_keeps_image<T: Skeletonizer>(skeletonizer: &T) {
+        let shape = [
+            "..............",
+            ".############.",
+            ".############.",
+            ".############.",
+            ".############.",
+            ".############.",
+            "..............",
+        ];
+        let mut image = from_ascii(&shape);
+        let reports = Cell::new(0);
+
+        let result = skeletonizer.process_with_progress(&mut image, None, |_, _| {
+            reports.set(reports.get() + 1);
+            Progress::Cancel
+        });
+
+        assert_eq!(Err(Cancelled), result);
+        assert_eq!(1, reports.get());
+        assert_eq!(shape.to_vec(), to_ascii(&image));
+    }
+
+    #[test]
+    fn process_with_progress_cancel_test() {
+        assert_cancel_keeps_image(&EberlySkeletonizer::new());
+        assert_cancel_keeps_image(&GuoHallSkeletonizer::new());
+        assert_cancel_keeps_image(&HuangSkeletonizer::new());
+        assert_cancel_keeps_image(&K3MSkeletonizer::new());
+        assert_cancel_keeps_image(&MedialAxisSkeletonizer::new());
+        assert_cancel_keeps_image(&RosenfeldSkeletonizer::new(AdjacencyMode::Eight));
+        assert_cancel_keeps_image(&StentifordSkeletonizer::new());
+        assert_cancel_keeps_image(&ZhangSuenSkeletonizer::new());
+    }
+
+    #[test]
+    fn process_in_roi_test() {
+        // Arrange
+        let mut image = from_ascii(&[
+            "..............",
+            ".############.",
+            ".############.",
+            ".############.",
+            ".############.",
+            ".############.",
+            "..............",
+        ]);
+        let mut mask = BinaryImage::new(14, 7, PixelColor::White);
+        for y in 0..7 {
+            for x in 6..14 {
+                mask.set_fg(x, y);
+            }
+        }
+
+        // Act
+        ZhangSuenSkeletonizer::new().process_in_roi(&mut image, &Roi::Mask(mask));
+
+        // Assert
+        let expected = [
+            "..............", //
+            ".#####........", //
+            ".#####........", //
+            ".#########....", //
+            ".#####........", //
+            ".#####........", //
+            "..............", //
+        ];
+        assert_eq!(expected.to_vec(), to_ascii(&image));
+    }
+
+    #[test]
+    fn process_in_whole_roi_test() {
+        // Arrange
+        let mut image = from_ascii(&test_shapes::PLUS);
+        let mut expected = from_ascii(&test_shapes::PLUS);
+        let roi = Roi::Rect(BoundingBox::new(0, 0, image.width(), image.height()));
+
+        // Act
+        GuoHallSkeletonizer::new().process_in_roi(&mut image, &roi);
+        GuoHallSkeletonizer::new().process(&mut expected);
+
+        // Assert
+        assert_eq!(to_ascii(&expected), to_ascii(&image));
+    }
 }
@@ -16,6 +16,8 @@
 
 use crate::binary_image::BinaryImage;
 use crate::bool_matrix::BoolMatrix;
+use crate::progress::{report, Cancelled, Progress};
+use crate::roi::{editable_mask, Roi};
 use crate::skeletonizers::{is_local_articulation_point, AdjacencyMode, Skeletonizer};
 use std::cmp::max;
 
@@ -32,9 +34,14 @@ pub struct RosenfeldSkeletonizer {
 }
 
 impl Skeletonizer for RosenfeldSkeletonizer {
-    fn process_with_progress<F>(&self, image: &mut BinaryImage, report_progress: F)
+    fn process_with_progress<F>(
+        &self,
+        image: &mut BinaryImage,
+        roi: Option<&Roi>,
+        report_progress: F,
+    ) -> Result<(), Cancelled>
     where
-        F: Fn(i32, i32),
+        F: Fn(i32, i32) -> Progress,
     {
         let mut current_progress = 0;
         let max_progress = Self::compute_max_progress(image.width(), image.height());
@@ -46,15 +53,20 @@ impl Skeletonizer for RosenfeldSkeletonizer {
             ProcessingSide::East,
         ];
 
+        let editable = editable_mask(roi, image.width(), image.height());
+
+        // thinned on a copy, so a cancelled run leaves the image as it was
+        let mut thinned = image.clone();
+
         loop {
             let mut x = 0;
             for side in &sides {
-                x += self.process_side(image, side);
+                x += self.process_side(&mut thinned, &editable, side);
             }
 
             current_progress += 1;
 
-            report_progress(current_progress, max_progress as i32);
+            report(&report_progress, current_progress, max_progress as i32)?;
 
             if x == 0 {
                 break;
@@ -62,6 +74,10 @@ impl Skeletonizer for RosenfeldSkeletonizer {
         }
 
         report_progress(max_progress as i32, max_progress as i32);
+
+        *image = thinned;
+
+        Ok(())
     }
 }
 
@@ -74,13 +90,18 @@ impl RosenfeldSkeletonizer {
         max(width, height) / 2
     }
 
-    fn process_side(&self, image: &mut BinaryImage, side: &ProcessingSide) -> usize {
+    fn process_side(
+        &self,
+        image: &mut BinaryImage,
+        editable: &BinaryImage,
+        side: &ProcessingSide,
+    ) -> usize {
         let mut amount = 0;
 
         let mut is_deleted = BoolMatrix::new(image.width(), image.height(), false);
 
         for (x, y) in image.pixels_iter() {
-            if image.is_bg(x, y) {
+            if image.is_bg(x, y) || editable.is_bg(x, y) {
                 continue;
             }
 
@@ -0,0 +1,195 @@
+// stentiford_skeletonizer.rs - Skeletonization using the Stentiford algorithm
+// Copyright (C) 2019 Denis Karpovskiy
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use crate::binary_image::BinaryImage;
+use crate::progress::{Cancelled, Progress};
+use crate::roi::Roi;
+use crate::skeletonizers::{
+    connectivity_number, count_fg_neighbours, delete_in_parallel, neighbours, thin_with_frame,
+    Skeletonizer,
+};
+
+// Indices into the neighbours going clockwise from the north one
+const NORTH: usize = 0;
+const EAST: usize = 2;
+const SOUTH: usize = 4;
+const WEST: usize = 6;
+
+// Each template is a (background, foreground) pair of neighbours, they peel
+// the top, the left, the bottom and the right sides of the shape in turn.
+const TEMPLATES: [(usize, usize); 4] = [(NORTH, SOUTH), (WEST, EAST), (SOUTH, NORTH), (EAST, WEST)];
+
+pub struct StentifordSkeletonizer;
+
+impl Skeletonizer for StentifordSkeletonizer {
+    fn process_with_progress<F>(
+        &self,
+        image: &mut BinaryImage,
+        roi: Option<&Roi>,
+        report_progress: F,
+    ) -> Result<(), Cancelled>
+    where
+        F: Fn(i32, i32) -> Progress,
+    {
+        thin_with_frame(image, roi, report_progress, |outer_image, editable| {
+            TEMPLATES
+                .iter()
+                .map(|&template| {
+                    delete_in_parallel(outer_image, editable, |image, x, y| {
+                        Self::is_deletable(image, x, y, template)
+                    })
+                })
+                .sum()
+        })
+    }
+}
+
+impl StentifordSkeletonizer {
+    pub fn new() -> Self {
+        StentifordSkeletonizer {}
+    }
+
+    fn is_deletable(
+        image: &BinaryImage,
+        x: usize,
+        y: usize,
+        (bg_neighbour, fg_neighbour): (usize, usize),
+    ) -> bool {
+        let around = neighbours(image, x, y);
+
+        !around[bg_neighbour]
+            && around[fg_neighbour]
+            && count_fg_neighbours(&around) > 1
+            && connectivity_number(&around) == 1
+    }
+}
+
+impl Default for StentifordSkeletonizer {
+    fn default() -> Self {
+        StentifordSkeletonizer::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::skeletonizers::test_shapes::*;
+
+    #[test]
+    fn stentiford_rectangle_test() {
+        // Arrange
+        let skeletonizer = StentifordSkeletonizer::new();
+        let expected = [
+            "............",
+            "............",
+            "............",
+            "...#######..",
+            "............",
+            "............",
+            "............",
+        ];
+
+        // Act & Assert
+        assert_skeleton(&skeletonizer, &RECTANGLE, &expected);
+    }
+
+    #[test]
+    fn stentiford_plus_test() {
+        // Arrange
+        let skeletonizer = StentifordSkeletonizer::new();
+        let expected = [
+            "...........",
+            "...........",
+            ".....#.....",
+            ".....#.....",
+            ".....#.....",
+            "..########.",
+            ".....#.....",
+            ".....#.....",
+            ".....#.....",
+            "...........",
+            "...........",
+        ];
+
+        // Act & Assert
+        assert_skeleton(&skeletonizer, &PLUS, &expected);
+    }
+
+    #[test]
+    fn stentiford_ring_test() {
+        // Arrange
+        let skeletonizer = StentifordSkeletonizer::new();
+        let expected = [
+            "..........",
+            "..........",
+            "...#####..",
+            "..#.....#.",
+            "..#.....#.",
+            "..#.....#.",
+            "..#.....#.",
+            "..#.....#.",
+            "...#####..",
+            "..........",
+        ];
+
+        // Act & Assert
+        assert_skeleton(&skeletonizer, &RING, &expected);
+    }
+
+    #[test]
+    fn stentiford_diagonal_test() {
+        // Arrange
+        let skeletonizer = StentifordSkeletonizer::new();
+        let expected = [
+            "............",
+            "............",
+            "..#.........",
+            "...#........",
+            "....#.......",
+            ".....#......",
+            "......#.....",
+            ".......#....",
+            "........#...",
+            ".........#..",
+            "..........#.",
+            "............",
+        ];
+
+        // Act & Assert
+        assert_skeleton(&skeletonizer, &DIAGONAL, &expected);
+    }
+
+    #[test]
+    fn stentiford_l_shape_test() {
+        // Arrange
+        let skeletonizer = StentifordSkeletonizer::new();
+        let expected = [
+            "..........",
+            "..........",
+            "..#.......",
+            "..#.......",
+            "..#.......",
+            "..#.......",
+            "..#.......",
+            "...######.",
+            "..........",
+            "..........",
+        ];
+
+        // Act & Assert
+        assert_skeleton(&skeletonizer, &L_SHAPE, &expected);
+    }
+}
@@ -15,51 +15,29 @@
 // along with this program.  If not, see <https://www.gnu.org/licenses/>.
 
 use crate::binary_image::BinaryImage;
-use crate::skeletonizers::Skeletonizer;
-use std::cmp::max;
+use crate::progress::{Cancelled, Progress};
+use crate::roi::Roi;
+use crate::skeletonizers::{
+    count_fg_neighbours, count_transitions, delete_in_parallel, neighbours, thin_with_frame,
+    Skeletonizer,
+};
 
 pub struct ZhangSuenSkeletonizer;
 
 impl Skeletonizer for ZhangSuenSkeletonizer {
-    fn process_with_progress<F>(&self, image: &mut BinaryImage, report_progress: F)
+    fn process_with_progress<F>(
+        &self,
+        image: &mut BinaryImage,
+        roi: Option<&Roi>,
+        report_progress: F,
+    ) -> Result<(), Cancelled>
     where
-        F: Fn(i32, i32),
+        F: Fn(i32, i32) -> Progress,
     {
-        let mut outer_image =
-            BinaryImage::new(image.width() + 2, image.height() + 2, image.get_bg_color());
-        for (x, y) in image.pixels_iter() {
-            if image.is_fg(x, y) {
-                outer_image.set_fg(x + 1, y + 1);
-            }
-        }
-
-        let max_progress = Self::compute_max_progress(image.width(), image.height());
-        let mut current_progress = 0;
-
-        loop {
-            let mut pixels_changed = 0;
-
-            pixels_changed += ZhangSuenSkeletonizer::step_one(&mut outer_image);
-            pixels_changed += ZhangSuenSkeletonizer::step_two(&mut outer_image);
-
-            current_progress += 1;
-
-            report_progress(current_progress, max_progress as i32);
-
-            if pixels_changed == 0 {
-                break;
-            }
-        }
-
-        report_progress(max_progress as i32, max_progress as i32);
-
-        for (x, y) in image.pixels_iter() {
-            if outer_image.is_fg(x + 1, y + 1) {
-                image.set_fg(x, y);
-            } else {
-                image.set_bg(x, y);
-            }
-        }
+        thin_with_frame(image, roi, report_progress, |outer_image, editable| {
+            ZhangSuenSkeletonizer::step_one(outer_image, editable)
+                + ZhangSuenSkeletonizer::step_two(outer_image, editable)
+        })
     }
 }
 
@@ -68,111 +46,41 @@ impl ZhangSuenSkeletonizer {
         ZhangSuenSkeletonizer {}
     }
 
-    fn compute_max_progress(width: usize, height: usize) -> usize {
-        max(width, height) / 2
-    }
-
-    fn step<F>(image: &mut BinaryImage, check_around: F) -> u32
+    fn step<F>(image: &mut BinaryImage, editable: &BinaryImage, check_around: F) -> u32
     where
         F: Fn(&BinaryImage, usize, usize) -> bool,
     {
-        let mut count = 0;
-        let mut marked_pixels = Vec::new();
-
-        for y in 1..image.height() - 1 {
-            for x in 1..image.width() - 1 {
-                if image.is_bg(x, y) {
-                    continue;
-                }
-
-                let black_count = ZhangSuenSkeletonizer::count_black_neighbours(image, x, y);
-                if black_count < 2 || black_count > 6 {
-                    continue;
-                }
-
-                if ZhangSuenSkeletonizer::count_transitions(image, x, y) != 1 {
-                    continue;
-                }
+        delete_in_parallel(image, editable, |image, x, y| {
+            let neighbours = neighbours(image, x, y);
 
-                if check_around(image, x, y) {
-                    continue;
-                }
-
-                marked_pixels.push((x, y));
-                count += 1;
+            let black_count = count_fg_neighbours(&neighbours);
+            if !(2..=6).contains(&black_count) {
+                return false;
             }
-        }
 
-        for (x, y) in marked_pixels {
-            image.set_bg(x, y);
-        }
+            if count_transitions(&neighbours) != 1 {
+                return false;
+            }
 
-        count
+            !check_around(image, x, y)
+        })
     }
 
-    fn step_one(image: &mut BinaryImage) -> u32 {
-        Self::step(image, |image, x, y| -> bool {
+    fn step_one(image: &mut BinaryImage, editable: &BinaryImage) -> u32 {
+        Self::step(image, editable, |image, x, y| -> bool {
             image.is_fg(x, y + 1)
                 && image.is_fg(x + 1, y)
                 && (image.is_fg(x, y - 1) || image.is_fg(x - 1, y))
         })
     }
 
-    fn step_two(image: &mut BinaryImage) -> u32 {
-        Self::step(image, |image, x, y| {
+    fn step_two(image: &mut BinaryImage, editable: &BinaryImage) -> u32 {
+        Self::step(image, editable, |image, x, y| {
             image.is_fg(x, y - 1)
                 && image.is_fg(x - 1, y)
                 && (image.is_fg(x, y + 1) || image.is_fg(x + 1, y))
         })
     }
-
-    fn count_black_neighbours(image: &BinaryImage, mut x: usize, mut y: usize) -> u32 {
-        let mut count = 0;
-        x -= 1;
-        y -= 1;
-        let delta_x = [1, 2, 2, 2, 1, 0, 0, 0];
-        let delta_y = [0, 0, 1, 2, 2, 2, 1, 0];
-
-        for i in 0..8 {
-            if image.is_fg(x + delta_x[i], y + delta_y[i]) {
-                count += 1;
-            }
-        }
-
-        count
-    }
-
-    fn count_transitions(image: &BinaryImage, x: usize, y: usize) -> u32 {
-        let delta_x = [2, 2, 2, 1, 0, 0, 0, 1];
-        let delta_y = [0, 1, 2, 2, 2, 1, 0, 0];
-
-        let mut count = 0;
-        let mut is_previous_bg = if y == 0 || y > image.height() || x > image.height() - 1 {
-            true
-        } else {
-            image.is_bg(x, y - 1)
-        };
-
-        for i in 0..8 {
-            let new_x = x + delta_x[i];
-            let new_y = y + delta_y[i];
-
-            let is_current_bg =
-                if new_x == 0 || new_x > image.width() || new_y == 0 || new_y > image.height() {
-                    true
-                } else {
-                    image.is_bg(new_x - 1, new_y - 1)
-                };
-
-            if is_previous_bg && !is_current_bg {
-                count += 1;
-            }
-
-            is_previous_bg = is_current_bg;
-        }
-
-        count
-    }
 }
 
 impl Default for ZhangSuenSkeletonizer {
@@ -207,88 +115,25 @@ mod tests {
     }
 
     #[test]
-    fn count_transitions_borders_test() {
-        // Arrange
-        let mut image = BinaryImage::new(1, 1, PixelColor::White);
-        image.set_fg(0, 0);
-
-        // Act
-        let count = ZhangSuenSkeletonizer::count_transitions(&image, 0, 0);
-
-        // Assert
-        assert_eq!(0, count);
-    }
-
-    #[test]
-    fn count_transitions_zero_black_test() {
+    fn zhangsuen_wide_bar_test() {
         // Arrange
-        let mut image = BinaryImage::new(3, 3, PixelColor::White);
-        image.fill(PixelColor::Black);
-
-        // Act
-        let count = ZhangSuenSkeletonizer::count_transitions(&image, 1, 1);
-
-        // Assert
-        assert_eq!(0, count);
-    }
-
-    #[test]
-    fn count_transitions_zero_white_test() {
-        // Arrange
-        let mut image = BinaryImage::new(3, 3, PixelColor::White);
-        image.set_fg(1, 1);
-
-        // Act
-        let count = ZhangSuenSkeletonizer::count_transitions(&image, 1, 1);
-
-        // Assert
-        assert_eq!(0, count);
-    }
-
-    #[test]
-    fn count_transitions_one_test() {
-        // Arrange
-        let mut image = BinaryImage::new(3, 3, PixelColor::White);
-        image.fill(PixelColor::Black);
-        image.set_bg(0, 0);
-
-        // Act
-        let count = ZhangSuenSkeletonizer::count_transitions(&image, 1, 1);
-
-        // Assert
-        assert_eq!(1, count);
-    }
-
-    #[test]
-    fn count_transitions_four_test() {
-        // Arrange
-        let mut image = BinaryImage::new(3, 3, PixelColor::White);
-        image.fill(PixelColor::Black);
-        image.set_bg(0, 0);
-        image.set_bg(2, 0);
-        image.set_bg(0, 2);
-        image.set_bg(2, 2);
-
-        // Act
-        let count = ZhangSuenSkeletonizer::count_transitions(&image, 1, 1);
-
-        // Assert
-        assert_eq!(4, count);
-    }
-
-    #[test]
-    fn count_black_neighbours_test() {
-        // Arrange
-        let mut image = BinaryImage::new(3, 3, PixelColor::White);
-        image.set_fg(0, 0);
-        image.set_fg(2, 0);
-        image.set_fg(0, 2);
-        image.set_fg(2, 1);
+        let mut image = BinaryImage::new(100, 5, PixelColor::White);
+        for x in 0..100 {
+            for y in 1..4 {
+                image.set_fg(x, y);
+            }
+        }
+        let skeletonizer = ZhangSuenSkeletonizer::new();
 
         // Act
-        let count = ZhangSuenSkeletonizer::count_black_neighbours(&image, 1, 1);
+        skeletonizer.process(&mut image);
 
         // Assert
-        assert_eq!(4, count);
+        for (x, y) in image.pixels_iter() {
+            if image.is_fg(x, y) {
+                assert!(y == 2 || y == 3);
+            }
+        }
+        assert!(image.count_fg() > 90);
     }
 }
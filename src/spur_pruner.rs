@@ -0,0 +1,351 @@
+// spur_pruner.rs - Removal of short end branches from skeletons
+// Copyright (C) 2019 Denis Karpovskiy
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use crate::binary_image::BinaryImage;
+use crate::distance_transform;
+use crate::grid::Grid;
+use crate::progress::{Cancelled, Progress};
+use crate::roi::Roi;
+use crate::skeleton_graph::{Node, NodeKind, SkeletonGraph};
+use crate::skeletonizers::{AdjacencyMode, Skeletonizer};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SpurLength {
+    Pixels(f64),
+    // A fraction of the stroke width at the junction the spur grows from
+    StrokeWidth(f64),
+}
+
+// Removes the branches that go from a junction to an endpoint and are shorter
+// than the maximum length. Every junction keeps two of its branches, so the
+// skeleton is never split and never shrinks to a point.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SpurPruner {
+    max_length: SpurLength,
+    mode: AdjacencyMode,
+    is_iterative: bool,
+}
+
+impl SpurPruner {
+    // An iterative pruner repeats until nothing is removed, so the branches
+    // that become spurs after their twigs are gone are pruned as well.
+    pub fn new(max_length: SpurLength, mode: AdjacencyMode, is_iterative: bool) -> Self {
+        SpurPruner {
+            max_length,
+            mode,
+            is_iterative,
+        }
+    }
+
+    // The stroke widths are measured on `shape`, the image before the
+    // skeletonization, or on the skeleton itself when there is none. Returns
+    // the number of removed branches.
+    pub fn prune(&self, skeleton: &mut BinaryImage, shape: Option<&BinaryImage>) -> usize {
+        let distances = match self.max_length {
+            SpurLength::Pixels(_) => None,
+            SpurLength::StrokeWidth(_) => {
+                Some(distance_transform::euclidean(shape.unwrap_or(skeleton)))
+            }
+        };
+
+        let mut count = 0;
+        loop {
+            let removed = self.prune_once(skeleton, distances.as_ref());
+            count += removed;
+
+            if removed == 0 || !self.is_iterative {
+                break;
+            }
+        }
+
+        count
+    }
+
+    fn prune_once(&self, skeleton: &mut BinaryImage, distances: Option<&Grid<f32>>) -> usize {
+        let graph = SkeletonGraph::new(skeleton, self.mode);
+
+        let mut spurs = vec![Vec::new(); graph.node_count()];
+        for (i, edge) in graph.edges().enumerate() {
+            let start = graph.node(edge.start()).kind();
+            let end = graph.node(edge.end()).kind();
+            let junction = match (start, end) {
+                (NodeKind::Endpoint, NodeKind::Junction) => edge.end(),
+                (NodeKind::Junction, NodeKind::Endpoint) => edge.start(),
+                _ => continue,
+            };
+
+            if edge.length() < self.max_length_at(graph.node(junction), distances) {
+                spurs[junction].push(i);
+            }
+        }
+
+        let mut count = 0;
+        for (junction, mut candidates) in spurs.into_iter().enumerate() {
+            let junction = graph.node(junction);
+            let removable = junction.degree().saturating_sub(2);
+
+            candidates.sort_by(|&a, &b| {
+                let a = graph.edge(a).length();
+                let b = graph.edge(b).length();
+                a.partial_cmp(&b).unwrap()
+            });
+
+            for &i in candidates.iter().take(removable) {
+                for &(x, y) in graph.edge(i).pixels() {
+                    if !junction.pixels().contains(&(x, y)) {
+                        skeleton.set_bg(x, y);
+                    }
+                }
+                count += 1;
+            }
+        }
+
+        count
+    }
+
+    fn max_length_at(&self, junction: &Node, distances: Option<&Grid<f32>>) -> f64 {
+        match (self.max_length, distances) {
+            (SpurLength::StrokeWidth(fraction), Some(distances)) => {
+                // a one pixel wide line is one pixel away from the background
+                let distance = distances.get(junction.x(), junction.y()) as f64;
+                fraction * (2.0 * distance - 1.0).max(1.0)
+            }
+            (SpurLength::StrokeWidth(fraction), None) => fraction,
+            (SpurLength::Pixels(length), _) => length,
+        }
+    }
+}
+
+// Runs a skeletonizer and prunes its output, the stroke widths come from the
+// image before the skeletonization.
+pub struct PrunedSkeletonizer<S: Skeletonizer> {
+    skeletonizer: S,
+    pruner: SpurPruner,
+}
+
+impl<S: Skeletonizer> PrunedSkeletonizer<S> {
+    pub fn new(skeletonizer: S, pruner: SpurPruner) -> Self {
+        PrunedSkeletonizer {
+            skeletonizer,
+            pruner,
+        }
+    }
+}
+
+impl<S: Skeletonizer> Skeletonizer for PrunedSkeletonizer<S> {
+    fn process_with_progress<F>(
+        &self,
+        image: &mut BinaryImage,
+        roi: Option<&Roi>,
+        report_progress: F,
+    ) -> Result<(), Cancelled>
+    where
+        F: Fn(i32, i32) -> Progress,
+    {
+        let shape = image.clone();
+
+        self.skeletonizer
+            .process_with_progress(image, roi, report_progress)?;
+
+        self.pruner.prune(image, Some(&shape));
+
+        // spurs may run out of the region, the pixels outside of it are
+        // put back as they were
+        if let Some(roi) = roi {
+            let editable = roi.to_mask(image.width(), image.height());
+            for y in 0..image.height() {
+                for word_x in 0..image.words_per_row() {
+                    let is_editable = editable.get_word(word_x, y);
+                    let word = (image.get_word(word_x, y) & is_editable)
+                        | (shape.get_word(word_x, y) & !is_editable);
+                    image.set_word(word_x, y, word);
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::skeletonizers::test_shapes::{from_ascii, to_ascii};
+    use crate::ZhangSuenSkeletonizer;
+
+    #[test]
+    fn spur_pruner_short_spur_test() {
+        // Arrange
+        let mut image = from_ascii(&[
+            "....#.......", //
+            "....#.......", //
+            "############", //
+            ".......#....", //
+            ".......#....", //
+            ".......#....", //
+            ".......#....", //
+        ]);
+        let pruner = SpurPruner::new(SpurLength::Pixels(3.0), AdjacencyMode::Eight, false);
+
+        // Act
+        let count = pruner.prune(&mut image, None);
+
+        // Assert
+        assert_eq!(1, count);
+        assert_eq!(
+            vec![
+                "............",
+                "............",
+                "############",
+                ".......#....",
+                ".......#....",
+                ".......#....",
+                ".......#....",
+            ],
+            to_ascii(&image)
+        );
+    }
+
+    #[test]
+    fn spur_pruner_keeps_two_branches_test() {
+        // Arrange
+        let mut image = from_ascii(&[
+            "#...#", //
+            ".#.#.", //
+            "..#..", //
+            "..#..", //
+        ]);
+        let pruner = SpurPruner::new(SpurLength::Pixels(10.0), AdjacencyMode::Eight, true);
+
+        // Act
+        let count = pruner.prune(&mut image, None);
+
+        // Assert
+        assert_eq!(1, count);
+        assert_eq!(vec!["#...#", ".#.#.", "..#..", "....."], to_ascii(&image));
+    }
+
+    #[test]
+    fn spur_pruner_iterative_test() {
+        // Arrange
+        let rows = [
+            ".....#.#......", //
+            "......#.......", //
+            "......#.......", //
+            "##############", //
+        ];
+        let pruner = SpurPruner::new(SpurLength::Pixels(3.5), AdjacencyMode::Eight, false);
+        let iterative_pruner = SpurPruner::new(SpurLength::Pixels(3.5), AdjacencyMode::Eight, true);
+        let mut image = from_ascii(&rows);
+        let mut iterative_image = from_ascii(&rows);
+
+        // Act
+        pruner.prune(&mut image, None);
+        iterative_pruner.prune(&mut iterative_image, None);
+
+        // Assert
+        assert_eq!(
+            vec![
+                ".......#......",
+                "......#.......",
+                "......#.......",
+                "##############"
+            ],
+            to_ascii(&image)
+        );
+        assert_eq!(
+            vec![
+                "..............",
+                "..............",
+                "..............",
+                "##############"
+            ],
+            to_ascii(&iterative_image)
+        );
+    }
+
+    #[test]
+    fn spur_pruner_stroke_width_test() {
+        // Arrange
+        let shape = from_ascii(&[
+            "..............", //
+            ".############.", //
+            ".############.", //
+            ".############.", //
+            ".############.", //
+            ".############.", //
+            "..............", //
+        ]);
+        let skeleton = from_ascii(&[
+            "..............", //
+            "......#.......", //
+            "......#.......", //
+            "..##########..", //
+            "..............", //
+            "..............", //
+            "..............", //
+        ]);
+        let by_width = SpurPruner::new(SpurLength::StrokeWidth(0.5), AdjacencyMode::Eight, false);
+        let by_pixels = SpurPruner::new(SpurLength::Pixels(1.5), AdjacencyMode::Eight, false);
+        let mut width_image = skeleton.clone();
+        let mut pixels_image = skeleton.clone();
+
+        // Act
+        by_width.prune(&mut width_image, Some(&shape));
+        by_pixels.prune(&mut pixels_image, Some(&shape));
+
+        // Assert
+        assert_eq!(10, width_image.count_fg());
+        assert_eq!(to_ascii(&skeleton), to_ascii(&pixels_image));
+    }
+
+    #[test]
+    fn pruned_skeletonizer_test() {
+        // Arrange
+        let shape = from_ascii(&[
+            "...#........#.......", //
+            "...#........#.......", //
+            "...#........#.......", //
+            ".##################.", //
+            ".##################.", //
+            ".##################.", //
+            ".##################.", //
+            ".##################.", //
+            ".##################.", //
+            "....................", //
+        ]);
+        let pruner = SpurPruner::new(SpurLength::StrokeWidth(1.0), AdjacencyMode::Eight, true);
+        let skeletonizer = PrunedSkeletonizer::new(ZhangSuenSkeletonizer::new(), pruner);
+        let mut image = shape.clone();
+        let mut expected = shape.clone();
+        ZhangSuenSkeletonizer::new().process(&mut expected);
+        let unpruned_count = expected.count_fg();
+
+        // Act
+        skeletonizer.process(&mut image);
+
+        // Assert
+        assert_eq!(1, pruner.prune(&mut expected, Some(&shape)));
+        assert_eq!(to_ascii(&expected), to_ascii(&image));
+        assert!(image.count_fg() < unpruned_count);
+        assert_eq!(
+            0,
+            SkeletonGraph::new(&image, AdjacencyMode::Eight)
+                .junctions()
+                .count()
+        );
+    }
+}
@@ -0,0 +1,370 @@
+// vectorizer.rs - Polylines along the branches of a skeleton
+// Copyright (C) 2019 Denis Karpovskiy
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use crate::binary_image::BinaryImage;
+use crate::contours::{simplify_closed, simplify_open};
+use crate::skeleton_graph::SkeletonGraph;
+use crate::skeletonizers::AdjacencyMode;
+use std::fmt::Write;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Polyline {
+    points: Vec<(f64, f64)>,
+    is_closed: bool,
+}
+
+impl Polyline {
+    pub fn new(points: Vec<(f64, f64)>, is_closed: bool) -> Self {
+        Polyline { points, is_closed }
+    }
+
+    // For a closed polyline the last point is not repeated.
+    pub fn points(&self) -> &[(f64, f64)] {
+        &self.points
+    }
+
+    pub fn is_closed(&self) -> bool {
+        self.is_closed
+    }
+
+    pub fn length(&self) -> f64 {
+        let mut length: f64 = self
+            .points
+            .windows(2)
+            .map(|pair| (pair[1].0 - pair[0].0).hypot(pair[1].1 - pair[0].1))
+            .sum();
+
+        if self.is_closed && self.points.len() > 1 {
+            let (first, last) = (self.points[0], self.points[self.points.len() - 1]);
+            length += (first.0 - last.0).hypot(first.1 - last.1);
+        }
+
+        length
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VectorFormat {
+    Svg,
+    GeoJson,
+    Json,
+}
+
+// Turns every branch of a skeleton between its junctions and endpoints into
+// a polyline through the pixel positions, the isolated pixels are skipped.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SkeletonVectorizer {
+    mode: AdjacencyMode,
+    smoothing_passes: usize,
+    epsilon: f64,
+}
+
+impl SkeletonVectorizer {
+    // Every smoothing pass moves the inner points to the average of them and
+    // their neighbours, then the points not needed to stay within `epsilon`
+    // pixels of the polyline are dropped. Zero turns either step off.
+    pub fn new(mode: AdjacencyMode, smoothing_passes: usize, epsilon: f64) -> Self {
+        SkeletonVectorizer {
+            mode,
+            smoothing_passes,
+            epsilon,
+        }
+    }
+
+    pub fn vectorize(&self, image: &BinaryImage) -> Vec<Polyline> {
+        let graph = SkeletonGraph::new(image, self.mode);
+
+        graph
+            .edges()
+            .map(|edge| {
+                let mut pixels = edge.pixels().to_vec();
+
+                // the branches of a junction meet at its position
+                let start = graph.node(edge.start()).position();
+                let end = graph.node(edge.end()).position();
+                if pixels[0] != start {
+                    pixels.insert(0, start);
+                }
+                if pixels[pixels.len() - 1] != end {
+                    pixels.push(end);
+                }
+
+                let is_closed = edge.is_loop() && graph.node(edge.start()).degree() == 2;
+                if is_closed {
+                    pixels.pop();
+                }
+
+                let points = pixels.iter().map(|&(x, y)| (x as f64, y as f64)).collect();
+                self.refine(Polyline::new(points, is_closed))
+            })
+            .collect()
+    }
+
+    pub fn vectorize_to_string(&self, image: &BinaryImage, format: VectorFormat) -> String {
+        let polylines = self.vectorize(image);
+
+        match format {
+            VectorFormat::Svg => polylines_to_svg(&polylines, image.width(), image.height()),
+            VectorFormat::GeoJson => polylines_to_geojson(&polylines),
+            VectorFormat::Json => polylines_to_json(&polylines),
+        }
+    }
+
+    fn refine(&self, mut polyline: Polyline) -> Polyline {
+        for _ in 0..self.smoothing_passes {
+            polyline.points = smooth(&polyline.points, polyline.is_closed);
+        }
+
+        if self.epsilon > 0.0 {
+            let kept = if polyline.is_closed {
+                simplify_closed(&polyline.points, self.epsilon)
+            } else {
+                simplify_open(&polyline.points, self.epsilon)
+            };
+            polyline.points = kept.into_iter().map(|i| polyline.points[i]).collect();
+        }
+
+        polyline
+    }
+}
+
+// The ends of an open polyline stay where they are.
+fn smooth(points: &[(f64, f64)], is_closed: bool) -> Vec<(f64, f64)> {
+    let count = points.len();
+    if count < 3 {
+        return points.to_vec();
+    }
+
+    (0..count)
+        .map(|i| {
+            if !is_closed && (i == 0 || i == count - 1) {
+                return points[i];
+            }
+
+            let previous = points[(i + count - 1) % count];
+            let next = points[(i + 1) % count];
+            (
+                (previous.0 + points[i].0 + next.0) / 3.0,
+                (previous.1 + points[i].1 + next.1) / 3.0,
+            )
+        })
+        .collect()
+}
+
+// The points are the centers of the pixels, as in `Contour::to_svg_path`.
+pub fn polylines_to_svg(polylines: &[Polyline], width: usize, height: usize) -> String {
+    let mut svg = format!(
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{0}\" height=\"{1}\" \
+         viewBox=\"0 0 {0} {1}\">",
+        width, height
+    );
+
+    for polyline in polylines {
+        svg.push_str("<path fill=\"none\" stroke=\"black\" d=\"");
+        for (i, (x, y)) in polyline.points.iter().enumerate() {
+            let command = if i == 0 { 'M' } else { 'L' };
+            write!(svg, "{}{} {} ", command, x + 0.5, y + 0.5).unwrap();
+        }
+        if polyline.is_closed {
+            svg.push('Z');
+        }
+        svg.push_str("\"/>");
+    }
+    svg.push_str("</svg>");
+
+    svg
+}
+
+// A feature collection of line strings in pixel coordinates, a closed
+// polyline ends with its first point.
+pub fn polylines_to_geojson(polylines: &[Polyline]) -> String {
+    let mut json = String::from("{\"type\":\"FeatureCollection\",\"features\":[");
+    for (i, polyline) in polylines.iter().enumerate() {
+        if i > 0 {
+            json.push(',');
+        }
+
+        let mut points = polyline.points.clone();
+        if polyline.is_closed && !points.is_empty() {
+            points.push(points[0]);
+        }
+
+        write!(
+            json,
+            "{{\"type\":\"Feature\",\"properties\":{{\"closed\":{}}},\
+             \"geometry\":{{\"type\":\"LineString\",\"coordinates\":{}}}}}",
+            polyline.is_closed,
+            points_to_json(&points)
+        )
+        .unwrap();
+    }
+    json.push_str("]}");
+
+    json
+}
+
+pub fn polylines_to_json(polylines: &[Polyline]) -> String {
+    let mut json = String::from("{\"polylines\":[");
+    for (i, polyline) in polylines.iter().enumerate() {
+        if i > 0 {
+            json.push(',');
+        }
+
+        write!(
+            json,
+            "{{\"closed\":{},\"points\":{}}}",
+            polyline.is_closed,
+            points_to_json(&polyline.points)
+        )
+        .unwrap();
+    }
+    json.push_str("]}");
+
+    json
+}
+
+fn points_to_json(points: &[(f64, f64)]) -> String {
+    let points: Vec<String> = points
+        .iter()
+        .map(|(x, y)| format!("[{},{}]", x, y))
+        .collect();
+
+    format!("[{}]", points.join(","))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::skeletonizers::test_shapes::from_ascii;
+
+    #[test]
+    fn vectorize_junction_test() {
+        // Arrange
+        let image = from_ascii(&[
+            "#....#", //
+            ".#..#.", //
+            "..##..", //
+            "..##..", //
+            ".#..#.", //
+            "#....#", //
+        ]);
+        let vectorizer = SkeletonVectorizer::new(AdjacencyMode::Eight, 0, 0.0);
+
+        // Act
+        let polylines = vectorizer.vectorize(&image);
+
+        // Assert
+        assert_eq!(4, polylines.len());
+        for polyline in polylines.iter() {
+            assert!(!polyline.is_closed());
+            let points = polyline.points();
+            assert!(points[0] == (2.0, 2.0) || points[points.len() - 1] == (2.0, 2.0));
+        }
+    }
+
+    #[test]
+    fn vectorize_closed_test() {
+        // Arrange
+        let image = from_ascii(&[
+            ".###.", //
+            "#...#", //
+            "#...#", //
+            ".###.", //
+        ]);
+        let vectorizer = SkeletonVectorizer::new(AdjacencyMode::Eight, 0, 0.0);
+
+        // Act
+        let polylines = vectorizer.vectorize(&image);
+
+        // Assert
+        assert_eq!(1, polylines.len());
+        assert!(polylines[0].is_closed());
+        assert_eq!(10, polylines[0].points().len());
+        assert!((polylines[0].length() - (6.0 + 4.0 * 2f64.sqrt())).abs() < 1e-9);
+    }
+
+    #[test]
+    fn vectorize_simplified_test() {
+        // Arrange
+        let image = from_ascii(&[
+            "#.........", //
+            ".#........", //
+            "..#.......", //
+            "...#######", //
+        ]);
+        let vectorizer = SkeletonVectorizer::new(AdjacencyMode::Eight, 2, 0.5);
+
+        // Act
+        let polylines = vectorizer.vectorize(&image);
+
+        // Assert
+        assert_eq!(1, polylines.len());
+        let points = polylines[0].points();
+        assert!(points.len() >= 3 && points.len() < 6);
+        assert_eq!((0.0, 0.0), points[0]);
+        assert_eq!((9.0, 3.0), points[points.len() - 1]);
+    }
+
+    #[test]
+    fn smooth_test() {
+        // Arrange
+        let points = [(0.0, 0.0), (1.0, 3.0), (2.0, 0.0)];
+
+        // Act
+        let open = smooth(&points, false);
+        let closed = smooth(&points, true);
+
+        // Assert
+        assert_eq!(vec![(0.0, 0.0), (1.0, 1.0), (2.0, 0.0)], open);
+        assert_eq!(vec![(1.0, 1.0), (1.0, 1.0), (1.0, 1.0)], closed);
+    }
+
+    #[test]
+    fn export_test() {
+        // Arrange
+        let polylines = [
+            Polyline::new(vec![(0.0, 0.0), (2.0, 1.5)], false),
+            Polyline::new(vec![(0.0, 0.0), (1.0, 0.0), (1.0, 1.0)], true),
+        ];
+
+        // Act
+        let svg = polylines_to_svg(&polylines, 3, 2);
+        let geojson = polylines_to_geojson(&polylines);
+        let json = polylines_to_json(&polylines);
+
+        // Assert
+        assert_eq!(
+            "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"3\" height=\"2\" \
+             viewBox=\"0 0 3 2\">\
+             <path fill=\"none\" stroke=\"black\" d=\"M0.5 0.5 L2.5 2 \"/>\
+             <path fill=\"none\" stroke=\"black\" d=\"M0.5 0.5 L1.5 0.5 L1.5 1.5 Z\"/></svg>",
+            svg
+        );
+        assert_eq!(
+            "{\"type\":\"FeatureCollection\",\"features\":[\
+             {\"type\":\"Feature\",\"properties\":{\"closed\":false},\
+             \"geometry\":{\"type\":\"LineString\",\"coordinates\":[[0,0],[2,1.5]]}},\
+             {\"type\":\"Feature\",\"properties\":{\"closed\":true},\
+             \"geometry\":{\"type\":\"LineString\",\"coordinates\":[[0,0],[1,0],[1,1],[0,0]]}}]}",
+            geojson
+        );
+        assert_eq!(
+            "{\"polylines\":[{\"closed\":false,\"points\":[[0,0],[2,1.5]]},\
+             {\"closed\":true,\"points\":[[0,0],[1,0],[1,1]]}]}",
+            json
+        );
+    }
+}